@@ -0,0 +1,45 @@
+/* C interface of dot-linker, built with the `ffi` cargo feature.
+   Panics are reported as failures, they never unwind into C */
+#ifndef DOT_LINKER_H
+#define DOT_LINKER_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct DotLinkerGraphs DotLinkerGraphs;
+
+typedef struct DotLinkerEdge {
+    char *from;
+    char *to;
+    /* 0 for direct, 1 for indirect and 2 for synthetic edges */
+    uint32_t kind;
+    /* Calls seen along the edge, 1 for edges of static call graphs */
+    uint64_t count;
+} DotLinkerEdge;
+
+/* Read inputs in any supported format, directories are searched for dot files. NULL on failure */
+DotLinkerGraphs *dot_linker_load(const char *const *paths, size_t count);
+
+/* Run passes from the config file. 0 on success, -1 on failure keeping the graphs */
+int dot_linker_run_config(DotLinkerGraphs *graphs, const char *config);
+
+/* Number of graphs, 1 after a config with `link` */
+size_t dot_linker_graph_count(const DotLinkerGraphs *graphs);
+
+/* Edges of graph number `index`, released with dot_linker_edges_free. NULL for a wrong index */
+DotLinkerEdge *dot_linker_edges(const DotLinkerGraphs *graphs, size_t index, size_t *len);
+
+void dot_linker_edges_free(DotLinkerEdge *edges, size_t len);
+
+/* Also releases interned names no other graphs use */
+void dot_linker_free(DotLinkerGraphs *graphs);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -1 +1,22 @@
-pub mod linker;
+//! Library behind the `inv-call-extract` binary: reads call graphs,
+//! runs passes on them and links them into one.
+//!
+//! ```
+//! # #[cfg(feature = "dot")] {
+//! use inv_call_extract::linker::graph_link::link_all_graphs;
+//! use inv_call_extract::linker::node::CallGraph;
+//! use inv_call_extract::linker::pass::{Pass, UniqueEdgesPass};
+//!
+//! let graphs = ["digraph { a -> b }", "digraph { b -> c; b -> c }"]
+//!     .map(|dot| {
+//!         let dot = graphviz_rust::parse(dot).unwrap();
+//!         inv_call_extract::linker::conversion::graphviz_to_graph(&dot)
+//!     });
+//! let mut linked: CallGraph = link_all_graphs(&graphs);
+//! UniqueEdgesPass::default().run_pass(&mut linked).unwrap();
+//! assert_eq!(linked.node_count(), 3);
+//! assert_eq!(linked.edge_count(), 2);
+//! # }
+//! ```
+
+pub mod linker;
\ No newline at end of file
@@ -1,4 +1,63 @@
+/// Graphviz input conversion and DOT output
 pub mod conversion;
+/// Graph transformations configured in the config file
 pub mod pass;
+/// Regex match results shared by passes
+pub mod match_cache;
+/// Merging several graphs into one
 pub mod graph_link;
-pub mod config;
\ No newline at end of file
+/// Graph node type
+pub mod node;
+/// Reports about the structure of a graph
+pub mod analysis;
+/// Scoring functions by importance
+pub mod weighting;
+/// Compact read-only graph for traversals
+pub mod csr;
+/// Node names stored once for all graphs
+pub mod intern;
+/// Config file parsing
+pub mod config;
+/// Pass keywords available in the config file
+pub mod registry;
+/// Graph changes requested by external passes
+pub mod edit;
+/// Passes loaded from shared libraries
+#[cfg(feature = "plugins")]
+pub mod plugin;
+/// Passes running sandboxed WebAssembly modules
+#[cfg(feature = "wasm")]
+pub mod wasm;
+/// Passes running Rhai scripts
+#[cfg(feature = "scripting")]
+pub mod script;
+/// C interface for loading inputs and running passes
+#[cfg(feature = "ffi")]
+pub mod ffi;
+/// Python bindings
+#[cfg(feature = "python")]
+pub mod python;
+/// Graph edge kinds and call counts
+pub mod edge;
+/// Reading inputs in all supported formats
+pub mod import;
+/// Errors raised by passes
+pub mod error;
+/// Statistics of pass runs
+pub mod report;
+/// Wall time and allocations of run phases
+pub mod profile;
+/// Stopping running pipelines
+pub mod cancel;
+/// Running configured passes on input graphs
+pub mod pipeline;
+/// Reading inputs, running the config and writing outputs like the command line does
+pub mod runner;
+/// Re-linking only inputs that changed since the last run
+pub mod incremental;
+/// Path of a test's file or directory in the temp directory. The process id keeps concurrent
+/// test runs, e.g. of different feature sets, from deleting each other's files
+#[cfg(test)]
+pub(crate) fn test_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("dot-linker-test-{}-{name}", std::process::id()))
+}
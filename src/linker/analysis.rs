@@ -0,0 +1,820 @@
+use std::cmp::Reverse;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::io::Write;
+use std::sync::Arc;
+use petgraph::algo::dominators::simple_fast;
+use petgraph::algo::tarjan_scc;
+use petgraph::Direction;
+use petgraph::graph::NodeIndex;
+use petgraph::unionfind::UnionFind;
+use regex::Regex;
+use crate::linker::csr::CsrGraph;
+use crate::linker::edge::Edge;
+use crate::linker::node::{CallGraph, Node};
+
+/// Recursion cycles: strongly connected components with several nodes or a self-loop.
+/// Members of a cycle are sorted by name, cycles by decreasing size and then by the first name.
+#[must_use]
+pub fn cycles(graph: &CallGraph) -> Vec<Vec<Arc<str>>> {
+    let mut cycles = tarjan_scc(graph)
+        .into_iter()
+        .filter(|scc| scc.len() > 1 || graph.contains_edge(scc[0], scc[0]))
+        .map(|scc| {
+            let mut names = scc.into_iter().map(|idx| graph[idx].name.clone()).collect::<Vec<_>>();
+            names.sort();
+            names
+        })
+        .collect::<Vec<_>>();
+    cycles.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+    cycles
+}
+
+/// Weakly connected component of a graph
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Component {
+    /// Number of nodes
+    pub size: usize,
+    /// Node with the most edges, the first name on ties
+    pub representative: Arc<str>,
+}
+
+/// Weakly connected components, sorted by decreasing size and then by representative
+#[must_use]
+pub fn components(graph: &CallGraph) -> Vec<Component> {
+    let mut sets = UnionFind::<usize>::new(graph.node_count());
+    for edge in graph.raw_edges() {
+        sets.union(edge.source().index(), edge.target().index());
+    }
+    let mut components: Vec<Option<(Component, usize)>> = vec![None; graph.node_count()];
+    for idx in graph.node_indices() {
+        let name = &graph[idx].name;
+        let degree = graph.neighbors_undirected(idx).count();
+        match &mut components[sets.find(idx.index())] {
+            Some((component, edges)) => {
+                component.size += 1;
+                if degree > *edges || (degree == *edges && *name < component.representative) {
+                    component.representative = name.clone();
+                    *edges = degree;
+                }
+            }
+            slot @ None => *slot = Some((Component { size: 1, representative: name.clone() }, degree)),
+        }
+    }
+    let mut components = components.into_iter().flatten().map(|(component, _)| component).collect::<Vec<_>>();
+    components.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.representative.cmp(&b.representative)));
+    components
+}
+
+/// Numbers of nodes with every incoming and outgoing degree.
+/// Parallel edges count separately, like in `cut_deg` without `distinct`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DegreeHistogram {
+    pub incoming: BTreeMap<usize, usize>,
+    pub outgoing: BTreeMap<usize, usize>,
+}
+
+impl DegreeHistogram {
+    /// Degrees present in either direction, in increasing order
+    fn degrees(&self) -> impl Iterator<Item = usize> + '_ {
+        let mut degrees = self.incoming.keys().chain(self.outgoing.keys()).copied().collect::<Vec<_>>();
+        degrees.sort_unstable();
+        degrees.dedup();
+        degrees.into_iter()
+    }
+
+    fn counts(&self, degree: usize) -> (usize, usize) {
+        (
+            self.incoming.get(&degree).copied().unwrap_or_default(),
+            self.outgoing.get(&degree).copied().unwrap_or_default()
+        )
+    }
+
+    /// Write `degree,in,out` lines with a header
+    pub fn write_csv(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(out, "degree,in,out")?;
+        for degree in self.degrees() {
+            let (incoming, outgoing) = self.counts(degree);
+            writeln!(out, "{degree},{incoming},{outgoing}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for DegreeHistogram {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{:>8} {:>8} {:>8}", "degree", "in", "out")?;
+        for degree in self.degrees() {
+            let (incoming, outgoing) = self.counts(degree);
+            writeln!(f, "{degree:>8} {incoming:>8} {outgoing:>8}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Histograms of incoming and outgoing degrees of all nodes
+#[must_use]
+pub fn degree_histogram(graph: &CallGraph) -> DegreeHistogram {
+    let mut histogram = DegreeHistogram::default();
+    for idx in graph.node_indices() {
+        *histogram.incoming.entry(graph.edges_directed(idx, Direction::Incoming).count()).or_default() += 1;
+        *histogram.outgoing.entry(graph.edges_directed(idx, Direction::Outgoing).count()).or_default() += 1;
+    }
+    histogram
+}
+
+/// Path found by [`hot_paths`], from an entry to a target
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HotPath {
+    pub nodes: Vec<NodeIndex>,
+    /// Smallest call count of a step, the calls every step of the path saw at least.
+    /// Counts of parallel edges between two nodes add up
+    pub weight: u64,
+}
+
+impl HotPath {
+    /// Number of steps
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.nodes.len().saturating_sub(1)
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// The `k` best simple paths from any of `entries` to any of `targets`, best first.
+/// Graphs with call counts rank paths by [`HotPath::weight`]
+/// and then by length, other graphs by length only. Paths end at the first target after their entry.
+/// Paths are found with Yen's algorithm, every one deviating from a path found before,
+/// so the work grows with `k` and path length, not with the number of paths in the graph.
+#[must_use]
+pub fn hot_paths(graph: &CallGraph, entries: &[NodeIndex], targets: &[NodeIndex], k: usize) -> Vec<HotPath> {
+    let mut multiplicity: BTreeMap<(NodeIndex, NodeIndex), u64> = BTreeMap::new();
+    for edge in graph.raw_edges() {
+        *multiplicity.entry((edge.source(), edge.target())).or_default() += edge.weight.count;
+    }
+    let weighted = multiplicity.values().any(|&count| count > 1);
+    // Only nodes reaching a target can be on a path
+    let useful = CsrGraph::from_graph(graph).reversed().reachable(targets.iter().copied());
+    let mut callees = vec![vec![]; graph.node_count()];
+    for (&(from, to), &count) in &multiplicity {
+        if useful[to.index()] {
+            callees[from.index()].push((to, count));
+        }
+    }
+    let mut is_target = vec![false; graph.node_count()];
+    for target in targets {
+        is_target[target.index()] = true;
+    }
+    let entries = entries.iter().copied().filter(|entry| useful[entry.index()]).collect::<BTreeSet<_>>();
+    let mut counts = multiplicity.values().copied().collect::<Vec<_>>();
+    counts.sort_unstable();
+    counts.dedup();
+    let search = PathSearch { callees: &callees, is_target: &is_target, entries: &entries, counts, weighted };
+    let weight_of = |nodes: &[NodeIndex]| nodes.windows(2)
+        .map(|step| multiplicity[&(step[0], step[1])])
+        .min()
+        .unwrap_or(u64::MAX);
+    // Smallest key first: heaviest, then shortest, then by nodes
+    let key = |path: &HotPath| (Reverse(if weighted { path.weight } else { 0 }), path.nodes.len());
+
+    let mut found: Vec<HotPath> = vec![];
+    let mut candidates = BTreeSet::new();
+    if let Some(nodes) = search.best(None, u64::MAX, &HashSet::new(), &HashSet::new()) {
+        let path = HotPath { weight: weight_of(&nodes), nodes };
+        candidates.insert((key(&path), path.nodes.clone()));
+    }
+    while found.len() < k {
+        let Some((_, nodes)) = candidates.pop_first() else {
+            break;
+        };
+        found.push(HotPath { weight: weight_of(&nodes), nodes });
+        let last = &found[found.len() - 1].nodes;
+        // Deviate from the new path after each of its prefixes, the empty one leaving from another entry
+        for spur in 0..last.len() {
+            let root = &last[..spur];
+            let blocked_steps = found.iter()
+                .filter(|path| path.nodes.len() > spur && path.nodes[..spur] == *root)
+                .map(|path| (root.last().copied(), path.nodes[spur]))
+                .collect::<HashSet<_>>();
+            let blocked_nodes = root[..spur.saturating_sub(1)].iter().copied().collect::<HashSet<_>>();
+            let Some(tail) = search.best(root.last().copied(), weight_of(root), &blocked_nodes, &blocked_steps) else {
+                continue;
+            };
+            let nodes = root[..spur.saturating_sub(1)].iter().copied().chain(tail).collect::<Vec<_>>();
+            let path = HotPath { weight: weight_of(&nodes), nodes };
+            candidates.insert((key(&path), path.nodes.clone()));
+        }
+    }
+    found
+}
+
+/// Best path search of [`hot_paths`] on callees reaching a target
+struct PathSearch<'a> {
+    callees: &'a [Vec<(NodeIndex, u64)>],
+    is_target: &'a [bool],
+    entries: &'a BTreeSet<NodeIndex>,
+    /// Distinct call counts of steps, ascending
+    counts: Vec<u64>,
+    weighted: bool,
+}
+
+impl PathSearch<'_> {
+    /// Heaviest and then shortest path from `start`, or from any entry without it, to the first target.
+    /// Steps are counted as at most `cap` calls, the weight of the path leading to `start`.
+    /// Paths avoid `blocked_nodes` and steps in `blocked_steps`, entries are blocked as steps from `None`
+    fn best(
+        &self,
+        start: Option<NodeIndex>,
+        cap: u64,
+        blocked_nodes: &HashSet<NodeIndex>,
+        blocked_steps: &HashSet<(Option<NodeIndex>, NodeIndex)>
+    ) -> Option<Vec<NodeIndex>> {
+        let starts = match start {
+            Some(start) => vec![start],
+            None => self.entries.iter()
+                .copied()
+                .filter(|&entry| !blocked_steps.contains(&(None, entry)))
+                .collect(),
+        };
+        let mut path = self.shortest(&starts, 0, blocked_nodes, blocked_steps)?;
+        if !self.weighted {
+            return Some(path);
+        }
+        // The best weight is the highest lower bound on calls still reaching a target
+        let mut bounds = [0].into_iter().chain(self.counts.iter().map(|&count| count.min(cap))).collect::<Vec<_>>();
+        bounds.dedup();
+        let (mut reached, mut unreached) = (0, bounds.len());
+        while unreached - reached > 1 {
+            let middle = (reached + unreached) / 2;
+            if let Some(heavier) = self.shortest(&starts, bounds[middle], blocked_nodes, blocked_steps) {
+                path = heavier;
+                reached = middle;
+            } else {
+                unreached = middle;
+            }
+        }
+        Some(path)
+    }
+
+    /// Shortest path from any of `starts` over steps of at least `min_count` calls
+    fn shortest(
+        &self,
+        starts: &[NodeIndex],
+        min_count: u64,
+        blocked_nodes: &HashSet<NodeIndex>,
+        blocked_steps: &HashSet<(Option<NodeIndex>, NodeIndex)>
+    ) -> Option<Vec<NodeIndex>> {
+        // Paths of starts that are targets can't return to them, so they may not share nodes with others
+        let (alone, shared) = starts.iter().partition::<Vec<_>, _>(|start| self.is_target[start.index()]);
+        let mut best = self.breadth_first(&shared, min_count, blocked_nodes, blocked_steps);
+        for start in alone {
+            if let Some(path) = self.breadth_first(&[start], min_count, blocked_nodes, blocked_steps) {
+                if best.as_ref().is_none_or(|best| path.len() < best.len()) {
+                    best = Some(path);
+                }
+            }
+        }
+        best
+    }
+
+    fn breadth_first(
+        &self,
+        starts: &[NodeIndex],
+        min_count: u64,
+        blocked_nodes: &HashSet<NodeIndex>,
+        blocked_steps: &HashSet<(Option<NodeIndex>, NodeIndex)>
+    ) -> Option<Vec<NodeIndex>> {
+        let mut parent: HashMap<NodeIndex, Option<NodeIndex>> = starts.iter().map(|&start| (start, None)).collect();
+        let mut queue = starts.iter().copied().collect::<VecDeque<_>>();
+        let path_to = |parent: &HashMap<NodeIndex, Option<NodeIndex>>, mut node: NodeIndex| {
+            let mut nodes = vec![node];
+            while let Some(&Some(previous)) = parent.get(&node) {
+                nodes.push(previous);
+                node = previous;
+            }
+            nodes.reverse();
+            nodes
+        };
+        while let Some(node) = queue.pop_front() {
+            for &(next, count) in &self.callees[node.index()] {
+                if count < min_count || blocked_nodes.contains(&next) || blocked_steps.contains(&(Some(node), next)) {
+                    continue;
+                }
+                if self.is_target[next.index()] {
+                    // Entries may be targets too, but a path can't return to its own
+                    let mut nodes = path_to(&parent, node);
+                    if nodes[0] != next {
+                        nodes.push(next);
+                        return Some(nodes);
+                    }
+                } else if let Entry::Vacant(entry) = parent.entry(next) {
+                    entry.insert(Some(node));
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Nodes and edges of `graph` on any of `paths`, parallel edges of a step included, in the order of `graph`
+#[must_use]
+pub fn paths_subgraph(graph: &CallGraph, paths: &[HotPath]) -> CallGraph {
+    let steps = paths.iter()
+        .flat_map(|path| path.nodes.windows(2).map(|step| (step[0], step[1])))
+        .collect::<HashSet<_>>();
+    let nodes = paths.iter().flat_map(|path| path.nodes.iter().copied()).collect::<HashSet<_>>();
+    graph.filter_map(
+        |idx, node| nodes.contains(&idx).then(|| node.clone()),
+        |idx, kind| {
+            let (source, target) = graph.edge_endpoints(idx)?;
+            steps.contains(&(source, target)).then_some(*kind)
+        }
+    )
+}
+
+/// Function reaching all targets of [`common_ancestors`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommonAncestor {
+    pub node: NodeIndex,
+    /// Distance to the farthest target
+    pub distance: usize,
+    /// Sum of distances to all targets
+    pub total_distance: usize,
+}
+
+/// Nodes from which all `targets` are reachable, targets reaching all others included.
+/// Sorted by the distance to the farthest target, then by the sum of distances and by name
+#[must_use]
+pub fn common_ancestors(graph: &CallGraph, targets: &[NodeIndex]) -> Vec<CommonAncestor> {
+    if targets.is_empty() {
+        return vec![];
+    }
+    let callers = CsrGraph::from_graph(graph).reversed();
+    let mut ancestors = graph.node_indices()
+        .map(|node| CommonAncestor { node, distance: 0, total_distance: 0 })
+        .collect::<Vec<_>>();
+    let mut reaches_all = vec![true; graph.node_count()];
+    for &target in targets.iter().collect::<BTreeSet<_>>() {
+        for (idx, distance) in callers.distances([target]).into_iter().enumerate() {
+            match distance {
+                Some(distance) => {
+                    ancestors[idx].distance = ancestors[idx].distance.max(distance);
+                    ancestors[idx].total_distance += distance;
+                }
+                None => reaches_all[idx] = false,
+            }
+        }
+    }
+    let mut ancestors = ancestors.into_iter()
+        .filter(|ancestor| reaches_all[ancestor.node.index()])
+        .collect::<Vec<_>>();
+    ancestors.sort_by(|a, b| (a.distance, a.total_distance, &graph[a.node].name)
+        .cmp(&(b.distance, b.total_distance, &graph[b.node].name)));
+    ancestors
+}
+
+/// Whether `nodes[j]` is reachable from `nodes[i]` for every `i` and `j`, nodes reach themselves
+#[must_use]
+pub fn reachability_matrix(graph: &CallGraph, nodes: &[NodeIndex]) -> Vec<Vec<bool>> {
+    let csr = CsrGraph::from_graph(graph);
+    nodes.iter()
+        .map(|&from| {
+            let reached = csr.reachable([from]);
+            nodes.iter().map(|to| reached[to.index()]).collect()
+        })
+        .collect()
+}
+
+/// CSV field, quoted if it contains separators or quotes
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Write [`reachability_matrix`] of `nodes` as CSV: a header with node names,
+/// then a row per caller starting with its name, `1` for reached nodes and `0` for others
+pub fn write_reachability_csv(graph: &CallGraph, nodes: &[NodeIndex], out: &mut impl Write) -> io::Result<()> {
+    let names = nodes.iter().map(|&idx| csv_field(&graph[idx].name)).collect::<Vec<_>>();
+    writeln!(out, ",{}", names.join(","))?;
+    for (name, row) in names.iter().zip(reachability_matrix(graph, nodes)) {
+        let row = row.iter().map(|&reached| if reached { "1" } else { "0" }).collect::<Vec<_>>();
+        writeln!(out, "{name},{}", row.join(","))?;
+    }
+    Ok(())
+}
+
+/// Call depths of a function below the entries of [`call_depths`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CallDepth {
+    /// Edges on the shortest path from an entry
+    pub min: usize,
+    /// Edges on the longest path from an entry, with every recursion cycle walked once
+    pub max: usize,
+}
+
+/// Call depths of every node reachable from `entries`, indexed by node index, `None` for unreachable ones.
+/// Maximum depths are longest paths between recursion cycles, which are contracted to a single step:
+/// functions of a cycle get the depth of the cycle, or their minimum depth if it is larger
+#[must_use]
+pub fn call_depths(graph: &CallGraph, entries: &[NodeIndex]) -> Vec<Option<CallDepth>> {
+    let csr = CsrGraph::from_graph(graph);
+    let mut min = vec![None; graph.node_count()];
+    let mut queue = VecDeque::new();
+    for &entry in entries {
+        if min[entry.index()].is_none() {
+            min[entry.index()] = Some(0);
+            queue.push_back(entry);
+        }
+    }
+    while let Some(node) = queue.pop_front() {
+        let depth = min[node.index()].map(|depth: usize| depth + 1);
+        for next in csr.neighbors(node) {
+            if min[next.index()].is_none() {
+                min[next.index()] = depth;
+                queue.push_back(next);
+            }
+        }
+    }
+
+    // Tarjan's algorithm lists components callees first
+    let components = tarjan_scc(graph);
+    let mut component_of = vec![0; graph.node_count()];
+    for (i, component) in components.iter().enumerate() {
+        for node in component {
+            component_of[node.index()] = i;
+        }
+    }
+    let mut max: Vec<Option<usize>> = vec![None; components.len()];
+    for &entry in entries {
+        max[component_of[entry.index()]].get_or_insert(0);
+    }
+    for (i, component) in components.iter().enumerate().rev() {
+        let Some(depth) = max[i] else { continue };
+        for &node in component {
+            for next in csr.neighbors(node) {
+                let next = component_of[next.index()];
+                if next != i {
+                    max[next] = Some(max[next].map_or(depth + 1, |old| old.max(depth + 1)));
+                }
+            }
+        }
+    }
+    graph.node_indices()
+        .map(|idx| min[idx.index()].map(|min| CallDepth {
+            min,
+            max: max[component_of[idx.index()]].map_or(min, |max| max.max(min)),
+        }))
+        .collect()
+}
+
+/// Sizes of two sets and their intersection
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Overlap {
+    pub common: usize,
+    pub only_first: usize,
+    pub only_second: usize,
+}
+
+impl Overlap {
+    fn of<T: Eq + std::hash::Hash>(first: &HashSet<T>, second: &HashSet<T>) -> Self {
+        let common = first.intersection(second).count();
+        Self { common, only_first: first.len() - common, only_second: second.len() - common }
+    }
+
+    /// Size of the intersection over the size of the union, 1 for two empty sets
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn jaccard(&self) -> f64 {
+        let union = self.common + self.only_first + self.only_second;
+        if union == 0 { 1.0 } else { self.common as f64 / union as f64 }
+    }
+}
+
+/// How much two graphs share, see [`similarity`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Similarity {
+    /// Nodes by name
+    pub nodes: Overlap,
+    /// Caller and callee name pairs, parallel edges and edge kinds are ignored
+    pub edges: Overlap,
+    /// Weakly connected components of edges present in one graph only, largest first
+    pub regions: Vec<Component>,
+}
+
+/// Compare two graphs by node names and by edges between names.
+/// Regions group nodes and edges present in one graph only, so one changed subsystem is one region.
+#[must_use]
+pub fn similarity(first: &CallGraph, second: &CallGraph) -> Similarity {
+    let names = |graph: &CallGraph| graph.node_weights().map(|node| node.name.clone()).collect::<HashSet<_>>();
+    let edges = |graph: &CallGraph| graph.raw_edges()
+        .iter()
+        .map(|edge| (graph[edge.source()].name.clone(), graph[edge.target()].name.clone()))
+        .collect::<HashSet<_>>();
+    let (first_names, second_names) = (names(first), names(second));
+    let (first_edges, second_edges) = (edges(first), edges(second));
+
+    let mut diff = CallGraph::new();
+    let mut indices = HashMap::new();
+    let mut index = |diff: &mut CallGraph, name: &Arc<str>| *indices.entry(name.clone())
+        .or_insert_with(|| diff.add_node(Node::from(&**name)));
+    let mut changed_names = first_names.symmetric_difference(&second_names).collect::<Vec<_>>();
+    changed_names.sort();
+    for name in changed_names {
+        index(&mut diff, name);
+    }
+    let mut changed_edges = first_edges.symmetric_difference(&second_edges).collect::<Vec<_>>();
+    changed_edges.sort();
+    for (from, to) in changed_edges {
+        let (from, to) = (index(&mut diff, from), index(&mut diff, to));
+        diff.add_edge(from, to, Edge::default());
+    }
+    Similarity {
+        nodes: Overlap::of(&first_names, &second_names),
+        edges: Overlap::of(&first_edges, &second_edges),
+        regions: components(&diff),
+    }
+}
+
+/// Syscall entry points of Linux kernels: architecture wrappers, `SYSCALL_DEFINE` sign extension helpers
+/// and their compat variants. The syscall name is `compat_` followed by the name for compat variants
+pub const SYSCALL_ENTRY_PATTERN: &str = r"^__(?:x64|x32|ia32|arm64|riscv|s390x|se)_(compat_)?sys_(\w+)$";
+
+/// What one syscall reaches, see [`syscall_surface`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SyscallSurface {
+    /// Syscall name without entry point prefixes, e.g. `read` or `compat_ioctl`
+    pub syscall: String,
+    /// Entry points of the syscall in the graph
+    pub entries: Vec<NodeIndex>,
+    /// Number of functions reachable from any entry point, entry points included
+    pub reachable: usize,
+    /// Targets reachable from the syscall, in the order given
+    pub targets: Vec<NodeIndex>,
+}
+
+/// Reachable functions and `targets` of every syscall with an entry point matching [`SYSCALL_ENTRY_PATTERN`],
+/// sorted by syscall name
+#[must_use]
+pub fn syscall_surface(graph: &CallGraph, targets: &[NodeIndex]) -> Vec<SyscallSurface> {
+    let pattern = Regex::new(SYSCALL_ENTRY_PATTERN).expect("valid syscall entry pattern");
+    let mut syscalls: BTreeMap<String, Vec<NodeIndex>> = BTreeMap::new();
+    for idx in graph.node_indices() {
+        if let Some(captures) = pattern.captures(&graph[idx].name) {
+            let compat = captures.get(1).map_or("", |compat| compat.as_str());
+            syscalls.entry(format!("{compat}{}", &captures[2])).or_default().push(idx);
+        }
+    }
+    let csr = CsrGraph::from_graph(graph);
+    syscalls.into_iter()
+        .map(|(syscall, entries)| {
+            let reached = csr.reachable(entries.iter().copied());
+            SyscallSurface {
+                syscall,
+                entries,
+                reachable: reached.iter().filter(|&&r| r).count(),
+                targets: targets.iter().copied().filter(|target| reached[target.index()]).collect(),
+            }
+        })
+        .collect()
+}
+
+/// Functions every path from `entry` to `target` goes through, from `entry` to `target` both included:
+/// the dominators of `target` in the graph reachable from `entry`. `None` if `entry` doesn't reach `target`
+#[must_use]
+pub fn must_pass_through(graph: &CallGraph, entry: NodeIndex, target: NodeIndex) -> Option<Vec<NodeIndex>> {
+    let dominators = simple_fast(graph, entry);
+    let mut chain = dominators.dominators(target)?.collect::<Vec<_>>();
+    chain.reverse();
+    Some(chain)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cycles() {
+        let mut graph = CallGraph::new();
+        let [a, b, c, d, e, f] = ["a", "b", "c", "d", "e", "f"].map(|name| graph.add_node(Node::from(name)));
+        for (from, to) in [(a, b), (b, a), (c, d), (d, e), (e, c), (e, f), (f, f)] {
+            graph.add_edge(from, to, Edge::default());
+        }
+        let cycles = cycles(&graph);
+        let cycles = cycles.iter()
+            .map(|cycle| cycle.iter().map(|name| &**name).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(cycles, [vec!["c", "d", "e"], vec!["a", "b"], vec!["f"]]);
+    }
+
+    #[test]
+    fn test_components() {
+        let mut graph = CallGraph::new();
+        let [a, b, c, d, e, _] = ["a", "b", "c", "d", "e", "f"].map(|name| graph.add_node(Node::from(name)));
+        for (from, to) in [(b, c), (a, b), (d, b), (e, e)] {
+            graph.add_edge(from, to, Edge::default());
+        }
+        let components = components(&graph)
+            .into_iter()
+            .map(|component| (component.size, component.representative.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(components, [(4, "b".to_string()), (1, "e".to_string()), (1, "f".to_string())]);
+    }
+
+    #[test]
+    fn test_degree_histogram() {
+        let mut graph = CallGraph::new();
+        let [a, b, c] = ["a", "b", "c"].map(|name| graph.add_node(Node::from(name)));
+        for (from, to) in [(a, b), (a, b), (a, c), (b, c)] {
+            graph.add_edge(from, to, Edge::default());
+        }
+        let histogram = degree_histogram(&graph);
+        assert_eq!(histogram.incoming, BTreeMap::from([(0, 1), (2, 2)]));
+        assert_eq!(histogram.outgoing, BTreeMap::from([(0, 1), (1, 1), (3, 1)]));
+
+        let mut csv = vec![];
+        histogram.write_csv(&mut csv).unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap(), "degree,in,out\n0,1,1\n1,0,1\n2,2,0\n3,0,1\n");
+        assert_eq!(histogram.to_string().lines().nth(3).unwrap(), "       2        2        0");
+    }
+
+    #[test]
+    fn test_common_ancestors() {
+        let mut graph = CallGraph::new();
+        let [main, dispatch, read, write, log] = ["main", "dispatch", "read", "write", "log"]
+            .map(|name| graph.add_node(Node::from(name)));
+        for (from, to) in [(main, dispatch), (dispatch, read), (dispatch, write), (write, log), (main, log)] {
+            graph.add_edge(from, to, Edge::default());
+        }
+        let ancestors = common_ancestors(&graph, &[read, log])
+            .into_iter()
+            .map(|ancestor| (&*graph[ancestor.node].name, ancestor.distance, ancestor.total_distance))
+            .collect::<Vec<_>>();
+        assert_eq!(ancestors, [("dispatch", 2, 3), ("main", 2, 3)]);
+        assert_eq!(common_ancestors(&graph, &[write, log]).len(), 3);
+        assert!(common_ancestors(&graph, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_reachability_matrix() {
+        let mut graph = CallGraph::new();
+        let [a, b, c] = ["a", "b", "c,d"].map(|name| graph.add_node(Node::from(name)));
+        graph.add_edge(a, b, Edge::default());
+        graph.add_edge(b, c, Edge::default());
+        assert_eq!(reachability_matrix(&graph, &[c, a]), [[true, false], [true, true]]);
+
+        let mut csv = vec![];
+        write_reachability_csv(&graph, &[a, b, c], &mut csv).unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap(), ",a,b,\"c,d\"\na,1,1,1\nb,0,1,1\n\"c,d\",0,0,1\n");
+    }
+
+    #[test]
+    fn test_call_depths() {
+        let mut graph = CallGraph::new();
+        let [main, a, b, c, d, unused] = ["main", "a", "b", "c", "d", "unused"]
+            .map(|name| graph.add_node(Node::from(name)));
+        for (from, to) in [(main, a), (a, b), (b, c), (main, c), (c, d), (d, c)] {
+            graph.add_edge(from, to, Edge::default());
+        }
+        let depths = call_depths(&graph, &[main]);
+        let depth = |idx: NodeIndex| depths[idx.index()].map(|depth| (depth.min, depth.max));
+        assert_eq!(depth(main), Some((0, 0)));
+        assert_eq!(depth(b), Some((2, 2)));
+        assert_eq!(depth(c), Some((1, 3)));
+        assert_eq!(depth(d), Some((2, 3)));
+        assert_eq!(depth(unused), None);
+    }
+
+    #[test]
+    fn test_similarity() {
+        let graph = |edges: &[(&str, &str)]| {
+            let mut graph = CallGraph::new();
+            let mut nodes = HashMap::new();
+            for (from, to) in edges {
+                let [from, to] = [from, to]
+                    .map(|name| *nodes.entry(*name).or_insert_with(|| graph.add_node(Node::from(*name))));
+                graph.add_edge(from, to, Edge::default());
+            }
+            graph
+        };
+        let old = graph(&[("main", "a"), ("a", "b"), ("main", "c"), ("c", "d"), ("d", "e")]);
+        let new = graph(&[("main", "a"), ("a", "b"), ("a", "b"), ("main", "c"), ("c", "x"), ("x", "y")]);
+        let similarity = similarity(&old, &new);
+        assert_eq!(similarity.nodes, Overlap { common: 4, only_first: 2, only_second: 2 });
+        assert_eq!(similarity.edges, Overlap { common: 3, only_first: 2, only_second: 2 });
+        assert!((similarity.edges.jaccard() - 3.0 / 7.0).abs() < 1e-9);
+        let regions = similarity.regions.iter()
+            .map(|region| (region.size, &*region.representative))
+            .collect::<Vec<_>>();
+        assert_eq!(regions, [(5, "c")]);
+        assert_eq!(Overlap::default().jaccard(), 1.0);
+    }
+
+    #[test]
+    fn test_syscall_surface() {
+        let mut graph = CallGraph::new();
+        let names = [
+            "__x64_sys_read", "__se_sys_read", "ksys_read", "vfs_read",
+            "__ia32_compat_sys_ioctl", "do_ioctl", "sys_helper"
+        ];
+        let [x64_read, se_read, ksys_read, vfs_read, compat_ioctl, do_ioctl, _] =
+            names.map(|name| graph.add_node(Node::from(name)));
+        let edges = [(x64_read, se_read), (se_read, ksys_read), (ksys_read, vfs_read), (compat_ioctl, do_ioctl)];
+        for (from, to) in edges {
+            graph.add_edge(from, to, Edge::default());
+        }
+        let surface = syscall_surface(&graph, &[do_ioctl, vfs_read]);
+        let surface = surface.iter()
+            .map(|syscall| (&*syscall.syscall, syscall.entries.len(), syscall.reachable, syscall.targets.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(surface, [("compat_ioctl", 1, 2, vec![do_ioctl]), ("read", 2, 4, vec![vfs_read])]);
+    }
+
+    #[test]
+    fn test_must_pass_through() {
+        let mut graph = CallGraph::new();
+        let [sys, check, fast, slow, hook, target, other] = ["sys", "check", "fast", "slow", "hook", "target", "other"]
+            .map(|name| graph.add_node(Node::from(name)));
+        let edges = [(sys, check), (check, fast), (check, slow), (fast, hook), (slow, hook), (hook, target)];
+        for (from, to) in edges.into_iter().chain([(other, hook)]) {
+            graph.add_edge(from, to, Edge::default());
+        }
+        assert_eq!(must_pass_through(&graph, sys, target), Some(vec![sys, check, hook, target]));
+        assert_eq!(must_pass_through(&graph, fast, target), Some(vec![fast, hook, target]));
+        assert_eq!(must_pass_through(&graph, target, sys), None);
+        assert_eq!(must_pass_through(&graph, sys, sys), Some(vec![sys]));
+    }
+
+    #[test]
+    fn test_hot_paths() {
+        let mut graph = CallGraph::new();
+        let [main, a, b, c, t, u] = ["main", "a", "b", "c", "t", "u"].map(|name| graph.add_node(Node::from(name)));
+        for (from, to) in [(main, a), (a, t), (main, b), (b, c), (c, t), (t, u), (c, main)] {
+            graph.add_edge(from, to, Edge::default());
+        }
+        let names = |graph: &CallGraph, paths: &[HotPath]| paths.iter()
+            .map(|path| path.nodes.iter().map(|&idx| &*graph[idx].name).collect::<Vec<_>>().join(" "))
+            .collect::<Vec<_>>();
+        let paths = hot_paths(&graph, &[main], &[t, u], 3);
+        assert_eq!(names(&graph, &paths), ["main a t", "main b c t"]);
+
+        // Calls counted by parallel edges outweigh length
+        for _ in 0..2 {
+            for (from, to) in [(main, b), (b, c), (c, t)] {
+                graph.add_edge(from, to, Edge::default());
+            }
+        }
+        let paths = hot_paths(&graph, &[main], &[t], 1);
+        assert_eq!(names(&graph, &paths), ["main b c t"]);
+        assert_eq!(paths[0].weight, 3);
+
+        let subgraph = paths_subgraph(&graph, &paths);
+        assert_eq!(subgraph.node_count(), 4);
+        assert_eq!(subgraph.edge_count(), 9);
+    }
+
+    #[test]
+    fn test_hot_paths_wide() {
+        // 20^6 paths, too many to explore one by one
+        let mut graph = CallGraph::new();
+        let main = graph.add_node(Node::from("main"));
+        let target = graph.add_node(Node::from("target"));
+        let mut layer = vec![main];
+        for depth in 0..6 {
+            let next = (0..20).map(|i| graph.add_node(Node::from(format!("f{depth}_{i}").as_str()))).collect::<Vec<_>>();
+            for &from in &layer {
+                for &to in &next {
+                    graph.add_edge(from, to, Edge::default());
+                }
+            }
+            layer = next;
+        }
+        for &from in &layer {
+            graph.add_edge(from, target, Edge::default());
+        }
+        let paths = hot_paths(&graph, &[main], &[target], 5);
+        assert_eq!(paths.len(), 5);
+        assert!(paths.iter().all(|path| path.len() == 7 && path.nodes[0] == main && path.nodes[7] == target));
+        assert_eq!(paths.iter().map(|path| &path.nodes).collect::<HashSet<_>>().len(), 5);
+        assert_eq!(hot_paths(&graph, &[main], &[target], 5), paths);
+
+        // Calls along one path make it the best, other paths see a single call
+        let heavy = [0, 9, 30, 50, 70, 90, 110, 1].map(NodeIndex::new);
+        for (i, step) in (10..).zip(heavy.windows(2)) {
+            graph.add_edge(step[0], step[1], Edge { count: i, ..Edge::default() });
+        }
+        let paths = hot_paths(&graph, &[main], &[target], 3);
+        assert_eq!(paths[0].nodes, heavy);
+        assert_eq!(paths[0].weight, 11);
+        assert!(paths[1..].iter().all(|path| path.weight == 1 && path.len() == 7));
+    }
+}
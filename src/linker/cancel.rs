@@ -0,0 +1,32 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use crate::linker::error::PassError;
+
+/// Flag shared between the pipeline and whoever may stop it, e.g. a Ctrl-C handler
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Fail with [`PassError::Cancelled`] once cancelled
+    pub fn check(&self) -> Result<(), PassError> {
+        if self.is_cancelled() {
+            Err(PassError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
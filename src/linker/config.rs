@@ -1,104 +1,79 @@
 use std::{fs, io};
-use std::path::PathBuf;
-use log::error;
-use crate::linker::pass::{
-    CutDegPass, Pass, RegexEdgeGenPass, RemoveEdgesPass, 
-    ReparentGraphPass, ReverseGraphPass, SubgraphExtractionPass, RemoveNodePass, 
-    UniqueEdgesPass};
+use std::path::Path;
+use std::sync::Arc;
+use crate::linker::match_cache::MatchCache;
+use crate::linker::pipeline::Pipeline;
+use crate::linker::registry::{PassArgs, PassRegistry};
 
-fn parse_line(config_line: &str, line_number: usize) -> io::Result<Box<dyn Pass>> {
-    let line = config_line
-        .split_whitespace()
-        .collect::<Vec<&str>>();
-    let pass = line[0];
-    match pass {
-        "remove_nodes" => {
-            let data = fs::read_to_string(
-                line.get(1).ok_or(io::ErrorKind::UnexpectedEof)?
-            )?;
-            Ok(Box::new(RemoveNodePass::new_from_str(&data)))
-        },
-        "remove_edges" => {
-            let data = fs::read_to_string(
-                line.get(1).ok_or(io::ErrorKind::UnexpectedEof)?
-            )?;
-            Ok(Box::new(RemoveEdgesPass::new_from_str(&data)))
-        },
-        "regex_edge_gen" => {
-            let data = fs::read_to_string(
-                line.get(1).ok_or(io::ErrorKind::UnexpectedEof)?
-            )?;
-            Ok(Box::new(RegexEdgeGenPass::new_from_str(&data)))
-        },
-        "cut_deg" => {
-            // TODO: ensure proper argument parsing
-            let mut incoming: Option<usize> = None;
-            let mut outgoing: Option<usize> = None;
-            for arg in &line[1..] {
-                let sign = arg.chars().next().unwrap();
-                match sign {
-                    '+' => incoming = Some(
-                        arg[1..]
-                            .parse::<usize>()
-                            .map_err(|_| io::ErrorKind::InvalidInput)?
-                    ),
-                    '-' => outgoing = Some(
-                        arg[1..]
-                            .parse::<usize>()
-                            .map_err(|_| io::ErrorKind::InvalidInput)?
-                    ),
-                    _ => {
-                        error!("Invalid prefix for deg filter on line {line_number}.\
-                         Expected '+' or '-', got {}", sign
-                        );
-                        return Err(io::ErrorKind::InvalidData.into());
-                    }
-                }
-            }
-            Ok(Box::new(CutDegPass::new(incoming, outgoing)))
-        },
-        "unique_edges" => {
-            Ok(Box::new(UniqueEdgesPass::default()))
-        },
-        "extract_subgraph" => {
-            let data = fs::read_to_string(
-                line.get(1).ok_or(io::ErrorKind::UnexpectedEof)?
-            )?;
-            Ok(Box::new(SubgraphExtractionPass::new_from_str(&data)))
-        },
-        "reverse" => {
-            Ok(Box::new(ReverseGraphPass::default()))
-        },
-        "reparent" => {
-            let data = fs::read_to_string(
-                line.get(1).ok_or(io::ErrorKind::UnexpectedEof)?
-            )?;
-            Ok(Box::new(ReparentGraphPass::new_from_str(&data)))
-        },
-        _ => {
-            error!("Invalid config on line {line_number}: no \"{pass}\" pass");
-            Err(io::ErrorKind::InvalidInput.into())
-        }
-    }
+/// Line of a config file: the link step or a pass keyword with its arguments
+pub(crate) enum ConfigLine<'a> {
+    Link,
+    Pass(&'a str, Vec<&'a str>),
+}
+
+/// Lines of a config file with their numbers, blank lines are skipped
+pub(crate) fn config_lines(contents: &str) -> impl Iterator<Item = (usize, ConfigLine<'_>)> {
+    contents.lines()
+        .enumerate()
+        .filter_map(|(line_number, line)| {
+            let mut words = line.split_whitespace();
+            let keyword = words.next()?;
+            let args = words.collect::<Vec<_>>();
+            Some((line_number, if keyword == "link" && args.is_empty() {
+                ConfigLine::Link
+            } else {
+                ConfigLine::Pass(keyword, args)
+            }))
+        })
+}
+
+/// Build a pipeline from the config file, one pass per line.
+/// A `link` line splits passes run on every input from passes run on the linked graph.
+pub fn parse_config_file(config_file: &Path) -> io::Result<Pipeline> {
+    parse_config_file_with(config_file, &PassRegistry::builtin())
+}
+
+/// Same as [`parse_config_file`], with pass keywords looked up in `registry`.
+/// Every line is parsed even after a failing one, so all invalid rule files get logged,
+/// the error of the first failing line is returned.
+pub fn parse_config_file_with(config_file: &Path, registry: &PassRegistry) -> io::Result<Pipeline> {
+    parse_config_file_cached(config_file, registry, None)
 }
 
-pub fn parse_config_file(config_file: &PathBuf) 
-    -> io::Result<(Vec<Box<dyn Pass>>, bool, Vec<Box<dyn Pass>>)> {
+/// Same as [`parse_config_file_with`], passes matching regexes share results in `match_cache`
+pub fn parse_config_file_cached(
+    config_file: &Path,
+    registry: &PassRegistry,
+    match_cache: Option<&Arc<MatchCache>>
+) -> io::Result<Pipeline> {
     let config_file_contents = fs::read_to_string(config_file)?;
-    let mut linked = false;
-    let mut before_link: Vec<Box<dyn Pass>> = vec![];
-    let mut after_link: Vec<Box<dyn Pass>> = vec![];
-    
-    for (line_number, line) in config_file_contents.lines().enumerate() {
-        if line == "link" {
-            linked = true;
+    let mut pipeline = Pipeline::new();
+    let mut first_error = None;
+
+    for (line_number, line) in config_lines(&config_file_contents) {
+        let ConfigLine::Pass(keyword, args) = line else {
+            pipeline = pipeline.link();
             continue;
-        }
-        if linked {
-            after_link.push(parse_line(line, line_number)?);
-        } else {
-            before_link.push(parse_line(line, line_number)?);
+        };
+        match registry.build(keyword, &PassArgs { args: &args, line_number, match_cache }) {
+            Ok(pass) => pipeline = pipeline.add(pass),
+            Err(e) => { first_error.get_or_insert(e); }
         }
     }
-    Ok((before_link, linked, after_link))
-}
\ No newline at end of file
+    first_error.map_or(Ok(pipeline), Err)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_lines() {
+        let lines = config_lines("remove_nodes  a.txt\n\n  \nlink\nlink x\n")
+            .map(|(line_number, line)| match line {
+                ConfigLine::Link => (line_number, vec!["<link>"]),
+                ConfigLine::Pass(keyword, args) => (line_number, [vec![keyword], args].concat()),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(lines, [(0, vec!["remove_nodes", "a.txt"]), (3, vec!["<link>"]), (4, vec!["link", "x"])]);
+    }
+}
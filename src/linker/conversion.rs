@@ -1,65 +1,843 @@
+use std::borrow::Cow;
+#[cfg(feature = "dot")]
 use std::collections::HashMap;
-use graphviz_rust::dot_structures::{EdgeTy, Id, Stmt};
-use graphviz_rust::dot_structures::Vertex::N;
-use petgraph::Graph;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+#[cfg(feature = "cli")]
+use clap::ValueEnum;
+use log::warn;
+#[cfg(feature = "dot")]
+use graphviz_rust::dot_structures::{EdgeTy, Id, Stmt, Vertex};
+#[cfg(feature = "dot")]
+use graphviz_rust::dot_structures::Vertex::{N, S};
+#[cfg(feature = "dot")]
+use petgraph::graph::EdgeIndex;
 use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+use crate::linker::edge::{Edge, EdgeKind};
+#[cfg(feature = "dot")]
+use crate::linker::edge::EdgeLabel;
+use crate::linker::node::{CallGraph, Node};
+#[cfg(feature = "dot")]
+use crate::linker::node::check_node_limit;
 
+#[cfg(feature = "dot")]
 type DotGraph = graphviz_rust::dot_structures::Graph;
 
-fn get_id_str(id: &Id) -> &str {
+/// What to do when a node is declared again with a different attribute value,
+/// e.g. `a [label = A]; "a" [label = B]`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum DuplicateNodes {
+    /// Later declarations override attributes, like in graphviz
+    #[default]
+    Merge,
+    /// Merge, logging a warning for every overridden attribute
+    Warn,
+    /// Fail on the first overridden attribute
+    Error,
+}
+
+/// Node statements seen while converting a graph, applying a [`DuplicateNodes`] policy
+#[derive(Default)]
+pub(crate) struct Declarations {
+    policy: DuplicateNodes,
+    declared: HashSet<NodeIndex>,
+    /// Node statements repeating an earlier one
+    pub(crate) duplicates: usize,
+}
+
+impl Declarations {
+    pub(crate) fn new(policy: DuplicateNodes) -> Self {
+        Self { policy, ..Self::default() }
+    }
+
+    /// Set `(key, value, html)` attributes of a node statement, `html` marks values read from HTML ids.
+    /// Fails with a message if the policy rejects the statement
+    pub(crate) fn declare(
+        &mut self,
+        idx: NodeIndex,
+        node: &mut Node,
+        attrs: impl IntoIterator<Item = (String, String, bool)>
+    ) -> Result<(), String> {
+        if !self.declared.insert(idx) {
+            self.duplicates += 1;
+        }
+        for (key, value, html) in attrs {
+            if let Some(old) = node.attrs.get(&key).filter(|old| **old != value) {
+                let message = format!("node \"{}\" declared again with {key} = \"{value}\", was \"{old}\"", node.name);
+                match self.policy {
+                    DuplicateNodes::Merge => {}
+                    DuplicateNodes::Warn => warn!("{message}"),
+                    DuplicateNodes::Error => return Err(message),
+                }
+            }
+            if html {
+                node.html_attrs.insert(key.clone());
+            } else {
+                node.html_attrs.remove(&key);
+            }
+            node.attrs.insert(key, value);
+        }
+        Ok(())
+    }
+}
+
+/// Contents of a quoted DOT string, resolving escapes like graphviz does:
+/// `\"` is a quote and a backslash before a line break continues the line.
+/// Other backslashes are kept on their own for escapes of labels like `\n`,
+/// so `\\"` is a backslash and a quote.
+pub(crate) fn unescape_dot(s: &str) -> Cow<'_, str> {
+    if !s.contains('\\') {
+        return Cow::Borrowed(s);
+    }
+    let mut result = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('\\', Some('"')) => {
+                chars.next();
+                result.push('"');
+            }
+            ('\\', Some('\n')) => {
+                chars.next();
+            }
+            (c, _) => result.push(c),
+        }
+    }
+    Cow::Owned(result)
+}
+
+/// Edge of an edge statement with `(key, value)` attributes: `kind` and `count` attributes
+/// written by [`write_dot`] are read back, missing or invalid ones are left at their default
+pub(crate) fn edge_weight<K: AsRef<str>, V: AsRef<str>>(attrs: impl IntoIterator<Item = (K, V)>) -> Edge {
+    let mut edge = Edge::default();
+    for (key, value) in attrs {
+        match key.as_ref() {
+            "kind" => edge.kind = value.as_ref().parse::<EdgeKind>().unwrap_or_default(),
+            "count" => edge.count = value.as_ref().parse::<u64>().unwrap_or(1),
+            _ => {}
+        }
+    }
+    edge
+}
+
+#[cfg(feature = "dot")]
+fn get_id_str(id: &Id) -> Cow<'_, str> {
     match id {
-        Id::Html(s) | Id::Plain(s) | Id::Anonymous(s) => s,
-        Id::Escaped(s) => &s[1..s.len() - 1], // fix for quoted names
+        Id::Html(s) | Id::Plain(s) | Id::Anonymous(s) => Cow::Borrowed(s),
+        Id::Escaped(s) => unescape_dot(&s[1..s.len() - 1]),
     }
 }
 
-fn ensure_node<'a, 'b>(
-    id: &'a Id, 
-    g: &mut Graph<String, ()>, 
-    mapping: &mut HashMap<&'b str, NodeIndex>
-) where 'a: 'b {
-    mapping
-        .entry(get_id_str(id))
-        .or_insert_with(||
-            g.add_node(get_id_str(id).to_string())
-        );
+#[cfg(feature = "dot")]
+/// Graph being converted, with the edges added so far if duplicates are skipped
+struct Conversion<'a> {
+    graph: CallGraph,
+    mapping: HashMap<Cow<'a, str>, NodeIndex>,
+    edges: Option<HashMap<(NodeIndex, NodeIndex), EdgeIndex>>,
+    declarations: Declarations,
+    max_nodes: Option<usize>,
+}
+
+#[cfg(feature = "dot")]
+impl<'a> Conversion<'a> {
+    fn ensure_node(&mut self, id: &'a Id) -> io::Result<NodeIndex> {
+        let graph = &mut self.graph;
+        let name = get_id_str(id);
+        if let Some(&idx) = self.mapping.get(&name) {
+            return Ok(idx);
+        }
+        check_node_limit(graph.node_count() + 1, self.max_nodes)?;
+        let idx = graph.add_node(Node::from(&*name));
+        self.mapping.insert(name, idx);
+        Ok(idx)
+    }
+
+    /// Add an edge, with unique edges calls of a duplicate are added to the first edge
+    fn add_edge(&mut self, from: NodeIndex, to: NodeIndex, edge: Edge) {
+        let Some(edges) = &mut self.edges else {
+            self.graph.add_edge(from, to, edge);
+            return;
+        };
+        match edges.get(&(from, to)) {
+            Some(&first) => self.graph[first].add_count(edge.count),
+            None => {
+                edges.insert((from, to), self.graph.add_edge(from, to, edge));
+            }
+        }
+    }
+}
+
+#[cfg(feature = "dot")]
+/// Nodes an edge endpoint refers to: a single node or all members of a subgraph
+enum Endpoint {
+    Node(NodeIndex),
+    Subgraph(Vec<NodeIndex>),
+}
+
+#[cfg(feature = "dot")]
+impl Endpoint {
+    fn nodes(&self) -> &[NodeIndex] {
+        match self {
+            Endpoint::Node(idx) => std::slice::from_ref(idx),
+            Endpoint::Subgraph(members) => members,
+        }
+    }
+}
+
+#[cfg(feature = "dot")]
+fn add_vertex<'a>(vertex: &'a Vertex, g: &mut Conversion<'a>) -> io::Result<Endpoint> {
+    Ok(match vertex {
+        N(node) => Endpoint::Node(g.ensure_node(&node.0)?),
+        S(subgraph) => {
+            let mut members = vec![];
+            add_stmts(&subgraph.stmts, g, Some(&mut members))?;
+            let mut seen = HashSet::new();
+            members.retain(|v| seen.insert(*v));
+            Endpoint::Subgraph(members)
+        }
+    })
+}
+
+#[cfg(feature = "dot")]
+fn edge_vertices(ty: &EdgeTy) -> impl Iterator<Item = &Vertex> {
+    let (pair, chain) = match ty {
+        EdgeTy::Pair(from, to) => (Some([from, to]), [].as_slice()),
+        EdgeTy::Chain(chain) => (None, chain.as_slice()),
+    };
+    pair.into_iter().flatten().chain(chain)
 }
 
+#[cfg(feature = "dot")]
+/// Number of node statements and of links in edge chains, subgraphs included.
+/// Lower bounds of graph size used to reserve capacity.
+fn count_stmts(stmts: &[Stmt]) -> (usize, usize) {
+    let mut count = (0, 0);
+    for stmt in stmts {
+        let (nodes, edges) = match stmt {
+            Stmt::Node(_) => (1, 0),
+            Stmt::Subgraph(subgraph) => count_stmts(&subgraph.stmts),
+            Stmt::Edge(edge) => (0, edge_vertices(&edge.ty).count().saturating_sub(1)),
+            _ => (0, 0),
+        };
+        count.0 += nodes;
+        count.1 += edges;
+    }
+    count
+}
+
+#[cfg(feature = "dot")]
+/// Add all nodes and edges from statements, subgraphs are flattened.
+/// Every node mentioned in statements is pushed to `members` if provided.
+fn add_stmts<'a>(
+    stmts: &'a [Stmt],
+    g: &mut Conversion<'a>,
+    mut members: Option<&mut Vec<NodeIndex>>
+) -> io::Result<()> {
+    for stmt in stmts {
+        match stmt {
+            Stmt::Node(node) => {
+                let idx = g.ensure_node(&node.id.0)?;
+                let attrs = node.attributes
+                    .iter()
+                    .map(|attr| (
+                        get_id_str(&attr.0).to_string(),
+                        get_id_str(&attr.1).to_string(),
+                        matches!(attr.1, Id::Html(_))
+                    ));
+                g.declarations.declare(idx, &mut g.graph[idx], attrs)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                if let Some(members) = members.as_deref_mut() {
+                    members.push(idx);
+                }
+            }
+            Stmt::Subgraph(subgraph) => {
+                add_stmts(&subgraph.stmts, g, members.as_deref_mut())?;
+            }
+            Stmt::Edge(edge) => {
+                let weight = edge_weight(edge.attributes
+                    .iter()
+                    .map(|attr| (get_id_str(&attr.0), get_id_str(&attr.1))));
+                // a -> b -> c is the same as a -> b; b -> c
+                let mut prev: Option<Endpoint> = None;
+                for vertex in edge_vertices(&edge.ty) {
+                    let endpoint = add_vertex(vertex, g)?;
+                    for &v in prev.as_ref().map_or(&[][..], Endpoint::nodes) {
+                        for &u in endpoint.nodes() {
+                            g.add_edge(v, u, weight);
+                        }
+                    }
+                    if let Some(members) = members.as_deref_mut() {
+                        members.extend_from_slice(endpoint.nodes());
+                    }
+                    prev = Some(endpoint);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "dot")]
+#[must_use]
+pub fn graphviz_to_graph(value: &DotGraph) -> CallGraph {
+    graphviz_to_graph_with(value, false)
+}
+
+#[cfg(feature = "dot")]
+/// Same as [`graphviz_to_graph`], with `unique_edges` only the first edge between
+/// two nodes is added, like running `unique_edges` afterwards
 #[must_use]
-pub fn graphviz_to_graph(value: &DotGraph) -> Graph<String, ()> {
+pub fn graphviz_to_graph_with(value: &DotGraph, unique_edges: bool) -> CallGraph {
+    graphviz_to_graph_checked(value, unique_edges, DuplicateNodes::Merge, None)
+        .map(|(graph, _)| graph)
+        .expect("merging duplicate nodes without a node limit never fails")
+}
+
+#[cfg(feature = "dot")]
+/// Same as [`graphviz_to_graph_with`], applying `duplicates` to nodes declared several times.
+/// Returns the number of repeated node statements along with the graph.
+/// Conversion stops with [`check_node_limit`] once the graph gets more than `max_nodes`.
+pub fn graphviz_to_graph_checked(
+    value: &DotGraph,
+    unique_edges: bool,
+    duplicates: DuplicateNodes,
+    max_nodes: Option<usize>
+) -> io::Result<(CallGraph, usize)> {
     let dot_graph = match value {
         DotGraph::Graph { stmts, .. }
         | DotGraph::DiGraph { stmts, .. } => stmts,
     };
-    let mut graph: Graph<String, ()> = Graph::new();
-    let mut node_id_to_v = HashMap::<&str, NodeIndex>::new();
+    let (nodes, edges) = count_stmts(dot_graph);
+    let mut conversion = Conversion {
+        graph: CallGraph::with_capacity(nodes, edges),
+        mapping: HashMap::with_capacity(nodes),
+        edges: unique_edges.then(|| HashMap::with_capacity(edges)),
+        declarations: Declarations::new(duplicates),
+        max_nodes,
+    };
+    add_stmts(dot_graph, &mut conversion, None)?;
+    Ok((conversion.graph, conversion.declarations.duplicates))
+}
 
-    for stmt in dot_graph {
-        match stmt {
-            Stmt::Node(node) => {
-                ensure_node(&node.id.0, &mut graph, &mut node_id_to_v);
-            }
-            Stmt::Edge(edge) => match &edge.ty {
-                EdgeTy::Pair(from, to) => match &(from, to) {
-                    (N(v), N(u)) => {
-                        ensure_node(&v.0, &mut graph, &mut node_id_to_v);
-                        ensure_node(&u.0, &mut graph, &mut node_id_to_v);
-                        graph.add_edge(
-                            node_id_to_v[get_id_str(&v.0)],
-                            node_id_to_v[get_id_str(&u.0)],
-                            ()
-                        );
+/// Split file contents into separate graphs, for files with several concatenated graphs.
+/// Braces inside strings, comments and HTML ids are ignored.
+#[must_use]
+pub fn split_dot_graphs(data: &str) -> Vec<&str> {
+    let mut graphs = vec![];
+    let mut start = 0;
+    let mut depth = 0usize;
+    let mut html_depth = 0usize;
+    let mut has_content = false;
+    let mut chars = data.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        if html_depth > 0 {
+            match c {
+                '<' => html_depth += 1,
+                '>' => html_depth -= 1,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => {
+                while let Some((_, c)) = chars.next() {
+                    match c {
+                        '\\' => { chars.next(); },
+                        '"' => break,
+                        _ => {}
                     }
-                    (_, _) => {
-                        panic!("Edge type mismatch");
+                }
+            }
+            '/' if chars.peek().is_some_and(|&(_, c)| c == '*') => {
+                chars.next();
+                let mut prev = ' ';
+                for (_, c) in chars.by_ref() {
+                    if prev == '*' && c == '/' {
+                        break;
                     }
-                },
-                EdgeTy::Chain(_) => {
-                    panic!("Chain not supported");
+                    prev = c;
+                }
+            }
+            '/' if chars.peek().is_some_and(|&(_, c)| c == '/') => {
+                chars.by_ref().find(|&(_, c)| c == '\n');
+            }
+            '#' => {
+                chars.by_ref().find(|&(_, c)| c == '\n');
+            }
+            '<' if depth > 0 => html_depth = 1,
+            '{' => depth += 1,
+            '}' => {
+                depth = depth.saturating_sub(1);
+                if depth == 0 {
+                    graphs.push(&data[start..=i]);
+                    start = i + 1;
+                    has_content = false;
                 }
-            },
+            }
+            c if !c.is_whitespace() => has_content = true,
             _ => {}
         }
+        if depth > 0 {
+            has_content = true;
+        }
+    }
+    // Leave incomplete graphs and garbage for the parser to report
+    if has_content || graphs.is_empty() {
+        graphs.push(&data[start..]);
+    }
+    graphs
+}
+
+/// Quote a name for output, the reverse of [`unescape_dot`]. Quotes are escaped.
+/// Backslashes right before a quote or a line break are followed by a line continuation,
+/// so they can't escape it, and parsers pairing backslashes as `\\` end the string at the same quote.
+fn quote(s: &str) -> String {
+    const CONTINUATION: &str = "\\\n";
+    let mut result = String::with_capacity(s.len() + 2);
+    result.push('"');
+    for c in s.chars() {
+        if matches!(c, '"' | '\n') && result.ends_with('\\') {
+            result.push_str(CONTINUATION);
+        }
+        if c == '"' {
+            result.push('\\');
+        }
+        result.push(c);
+    }
+    if result.ends_with('\\') {
+        result.push_str(CONTINUATION);
+    }
+    result.push('"');
+    result
+}
+
+/// Attribute names are usually plain identifiers, which need no quotes
+fn attr_key(key: &str) -> String {
+    let is_plain = key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if is_plain {
+        key.to_string()
+    } else {
+        quote(key)
+    }
+}
+
+
+/// Node names with the weight of every edge, borrowed from the graph
+pub fn named_edges(graph: &CallGraph) -> impl Iterator<Item = (&str, &str, Edge)> {
+    graph.edge_references().map(|edge| (
+        graph[edge.source()].name.as_ref(),
+        graph[edge.target()].name.as_ref(),
+        *edge.weight()
+    ))
+}
+
+fn node_line(node: &Node) -> String {
+    let mut line = format!("    {}", quote(&node.name));
+    if !node.attrs.is_empty() {
+        line += " [";
+        for (key, value) in &node.attrs {
+            // HTML-like values are written as read, everything else is quoted
+            let value = if node.html_attrs.contains(key) { value.clone() } else { quote(value) };
+            line += &format!(" {} = {value}", attr_key(key));
+        }
+        line += " ]";
+    }
+    line
+}
+
+fn edge_line((from, to, edge): (&str, &str, Edge)) -> String {
+    let mut line = format!("    {} -> {}", quote(from), quote(to));
+    let mut attrs = vec![];
+    if edge.kind != EdgeKind::Direct {
+        attrs.push(format!("kind = \"{}\"", edge.kind));
+    }
+    if edge.count != 1 {
+        attrs.push(format!("count = {}", edge.count));
+    }
+    if !attrs.is_empty() {
+        line += &format!(" [ {} ]", attrs.join(" "));
+    }
+    line
+}
+
+/// Lines of the graphviz output without line breaks, formatted one at a time
+/// so huge graphs are never formatted in memory as a whole
+pub fn dot_lines(graph: &CallGraph) -> impl Iterator<Item = String> + '_ {
+    std::iter::once("digraph {".to_string())
+        .chain(graph.node_weights().map(node_line))
+        .chain(named_edges(graph).map(edge_line))
+        .chain(std::iter::once("}".to_string()))
+}
+
+/// Write graph in graphviz format. Node names are used as node ids,
+/// so the output can be read again.
+pub fn write_dot(graph: &CallGraph, out: &mut impl Write) -> io::Result<()> {
+    for line in dot_lines(graph) {
+        writeln!(out, "{line}")?;
+    }
+    Ok(())
+}
+
+/// Write graph in graphviz format to a file
+pub fn save_dot(path: &Path, graph: &CallGraph) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write_dot(graph, &mut out)?;
+    out.flush()
+}
+
+#[cfg(feature = "dot")]
+/// Differences between a graph and the graph read back from its dot output, matched by node names
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct RoundTrip {
+    pub lost_nodes: Vec<String>,
+    pub added_nodes: Vec<String>,
+    /// Nodes read back with different attributes
+    pub altered_nodes: Vec<String>,
+    /// Edges as `from`, `to` and kind with the count, every parallel edge is listed
+    pub lost_edges: Vec<(String, String, String)>,
+    pub added_edges: Vec<(String, String, String)>,
+}
+
+#[cfg(feature = "dot")]
+impl RoundTrip {
+    /// The graph was read back unchanged
+    #[must_use]
+    pub fn is_clean(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[cfg(feature = "dot")]
+impl std::fmt::Display for RoundTrip {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (what, nodes) in [("lost", &self.lost_nodes), ("added", &self.added_nodes), ("altered", &self.altered_nodes)] {
+            for node in nodes {
+                writeln!(f, "{what} node {}", quote(node))?;
+            }
+        }
+        for (what, edges) in [("lost", &self.lost_edges), ("added", &self.added_edges)] {
+            for (from, to, kind) in edges {
+                writeln!(f, "{what} {kind} edge {} -> {}", quote(from), quote(to))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "dot")]
+/// Emit `graph` as dot, parse and convert the output again and list what changed.
+/// Fails if the emitted dot can't be parsed.
+pub fn round_trip(graph: &CallGraph) -> io::Result<RoundTrip> {
+    let mut dot = vec![];
+    write_dot(graph, &mut dot)?;
+    let dot = String::from_utf8(dot).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let parsed = graphviz_rust::parse(&dot).map_err(|e| io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Emitted dot can't be parsed again: {e}")
+    ))?;
+    let back = graphviz_to_graph(&parsed);
+
+    let nodes = |g: &CallGraph| g.node_weights()
+        .map(|node| (node.name.to_string(), (node.attrs.clone(), node.html_attrs.clone())))
+        .collect::<HashMap<_, _>>();
+    let (before, after) = (nodes(graph), nodes(&back));
+    let mut result = RoundTrip::default();
+    for (name, attrs) in &before {
+        match after.get(name) {
+            None => result.lost_nodes.push(name.clone()),
+            Some(other) if other != attrs => result.altered_nodes.push(name.clone()),
+            Some(_) => {}
+        }
+    }
+    result.added_nodes = after.keys().filter(|name| !before.contains_key(*name)).cloned().collect();
+
+    let edges = |g: &CallGraph| {
+        let mut counts: HashMap<(String, String, String), usize> = HashMap::new();
+        for edge in g.edge_references() {
+            let key = (g[edge.source()].name.to_string(), g[edge.target()].name.to_string(), edge.weight().to_string());
+            *counts.entry(key).or_default() += 1;
+        }
+        counts
+    };
+    let (before, after) = (edges(graph), edges(&back));
+    for (edge, &count) in &before {
+        let left = after.get(edge).copied().unwrap_or_default();
+        result.lost_edges.extend(std::iter::repeat_n(edge.clone(), count.saturating_sub(left)));
+    }
+    for (edge, &count) in &after {
+        let had = before.get(edge).copied().unwrap_or_default();
+        result.added_edges.extend(std::iter::repeat_n(edge.clone(), count.saturating_sub(had)));
+    }
+    for list in [&mut result.lost_nodes, &mut result.added_nodes, &mut result.altered_nodes] {
+        list.sort_unstable();
+    }
+    result.lost_edges.sort_unstable();
+    result.added_edges.sort_unstable();
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "dot")]
+    use graphviz_rust::parse;
+    use super::*;
+
+    #[cfg(feature = "dot")]
+    fn edges(graph: &CallGraph) -> Vec<(&str, &str)> {
+        let mut edges = graph.raw_edges()
+            .iter()
+            .map(|e| (graph[e.source()].name.as_ref(), graph[e.target()].name.as_ref()))
+            .collect::<Vec<_>>();
+        edges.sort_unstable();
+        edges
+    }
+
+    #[test]
+    #[cfg(feature = "dot")]
+    fn test_subgraphs() {
+        let dot = parse(r#"
+            digraph {
+                subgraph cluster_0 {
+                    a -> b;
+                    subgraph inner { c }
+                }
+                d -> subgraph { e; f };
+                subgraph { g; h -> subgraph { a } };
+                lonely;
+            }
+        "#).unwrap();
+        let graph = graphviz_to_graph(&dot);
+
+        assert_eq!(graph.node_count(), 9);
+        assert_eq!(edges(&graph), vec![
+            ("a", "b"), ("d", "e"), ("d", "f"), ("h", "a"),
+        ]);
+    }
+
+    #[test]
+    #[cfg(feature = "dot")]
+    fn test_port_endpoints() {
+        let dot = parse("digraph { a:out:s -> b:in; b:in -> subgraph { a:n; c } }").unwrap();
+        let graph = graphviz_to_graph(&dot);
+
+        assert_eq!(graph.node_count(), 3);
+        assert_eq!(edges(&graph), vec![("a", "b"), ("b", "a"), ("b", "c")]);
+    }
+
+    #[test]
+    #[cfg(feature = "dot")]
+    fn test_unique_edges() {
+        let dot = parse("digraph { a -> b; a -> b [kind = indirect]; b -> a; b -> subgraph { a; a } }").unwrap();
+        assert_eq!(graphviz_to_graph(&dot).edge_count(), 4);
+        let graph = graphviz_to_graph_with(&dot, true);
+        assert_eq!(edges(&graph), vec![("a", "b"), ("b", "a")]);
+        assert!(graph.edge_weights().all(|edge| edge.kind == EdgeKind::Direct));
+        assert_eq!(graph.edge_weights().map(|edge| edge.count).collect::<Vec<_>>(), [2, 2]);
+    }
+
+    #[test]
+    #[cfg(feature = "dot")]
+    fn test_chains() {
+        let dot = parse("digraph { a -> b -> c -> subgraph { d; e } -> f; a -> c }").unwrap();
+        let graph = graphviz_to_graph(&dot);
+
+        assert_eq!(edges(&graph), vec![
+            ("a", "b"), ("a", "c"), ("b", "c"), ("c", "d"), ("c", "e"), ("d", "f"), ("e", "f"),
+        ]);
+    }
+
+    #[test]
+    #[cfg(feature = "dot")]
+    fn test_node_attributes() {
+        let dot = parse(r#"digraph { a [label="A", shape=box]; a [shape=oval]; b [label=<<b>B</b>>]; a -> b }"#)
+            .unwrap();
+        let graph = graphviz_to_graph(&dot);
+        assert_eq!(graph[NodeIndex::new(0)].attrs["label"], "A");
+        assert_eq!(graph[NodeIndex::new(0)].attrs["shape"], "oval");
+
+        let mut out = vec![];
+        write_dot(&graph, &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\
+digraph {
+    \"a\" [ label = \"A\" shape = \"oval\" ]
+    \"b\" [ label = <<b>B</b>> ]
+    \"a\" -> \"b\"
+}
+");
+    }
+
+    #[test]
+    #[cfg(feature = "dot")]
+    fn test_html_labels() {
+        let dot = parse(r#"digraph { a [label="<init>"]; b [label=<<b>x</b>>]; b [shape="<box>"] }"#).unwrap();
+        let graph = graphviz_to_graph(&dot);
+        assert!(graph[NodeIndex::new(1)].html_attrs.contains("label"));
+
+        let mut out = vec![];
+        write_dot(&graph, &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\
+digraph {
+    \"a\" [ label = \"<init>\" ]
+    \"b\" [ label = <<b>x</b>> shape = \"<box>\" ]
+}
+");
+        assert!(round_trip(&graph).unwrap().is_clean());
+    }
+
+    #[test]
+    fn test_dot_lines() {
+        let mut graph = CallGraph::new();
+        let a = graph.add_node("a".into());
+        let b = graph.add_node("b".into());
+        graph.add_edge(a, b, EdgeKind::Indirect.into());
+        assert_eq!(named_edges(&graph).collect::<Vec<_>>(), [("a", "b", EdgeKind::Indirect.into())]);
+
+        let mut lines = dot_lines(&graph);
+        assert_eq!(lines.nth(1).unwrap(), "    \"a\"");
+        assert_eq!(lines.count(), 3);
+
+        let mut out = vec![];
+        write_dot(&graph, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            dot_lines(&graph).map(|line| line + "\n").collect::<String>()
+        );
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("plain"), "\"plain\"");
+        assert_eq!(quote(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(quote(r"label\l"), r#""label\l""#);
+        assert_eq!(quote(r#"slash \" quote"#), "\"slash \\\\\n\\\" quote\"");
+        assert_eq!(quote("trailing\\"), "\"trailing\\\\\n\"");
+        assert_eq!(quote("line\\\nbreak"), "\"line\\\\\n\nbreak\"");
+    }
+
+    #[test]
+    fn test_unescape_dot() {
+        assert_eq!(unescape_dot(r#"say \"hi\""#), r#"say "hi""#);
+        assert_eq!(unescape_dot("multi\\\nline"), "multiline");
+        assert_eq!(unescape_dot(r"keeps \l and \\"), r"keeps \l and \\");
+        assert_eq!(unescape_dot(r#"slash \\" quote"#), r#"slash \" quote"#);
+        assert!(matches!(unescape_dot("plain"), Cow::Borrowed("plain")));
+        let names = [
+            r#"say "hi""#, r"label\l", r#"slash \" quote"#, r#"two \\" quote"#, r#""""#,
+            "trailing\\", "trailing\\\\", "line\\\nbreak", "\\",
+        ];
+        for name in names {
+            let quoted = quote(name);
+            assert_eq!(unescape_dot(&quoted[1..quoted.len() - 1]), name);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "dot")]
+    fn test_escaped_ids() {
+        let dot = parse(r#"digraph { "say \"hi\"" [label = "A \"B\"\l"]; "say \"hi\"" -> "multi\
+line" }"#).unwrap();
+        let graph = graphviz_to_graph(&dot);
+        assert_eq!(edges(&graph), vec![(r#"say "hi""#, "multiline")]);
+        assert_eq!(graph[NodeIndex::new(0)].attrs["label"], r#"A "B"\l"#);
+
+        let mut out = vec![];
+        write_dot(&graph, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains(r#""say \"hi\"" [ label = "A \"B\"\l" ]"#));
+        let reread = graphviz_to_graph(&parse(&out).unwrap());
+        assert_eq!(reread.node_weights().collect::<Vec<_>>(), graph.node_weights().collect::<Vec<_>>());
+        assert_eq!(edges(&reread), edges(&graph));
+
+        // Backslashes before a quote and at the end of a name survive writing and reading
+        let mut graph = CallGraph::new();
+        let slash = graph.add_node(Node::from(r#"slash \" quote"#));
+        let trailing = graph.add_node(Node::from("trailing\\"));
+        graph.add_edge(slash, trailing, Edge::default());
+        let mut out = vec![];
+        write_dot(&graph, &mut out).unwrap();
+        let reread = graphviz_to_graph(&parse(&String::from_utf8(out).unwrap()).unwrap());
+        assert_eq!(edges(&reread), [(r#"slash \" quote"#, "trailing\\")]);
+    }
+
+    #[test]
+    #[cfg(feature = "dot")]
+    fn test_split_graphs() {
+        let data = r#"
+            digraph first { a -> b [label="}"]; c [label=<<i>{</i>>] }
+            // comment with }
+            /* and { here */
+            digraph second { subgraph { x -> y } }
+        "#;
+        let graphs = split_dot_graphs(data);
+        assert_eq!(graphs.len(), 2);
+        assert_eq!(graphviz_to_graph(&parse(graphs[0]).unwrap()).node_count(), 3);
+        assert_eq!(graphviz_to_graph(&parse(graphs[1]).unwrap()).node_count(), 2);
+
+        assert_eq!(split_dot_graphs("digraph { a -> b } garbage").len(), 2);
+        assert_eq!(split_dot_graphs(""), vec![""]);
+    }
+
+    #[test]
+    #[cfg(feature = "dot")]
+    fn test_edge_kinds() {
+        let dot = parse(r#"digraph { a -> b [count=7]; a -> c [kind=indirect]; c -> d [kind="synthetic"] }"#).unwrap();
+        let graph = graphviz_to_graph(&dot);
+        assert_eq!(
+            graph.edge_weights().copied().collect::<Vec<_>>(),
+            [Edge::new(EdgeKind::Direct, 7), EdgeKind::Indirect.into(), EdgeKind::Synthetic.into()]
+        );
+
+        let mut out = vec![];
+        write_dot(&graph, &mut out).unwrap();
+        assert!(String::from_utf8_lossy(&out).contains("\"a\" -> \"b\" [ count = 7 ]"));
+        let reread = graphviz_to_graph(&parse(&String::from_utf8(out).unwrap()).unwrap());
+        assert_eq!(
+            reread.edge_weights().collect::<Vec<_>>(),
+            graph.edge_weights().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "dot")]
+    fn test_duplicate_nodes() {
+        let dot = parse(r#"digraph { a [label = "A"]; "a" [shape = box]; b; b; a [label = "B"] }"#).unwrap();
+        let (graph, repeated) = graphviz_to_graph_checked(&dot, false, DuplicateNodes::Warn, None).unwrap();
+        assert_eq!(repeated, 3);
+        assert_eq!(graph.node_count(), 2);
+        assert_eq!(graph[NodeIndex::new(0)].attrs["label"], "B");
+        assert_eq!(graph[NodeIndex::new(0)].attrs["shape"], "box");
+
+        let err = graphviz_to_graph_checked(&dot, false, DuplicateNodes::Error, None).unwrap_err();
+        assert_eq!(err.to_string(), r#"node "a" declared again with label = "B", was "A""#);
+        let dot = parse(r#"digraph { a [label = "A"]; a [label = "A"] }"#).unwrap();
+        assert_eq!(graphviz_to_graph_checked(&dot, false, DuplicateNodes::Error, None).unwrap().1, 1);
+    }
+
+    #[test]
+    #[cfg(feature = "dot")]
+    fn test_round_trip() {
+        let dot = parse(r#"digraph {
+            "say \"hi\"" [label = "A \"B\"\l", shape = box]; b [label = <<b>B</b>>];
+            "say \"hi\"" -> b -> "multi\
+line"; b -> b [kind = indirect]; b -> b; lonely
+        }"#).unwrap();
+        let graph = graphviz_to_graph(&dot);
+        assert!(round_trip(&graph).unwrap().is_clean());
+
+        let diff = RoundTrip {
+            lost_nodes: vec!["a".to_string()],
+            added_edges: vec![("a".to_string(), "b c".to_string(), "direct".to_string())],
+            ..RoundTrip::default()
+        };
+        assert!(!diff.is_clean());
+        assert_eq!(diff.to_string(), "lost node \"a\"\nadded direct edge \"a\" -> \"b c\"\n");
     }
-    graph
 }
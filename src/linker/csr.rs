@@ -0,0 +1,199 @@
+#[cfg(feature = "parallel")]
+use std::sync::atomic::{AtomicBool, Ordering};
+use petgraph::Graph;
+use petgraph::graph::NodeIndex;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// Smaller graphs are walked on one thread with the `parallel` feature too
+#[cfg(feature = "parallel")]
+const PARALLEL_MIN_NODES: usize = 1 << 16;
+
+/// Immutable graph structure in compressed sparse row form: the targets of all edges
+/// sorted by source, with an offset per node. Uses two `u32` per edge instead of petgraph's
+/// edge list with weights and next pointers, and walks neighbors sequentially in memory.
+/// Node indices are the ones of the graph it was built from.
+pub struct CsrGraph {
+    /// Targets of node `i` are `targets[offsets[i]..offsets[i + 1]]`
+    offsets: Vec<u32>,
+    targets: Vec<u32>,
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn to_u32(index: usize) -> u32 {
+    // Graphs have at most `MAX_GRAPH_SIZE` nodes and edges, so indices and offsets fit
+    index as u32
+}
+
+impl CsrGraph {
+    fn from_edges(node_count: usize, edges: impl Iterator<Item = (usize, usize)> + Clone) -> Self {
+        let mut offsets = vec![0u32; node_count + 1];
+        for (source, _) in edges.clone() {
+            offsets[source + 1] += 1;
+        }
+        for i in 0..node_count {
+            offsets[i + 1] += offsets[i];
+        }
+        let mut next = offsets[..node_count].to_vec();
+        let mut targets = vec![0u32; offsets[node_count] as usize];
+        for (source, target) in edges {
+            targets[next[source] as usize] = to_u32(target);
+            next[source] += 1;
+        }
+        Self { offsets, targets }
+    }
+
+    /// Structure of `graph`, parallel edges are kept and neighbors keep the edge order
+    #[must_use]
+    pub fn from_graph<N, E>(graph: &Graph<N, E>) -> Self {
+        Self::from_edges(
+            graph.node_count(),
+            graph.raw_edges().iter().map(|e| (e.source().index(), e.target().index()))
+        )
+    }
+
+    /// Graph with every edge reversed, for walking callers. Neighbors are ordered by node index
+    #[must_use]
+    pub fn reversed(&self) -> Self {
+        let edges = (0..self.node_count())
+            .flat_map(|source| self.targets(source).iter().map(move |&target| (target as usize, source)));
+        Self::from_edges(self.node_count(), edges)
+    }
+
+    #[must_use]
+    pub fn node_count(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    #[must_use]
+    pub fn edge_count(&self) -> usize {
+        self.targets.len()
+    }
+
+    fn targets(&self, node: usize) -> &[u32] {
+        &self.targets[self.offsets[node] as usize..self.offsets[node + 1] as usize]
+    }
+
+    pub fn neighbors(&self, node: NodeIndex) -> impl Iterator<Item = NodeIndex> + '_ {
+        self.targets(node.index()).iter().map(|&target| NodeIndex::new(target as usize))
+    }
+
+    /// Nodes reachable from any of `starts`, starts included, indexed by node index.
+    /// Every node is visited once however many starts reach it.
+    /// With the `parallel` feature big graphs are walked breadth first, every frontier on all cores.
+    #[must_use]
+    pub fn reachable(&self, starts: impl IntoIterator<Item = NodeIndex>) -> Vec<bool> {
+        #[cfg(feature = "parallel")]
+        if self.node_count() >= PARALLEL_MIN_NODES {
+            return self.reachable_parallel(starts);
+        }
+        self.reachable_sequential(starts)
+    }
+
+    /// Numbers of edges on the shortest paths from the nearest of `starts` to every node,
+    /// `None` for unreachable ones. All starts are walked at once
+    #[must_use]
+    pub fn distances(&self, starts: impl IntoIterator<Item = NodeIndex>) -> Vec<Option<usize>> {
+        let mut distances = vec![None; self.node_count()];
+        let mut queue = std::collections::VecDeque::new();
+        for start in starts {
+            if distances[start.index()].is_none() {
+                distances[start.index()] = Some(0);
+                queue.push_back(to_u32(start.index()));
+            }
+        }
+        while let Some(node) = queue.pop_front() {
+            let next = distances[node as usize].map(|distance| distance + 1);
+            for &target in self.targets(node as usize) {
+                if distances[target as usize].is_none() {
+                    distances[target as usize] = next;
+                    queue.push_back(target);
+                }
+            }
+        }
+        distances
+    }
+
+    fn reachable_sequential(&self, starts: impl IntoIterator<Item = NodeIndex>) -> Vec<bool> {
+        let mut reached = vec![false; self.node_count()];
+        let mut stack = vec![];
+        for start in starts {
+            if !reached[start.index()] {
+                reached[start.index()] = true;
+                stack.push(to_u32(start.index()));
+            }
+            while let Some(node) = stack.pop() {
+                for &target in self.targets(node as usize) {
+                    if !reached[target as usize] {
+                        reached[target as usize] = true;
+                        stack.push(target);
+                    }
+                }
+            }
+        }
+        reached
+    }
+
+    #[cfg(feature = "parallel")]
+    fn reachable_parallel(&self, starts: impl IntoIterator<Item = NodeIndex>) -> Vec<bool> {
+        let reached = (0..self.node_count()).map(|_| AtomicBool::new(false)).collect::<Vec<_>>();
+        let mut frontier = starts.into_iter()
+            .filter(|start| !reached[start.index()].swap(true, Ordering::Relaxed))
+            .map(|start| to_u32(start.index()))
+            .collect::<Vec<_>>();
+        while !frontier.is_empty() {
+            frontier = frontier.par_iter()
+                .flat_map_iter(|&node| self.targets(node as usize)
+                    .iter()
+                    .copied()
+                    .filter(|&target| !reached[target as usize].swap(true, Ordering::Relaxed)))
+                .collect();
+        }
+        reached.into_iter().map(AtomicBool::into_inner).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csr() {
+        let mut graph = Graph::<&str, ()>::new();
+        let [a, b, c, d, e] = ["a", "b", "c", "d", "e"].map(|name| graph.add_node(name));
+        graph.extend_with_edges([(c, d), (a, b), (b, c), (a, c), (a, b), (e, a)]);
+
+        let csr = CsrGraph::from_graph(&graph);
+        assert_eq!((csr.node_count(), csr.edge_count()), (5, 6));
+        assert_eq!(csr.neighbors(a).collect::<Vec<_>>(), [b, c, b]);
+        assert_eq!(csr.neighbors(d).count(), 0);
+        assert_eq!(csr.reachable([b]), [false, true, true, true, false]);
+        assert_eq!(csr.reachable([d, a]), [true, true, true, true, false]);
+        assert_eq!(csr.distances([a]), [Some(0), Some(1), Some(1), Some(2), None]);
+        assert_eq!(csr.distances([d, b]), [None, Some(0), Some(1), Some(0), None]);
+
+        let reversed = csr.reversed();
+        assert_eq!(reversed.neighbors(c).collect::<Vec<_>>(), [a, b]);
+        assert_eq!(reversed.reachable([c]), [true, true, true, false, true]);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_reachable_parallel() {
+        let mut graph = Graph::<(), ()>::new();
+        let nodes = (0..1000).map(|_| graph.add_node(())).collect::<Vec<_>>();
+        // Two binary trees sharing their leaves, and an unreachable tail
+        for i in 1..500 {
+            graph.add_edge(nodes[(i - 1) / 2], nodes[i], ());
+            graph.add_edge(nodes[i], nodes[(i + 1) % 500], ());
+        }
+        graph.add_edge(nodes[999], nodes[0], ());
+        let csr = CsrGraph::from_graph(&graph);
+        for starts in [vec![nodes[0]], vec![nodes[3], nodes[250]], vec![nodes[999]], vec![]] {
+            assert_eq!(
+                csr.reachable_parallel(starts.iter().copied()),
+                csr.reachable_sequential(starts)
+            );
+        }
+    }
+}
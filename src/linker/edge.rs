@@ -0,0 +1,137 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// How a call graph edge was obtained
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum EdgeKind {
+    /// Call found in the input
+    #[default]
+    Direct,
+    /// Call through a function pointer
+    Indirect,
+    /// Edge created by a pass, e.g. `regex_edge_gen` or `reparent`
+    Synthetic,
+}
+
+/// Call graph edge: how it was obtained and how many calls it stands for
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Edge {
+    pub kind: EdgeKind,
+    /// Calls seen along the edge, e.g. by a profiler. Edges of static call graphs have 1
+    #[cfg_attr(feature = "serde", serde(default = "one"))]
+    pub count: u64,
+}
+
+#[cfg(feature = "serde")]
+fn one() -> u64 {
+    1
+}
+
+impl Edge {
+    #[must_use]
+    pub fn new(kind: EdgeKind, count: u64) -> Self {
+        Self { kind, count }
+    }
+}
+
+impl Default for Edge {
+    fn default() -> Self {
+        EdgeKind::Direct.into()
+    }
+}
+
+impl From<EdgeKind> for Edge {
+    fn from(kind: EdgeKind) -> Self {
+        Self::new(kind, 1)
+    }
+}
+
+/// Edge data passes can work with
+pub trait EdgeLabel: Clone {
+    fn kind(&self) -> EdgeKind;
+
+    /// Number of calls the edge stands for, types without counts stand for one
+    fn count(&self) -> u64 {
+        1
+    }
+
+    /// Add calls of a duplicate edge folded into this one, types without counts drop them
+    fn add_count(&mut self, _count: u64) {}
+
+    /// Weight of an edge created by a pass
+    fn synthetic() -> Self;
+}
+
+impl EdgeLabel for Edge {
+    fn kind(&self) -> EdgeKind {
+        self.kind
+    }
+
+    fn count(&self) -> u64 {
+        self.count
+    }
+
+    fn add_count(&mut self, count: u64) {
+        self.count = self.count.saturating_add(count);
+    }
+
+    fn synthetic() -> Self {
+        EdgeKind::Synthetic.into()
+    }
+}
+
+impl EdgeLabel for EdgeKind {
+    fn kind(&self) -> EdgeKind {
+        *self
+    }
+
+    fn synthetic() -> Self {
+        EdgeKind::Synthetic
+    }
+}
+
+/// Graphs without edge data, all their edges are direct
+impl EdgeLabel for () {
+    fn kind(&self) -> EdgeKind {
+        EdgeKind::Direct
+    }
+
+    fn synthetic() -> Self {}
+}
+
+impl Display for EdgeKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            EdgeKind::Direct => "direct",
+            EdgeKind::Indirect => "indirect",
+            EdgeKind::Synthetic => "synthetic",
+        })
+    }
+}
+
+/// Kind of the edge, followed by its count if it's not 1
+impl Display for Edge {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.count == 1 {
+            write!(f, "{}", self.kind)
+        } else {
+            write!(f, "{} ({} calls)", self.kind, self.count)
+        }
+    }
+}
+
+impl FromStr for EdgeKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "direct" => Ok(EdgeKind::Direct),
+            "indirect" => Ok(EdgeKind::Indirect),
+            "synthetic" => Ok(EdgeKind::Synthetic),
+            _ => Err(format!("unknown edge kind \"{s}\"")),
+        }
+    }
+}
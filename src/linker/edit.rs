@@ -0,0 +1,65 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use log::debug;
+use petgraph::graph::NodeIndex;
+use petgraph::prelude::EdgeRef;
+use crate::linker::edge::EdgeKind;
+use crate::linker::intern::intern;
+use crate::linker::node::{retain_in_order, CallGraph};
+use crate::linker::report::PassReport;
+
+/// Change requested by a pass implemented outside of this crate, nodes are referenced by name
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GraphEdit {
+    RemoveNode(String),
+    /// Remove all edges between two nodes
+    RemoveEdge(String, String),
+    /// Add an edge, missing nodes are created
+    AddEdge(String, String, EdgeKind),
+}
+
+/// Apply edits in order. Nodes are removed after all other edits, unknown nodes are ignored
+pub fn apply_edits(graph: &mut CallGraph, edits: impl IntoIterator<Item = GraphEdit>) -> PassReport {
+    let mut report = PassReport::default();
+    let mut index: HashMap<Arc<str>, NodeIndex> = graph.node_indices()
+        .map(|idx| (graph[idx].name.clone(), idx))
+        .collect();
+    let mut removed = HashSet::new();
+    let mut removed_edges = HashSet::new();
+    for edit in edits {
+        match edit {
+            GraphEdit::RemoveNode(name) => {
+                removed.extend(index.get(name.as_str()).copied());
+            }
+            GraphEdit::RemoveEdge(from, to) => {
+                let (Some(&from), Some(&to)) = (index.get(from.as_str()), index.get(to.as_str())) else {
+                    continue;
+                };
+                removed_edges.extend(graph.edges_connecting(from, to).map(|edge| edge.id()));
+            }
+            GraphEdit::AddEdge(from, to, kind) => {
+                let mut node = |name: String| *index
+                    .entry(intern(&name))
+                    .or_insert_with(|| {
+                        report.nodes_added += 1;
+                        graph.add_node(name.into())
+                    });
+                let (from, to) = (node(from), node(to));
+                debug!("Adding {} -> {}", graph[from], graph[to]);
+                graph.add_edge(from, to, kind.into());
+                report.edges_added += 1;
+            }
+        }
+    }
+    if removed.is_empty() && removed_edges.is_empty() {
+        return report;
+    }
+    // Removing everything at once keeps the order of remaining nodes and edges
+    let edges = graph.edge_count();
+    let keep = graph.node_indices().map(|idx| !removed.contains(&idx)).collect::<Vec<_>>();
+    retain_in_order(graph, &keep, |idx| !removed_edges.contains(&idx));
+    report.nodes_removed = removed.len();
+    report.edges_removed = edges - graph.edge_count();
+    report
+}
@@ -0,0 +1,156 @@
+use std::fmt::{Display, Formatter};
+use std::io;
+
+/// Error raised while running passes
+#[derive(Debug)]
+pub enum PassError {
+    /// Regex could not be matched against a node, e.g. backtracking limit was exceeded
+    Regex {
+        pattern: String,
+        subject: String,
+        source: Box<fancy_regex::Error>,
+    },
+    Io(io::Error),
+    /// The run was cancelled through a `CancellationToken`
+    Cancelled,
+    /// A pass left the graph inconsistent, found by paranoid pipelines
+    Invariant {
+        pass: String,
+        message: String,
+    },
+    /// Failure reported by a pass defined outside of this crate
+    Other(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl Display for PassError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PassError::Regex { pattern, subject, source } => {
+                write!(f, "failed to match \"{subject}\" against regex \"{pattern}\": {source}")
+            }
+            PassError::Io(e) => write!(f, "{e}"),
+            PassError::Cancelled => write!(f, "cancelled"),
+            PassError::Invariant { pass, message } => write!(f, "invariant broken by \"{pass}\": {message}"),
+            PassError::Other(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for PassError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PassError::Regex { source, .. } => Some(source.as_ref()),
+            PassError::Io(e) => Some(e),
+            PassError::Cancelled | PassError::Invariant { .. } => None,
+            PassError::Other(e) => Some(e.as_ref()),
+        }
+    }
+}
+
+impl From<io::Error> for PassError {
+    fn from(e: io::Error) -> Self {
+        PassError::Io(e)
+    }
+}
+
+impl From<PassError> for io::Error {
+    fn from(e: PassError) -> Self {
+        match e {
+            PassError::Io(e) => e,
+            PassError::Cancelled => io::Error::new(io::ErrorKind::Interrupted, e),
+            e => io::Error::other(e),
+        }
+    }
+}
+
+/// Why a rule line could not be parsed
+#[derive(Debug)]
+pub enum RuleErrorKind {
+    /// The line has a single pattern instead of a source and a target pattern
+    MissingPattern,
+    /// The line has more than two patterns
+    ExtraPattern,
+    /// A quoted pattern has no closing quote
+    UnterminatedQuote,
+    /// An edge generation rule has no `->` or `<-` separator
+    MissingSeparator,
+    /// The pattern of an edge generation rule is not wrapped in quotes
+    UnquotedPattern,
+    Regex(Box<fancy_regex::Error>),
+}
+
+impl Display for RuleErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuleErrorKind::MissingPattern => write!(f, "expected a source and a target pattern"),
+            RuleErrorKind::ExtraPattern => write!(f, "more than two patterns, quote patterns containing spaces"),
+            RuleErrorKind::UnterminatedQuote => write!(f, "unterminated quote"),
+            RuleErrorKind::MissingSeparator => write!(f, "expected a '->' or '<-' separator"),
+            RuleErrorKind::UnquotedPattern => write!(f, "regex is not wrapped in quotes"),
+            RuleErrorKind::Regex(e) => write!(f, "invalid regex: {e}"),
+        }
+    }
+}
+
+/// Rule line of a pass input file that could not be parsed
+#[derive(Debug)]
+pub struct RuleError {
+    /// Line number in the rule file, starting from 1
+    pub line_number: usize,
+    pub line: String,
+    pub kind: RuleErrorKind,
+}
+
+impl Display for RuleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}: \"{}\"", self.line_number, self.kind, self.line)
+    }
+}
+
+impl std::error::Error for RuleError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            RuleErrorKind::Regex(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<RuleError> for io::Error {
+    fn from(e: RuleError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    }
+}
+
+/// Invalid rule lines of a pass input file
+#[derive(Debug)]
+pub struct RuleFileError {
+    pub path: String,
+    /// Not empty
+    pub errors: Vec<RuleError>,
+}
+
+impl Display for RuleFileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.path)?;
+        if let Some(first) = self.errors.first() {
+            write!(f, ": {first}")?;
+        }
+        if self.errors.len() > 1 {
+            write!(f, " and {} more invalid rules", self.errors.len() - 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for RuleFileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.errors.first().map(|e| e as _)
+    }
+}
+
+impl From<RuleFileError> for io::Error {
+    fn from(e: RuleFileError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    }
+}
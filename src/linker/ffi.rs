@@ -0,0 +1,231 @@
+//! C interface, declared in `include/dot_linker.h`
+
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::ptr;
+use log::error;
+use crate::linker::conversion::named_edges;
+use crate::linker::edge::EdgeKind;
+use crate::linker::import::{expand_inputs, read_inputs, MultiGraph};
+use crate::linker::intern;
+use crate::linker::node::CallGraph;
+use crate::linker::pipeline::run_passes;
+
+/// Output name of the linked graph, the C interface never writes it
+const LINKED_OUTPUT: &str = "out.dot";
+
+/// Graphs loaded through the C interface with their output paths
+pub struct DotLinkerGraphs(Vec<(PathBuf, CallGraph)>);
+
+#[repr(C)]
+pub struct DotLinkerEdge {
+    pub from: *mut c_char,
+    pub to: *mut c_char,
+    /// 0 for direct, 1 for indirect and 2 for synthetic edges
+    pub kind: u32,
+    /// Calls seen along the edge, 1 for edges of static call graphs
+    pub count: u64,
+}
+
+unsafe fn path_from_c<'a>(path: *const c_char) -> Option<&'a Path> {
+    if path.is_null() {
+        return None;
+    }
+    CStr::from_ptr(path).to_str().ok().map(Path::new)
+}
+
+fn c_string(name: &str) -> *mut c_char {
+    CString::new(name.replace('\0', ""))
+        .unwrap_or_default()
+        .into_raw()
+}
+
+/// Run `f`, returning `on_panic` if it panics instead of unwinding into C
+fn catch_panic<T>(on_panic: T, f: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|_| {
+        error!("Panicked in a C interface call");
+        on_panic
+    })
+}
+
+/// Read inputs in any supported format, directories are searched for dot files.
+/// Returns null on failure. Like all functions of the C interface, it does not unwind on panics.
+///
+/// # Safety
+/// `paths` points to `count` NUL terminated strings
+#[no_mangle]
+pub unsafe extern "C" fn dot_linker_load(
+    paths: *const *const c_char,
+    count: usize
+) -> *mut DotLinkerGraphs {
+    catch_panic(ptr::null_mut(), || {
+        if paths.is_null() && count != 0 {
+            return ptr::null_mut();
+        }
+        let mut inputs = vec![];
+        for i in 0..count {
+            let Some(path) = path_from_c(*paths.add(i)) else {
+                return ptr::null_mut();
+            };
+            inputs.push(path.to_path_buf());
+        }
+        let graphs = expand_inputs(inputs, None)
+            .and_then(|files| read_inputs(&files, None, MultiGraph::Merge));
+        match graphs {
+            Ok(graphs) => Box::into_raw(Box::new(DotLinkerGraphs(graphs))),
+            Err(e) => {
+                error!("Failed to load inputs: {e}");
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Run passes from the `config` file. Returns 0 on success and -1 on failure,
+/// a failing pass leaves the graphs as the passes before it changed them.
+///
+/// # Safety
+/// `graphs` comes from [`dot_linker_load`], `config` is a NUL terminated string
+#[no_mangle]
+pub unsafe extern "C" fn dot_linker_run_config(
+    graphs: *mut DotLinkerGraphs,
+    config: *const c_char
+) -> c_int {
+    catch_panic(-1, || {
+        let (Some(graphs), Some(config)) = (graphs.as_mut(), path_from_c(config)) else {
+            return -1;
+        };
+        match run_passes(config, &mut graphs.0, Path::new(LINKED_OUTPUT)) {
+            Ok(_) => 0,
+            Err(e) => {
+                error!("Failed to run {}: {e}", config.display());
+                -1
+            }
+        }
+    })
+}
+
+/// Number of graphs, 1 after a config with `link`
+///
+/// # Safety
+/// `graphs` comes from [`dot_linker_load`]
+#[no_mangle]
+pub unsafe extern "C" fn dot_linker_graph_count(graphs: *const DotLinkerGraphs) -> usize {
+    catch_panic(0, || {
+        graphs.as_ref().map_or(0, |graphs| graphs.0.len())
+    })
+}
+
+/// Edges of graph number `index`, their count is stored to `len`.
+/// The list is released with [`dot_linker_edges_free`]. Returns null for a wrong index.
+///
+/// # Safety
+/// `graphs` comes from [`dot_linker_load`], `len` is a valid pointer
+#[no_mangle]
+pub unsafe extern "C" fn dot_linker_edges(
+    graphs: *const DotLinkerGraphs,
+    index: usize,
+    len: *mut usize
+) -> *mut DotLinkerEdge {
+    catch_panic(ptr::null_mut(), || {
+        let Some((_, graph)) = graphs.as_ref().and_then(|graphs| graphs.0.get(index)) else {
+            return ptr::null_mut();
+        };
+        let edges = named_edges(graph)
+            .map(|(from, to, edge)| DotLinkerEdge {
+                from: c_string(from),
+                to: c_string(to),
+                kind: match edge.kind {
+                    EdgeKind::Direct => 0,
+                    EdgeKind::Indirect => 1,
+                    EdgeKind::Synthetic => 2,
+                },
+                count: edge.count,
+            })
+            .collect::<Box<[_]>>();
+        *len = edges.len();
+        Box::into_raw(edges).cast()
+    })
+}
+
+/// # Safety
+/// `edges` and `len` come from [`dot_linker_edges`]
+#[no_mangle]
+pub unsafe extern "C" fn dot_linker_edges_free(edges: *mut DotLinkerEdge, len: usize) {
+    catch_panic((), || {
+        if edges.is_null() {
+            return;
+        }
+        let edges = Box::from_raw(ptr::slice_from_raw_parts_mut(edges, len));
+        for edge in edges.iter() {
+            drop(CString::from_raw(edge.from));
+            drop(CString::from_raw(edge.to));
+        }
+    })
+}
+
+/// Also releases interned names no other graphs use.
+///
+/// # Safety
+/// `graphs` comes from [`dot_linker_load`] and is not used afterwards
+#[no_mangle]
+pub unsafe extern "C" fn dot_linker_free(graphs: *mut DotLinkerGraphs) {
+    catch_panic((), || {
+        if !graphs.is_null() {
+            drop(Box::from_raw(graphs));
+            intern::release_unused();
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use super::*;
+
+    #[test]
+    fn test_c_interface() {
+        let root = crate::linker::test_path("ffi");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("a.dot"), "digraph { a -> b; x -> y }").unwrap();
+        fs::write(root.join("b.dot"), "digraph { b -> c }").unwrap();
+        fs::write(root.join("remove"), "^x$\n").unwrap();
+        fs::write(root.join("config"), format!("remove_nodes {}\n\nlink\n", root.join("remove").display())).unwrap();
+
+        let inputs = [root.join("a.dot"), root.join("b.dot")]
+            .map(|p| CString::new(p.to_str().unwrap()).unwrap());
+        let pointers = inputs.iter().map(|p| p.as_ptr()).collect::<Vec<_>>();
+        let config = CString::new(root.join("config").to_str().unwrap()).unwrap();
+        unsafe {
+            let graphs = dot_linker_load(pointers.as_ptr(), pointers.len());
+            assert!(!graphs.is_null());
+            assert_eq!(dot_linker_graph_count(graphs), 2);
+            assert_eq!(dot_linker_run_config(graphs, config.as_ptr()), 0);
+            assert_eq!(dot_linker_graph_count(graphs), 1);
+
+            let mut len = 0;
+            let edges = dot_linker_edges(graphs, 0, &mut len);
+            let names = std::slice::from_raw_parts(edges, len)
+                .iter()
+                .map(|e| (
+                    CStr::from_ptr(e.from).to_str().unwrap(),
+                    CStr::from_ptr(e.to).to_str().unwrap(),
+                    e.kind,
+                    e.count
+                ))
+                .collect::<Vec<_>>();
+            assert_eq!(names, [("a", "b", 0, 1), ("b", "c", 0, 1)]);
+            dot_linker_edges_free(edges, len);
+            assert!(dot_linker_edges(graphs, 1, &mut len).is_null());
+            dot_linker_free(graphs);
+        }
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_catch_panic() {
+        assert_eq!(catch_panic(-1, || 0), 0);
+        assert_eq!(catch_panic(-1, || panic!("unwinding into C")), -1);
+    }
+}
@@ -1,27 +1,212 @@
 use std::collections::HashMap;
-use std::hash::Hash;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
 use petgraph::Graph;
-use petgraph::graph::NodeIndex;
+use petgraph::graph::{EdgeIndex, NodeIndex};
 use petgraph::prelude::EdgeRef;
+use crate::linker::edge::EdgeLabel;
+use crate::linker::node::{try_add_edge, try_add_node, CallGraph, Node};
 
+/// Node data that can absorb a node with the same name from another graph
+pub trait MergeNode {
+    fn merge(&mut self, other: &Self);
+}
+
+impl MergeNode for String {
+    fn merge(&mut self, _: &Self) {}
+}
+
+impl MergeNode for Arc<str> {
+    fn merge(&mut self, _: &Self) {}
+}
+
+impl MergeNode for Node {
+    /// Attributes seen first win, source files of both nodes are kept.
+    /// Sources are compared by pointer, every input shares one `Arc` from [`record_shared_source`],
+    /// so merging a node found in thousands of inputs doesn't compare their paths
+    fn merge(&mut self, other: &Self) {
+        for (key, value) in &other.attrs {
+            if !self.attrs.contains_key(key) {
+                self.attrs.insert(key.clone(), value.clone());
+                if other.html_attrs.contains(key) {
+                    self.html_attrs.insert(key.clone());
+                }
+            }
+        }
+        for source in &other.sources {
+            if !self.sources.iter().any(|known| Arc::ptr_eq(known, source)) {
+                self.sources.push(source.clone());
+            }
+        }
+    }
+}
+
+/// Mark all nodes of a graph as coming from the `path` input file
+pub fn record_source(graph: &mut CallGraph, path: &Path) {
+    record_shared_source(graph, &Arc::from(path));
+}
+
+/// Same as [`record_source`] with the path shared by all graphs of an input
+pub fn record_shared_source(graph: &mut CallGraph, source: &Arc<Path>) {
+    for node in graph.node_weights_mut() {
+        node.sources.push(source.clone());
+    }
+}
+
+/// Merge graphs into one, nodes with the same name become a single node.
+/// All edges are kept, so calls of an edge found in several graphs add up.
+///
+/// # Panics
+/// If the linked graph doesn't fit in [`MAX_GRAPH_SIZE`](crate::linker::node::MAX_GRAPH_SIZE)
+/// nodes or edges, [`link_all_graphs_with`] returns an error instead
 #[must_use]
 pub fn link_all_graphs<N, E>(graphs: &[Graph<N, E>]) -> Graph<N, E>
-where N: Clone + Hash + Eq, E: Clone {
-    let mut result = Graph::<N, E>::new();
-    let mut mapping: HashMap<&N, NodeIndex> = HashMap::new();
+where N: Clone + AsRef<str> + MergeNode, E: EdgeLabel {
+    link_all_graphs_with(graphs, false).unwrap_or_else(|err| panic!("{err}"))
+}
+
+/// Add an edge of a linked graph. With `unique` edges calls of a duplicate are added to the first edge
+fn link_edge<N, E: EdgeLabel>(
+    result: &mut Graph<N, E>,
+    unique: Option<&mut HashMap<(NodeIndex, NodeIndex), EdgeIndex>>,
+    (from, to): (NodeIndex, NodeIndex),
+    edge: E
+) -> io::Result<()> {
+    let Some(unique) = unique else {
+        try_add_edge(result, from, to, edge)?;
+        return Ok(());
+    };
+    match unique.get(&(from, to)) {
+        Some(&first) => result[first].add_count(edge.count()),
+        None => {
+            unique.insert((from, to), try_add_edge(result, from, to, edge)?);
+        }
+    }
+    Ok(())
+}
+
+/// Same as [`link_all_graphs`], with `unique_edges` only the first edge between
+/// two linked nodes is added, whichever graph it comes from, and counts of the others are added to it.
+/// Fails if the linked graph is too large for `u32` indices.
+pub fn link_all_graphs_with<N, E>(graphs: &[Graph<N, E>], unique_edges: bool) -> io::Result<Graph<N, E>>
+where N: Clone + AsRef<str> + MergeNode, E: EdgeLabel {
+    // Nodes of different graphs may merge, but edges are all kept without `unique_edges`
+    let mut result = Graph::<N, E>::with_capacity(0, graphs.iter().map(Graph::edge_count).sum());
+    let mut mapping: HashMap<&str, NodeIndex> = HashMap::new();
+    let mut edges = unique_edges.then(HashMap::new);
     for g in graphs {
         for v in g.node_weights() {
-            if !mapping.contains_key(v) {
-                mapping.insert(v, result.add_node(v.clone()));
+            if let Some(&idx) = mapping.get(v.as_ref()) {
+                result[idx].merge(v);
+            } else {
+                mapping.insert(v.as_ref(), try_add_node(&mut result, v.clone())?);
             }
         }
         for edge in g.edge_references() {
-            result.add_edge(
-                mapping[&g[edge.source()]],
-                mapping[&g[edge.target()]],
-                edge.weight().clone()
-            );
+            let from = mapping[g[edge.source()].as_ref()];
+            let to = mapping[g[edge.target()].as_ref()];
+            link_edge(&mut result, edges.as_mut(), (from, to), edge.weight().clone())?;
+        }
+    }
+    Ok(result)
+}
+
+/// Like [`link_all_graphs`], but moves nodes and edges out of the graphs.
+/// Every input graph is dropped as soon as it is linked.
+///
+/// # Panics
+/// If the linked graph is too large like [`link_all_graphs`]
+#[must_use]
+pub fn link_graphs<N, E>(graphs: Vec<Graph<N, E>>) -> Graph<N, E>
+where N: AsRef<str> + MergeNode, E: EdgeLabel {
+    link_graphs_with(graphs, false).unwrap_or_else(|err| panic!("{err}"))
+}
+
+/// Same as [`link_graphs`], folding duplicate edges with `unique_edges` like [`link_all_graphs_with`].
+/// Fails if the linked graph is too large for `u32` indices.
+pub fn link_graphs_with<N, E>(graphs: Vec<Graph<N, E>>, unique_edges: bool) -> io::Result<Graph<N, E>>
+where N: AsRef<str> + MergeNode, E: EdgeLabel {
+    let mut result = Graph::<N, E>::with_capacity(0, graphs.iter().map(Graph::edge_count).sum());
+    let mut mapping: HashMap<String, NodeIndex> = HashMap::new();
+    let mut unique = unique_edges.then(HashMap::new);
+    for g in graphs {
+        let (nodes, edges) = g.into_nodes_edges();
+        let local = nodes.into_iter()
+            .map(|v| {
+                let v = v.weight;
+                if let Some(&idx) = mapping.get(v.as_ref()) {
+                    result[idx].merge(&v);
+                    Ok(idx)
+                } else {
+                    let name = v.as_ref().to_string();
+                    let idx = try_add_node(&mut result, v)?;
+                    mapping.insert(name, idx);
+                    Ok(idx)
+                }
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        for edge in edges {
+            let endpoints = (local[edge.source().index()], local[edge.target().index()]);
+            link_edge(&mut result, unique.as_mut(), endpoints, edge.weight)?;
         }
     }
-    result
-}
\ No newline at end of file
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::linker::edge::EdgeKind;
+    use super::*;
+
+    #[test]
+    fn test_link_provenance() {
+        let mut first = CallGraph::new();
+        let a = first.add_node("a".into());
+        let b = first.add_node("b".into());
+        first.add_edge(a, b, EdgeKind::Direct.into());
+        first[b].attrs.insert("label".to_string(), "B".to_string());
+        record_source(&mut first, Path::new("first.dot"));
+
+        let mut second = CallGraph::new();
+        let b = second.add_node("b".into());
+        let c = second.add_node("c".into());
+        second.add_edge(b, c, EdgeKind::Direct.into());
+        second[b].attrs.insert("label".to_string(), "other".to_string());
+        second[b].attrs.insert("shape".to_string(), "box".to_string());
+        record_source(&mut second, Path::new("second.dot"));
+
+        let graphs = [first, second];
+        let linked = link_all_graphs(&graphs);
+        assert_eq!(linked.node_count(), 3);
+        assert_eq!(linked.edge_count(), 2);
+
+        let b = linked.node_weights().find(|n| &*n.name == "b").unwrap();
+        assert_eq!(b.attrs["label"], "B");
+        assert_eq!(b.attrs["shape"], "box");
+        assert_eq!(
+            b.sources.iter().map(AsRef::as_ref).collect::<Vec<_>>(),
+            [Path::new("first.dot"), Path::new("second.dot")]
+        );
+        let c = linked.node_weights().find(|n| &*n.name == "c").unwrap();
+        assert_eq!(c.sources.len(), 1);
+
+        let owned = link_graphs(graphs.to_vec());
+        assert_eq!(owned.node_count(), linked.node_count());
+        assert!(owned.node_weights().zip(linked.node_weights()).all(|(a, b)| a == b));
+        let edges = |g: &CallGraph| g.raw_edges().iter().map(|e| (e.source(), e.target())).collect::<Vec<_>>();
+        assert_eq!(edges(&owned), edges(&linked));
+
+        let doubled = [graphs[0].clone(), graphs[1].clone(), graphs[0].clone()];
+        assert_eq!(link_all_graphs(&doubled).edge_count(), 3);
+        // Copies of an input share its source
+        let a = link_all_graphs(&doubled).node_weights().find(|n| &*n.name == "a").unwrap().clone();
+        assert_eq!(a.sources.len(), 1);
+        assert_eq!(edges(&link_all_graphs_with(&doubled, true).unwrap()), edges(&linked));
+        assert_eq!(edges(&link_graphs_with(doubled.to_vec(), true).unwrap()), edges(&linked));
+        // Calls of an edge found in both copies of the first graph add up
+        let counts = |g: &CallGraph| g.edge_weights().map(|e| e.count).collect::<Vec<_>>();
+        assert_eq!(counts(&link_all_graphs_with(&doubled, true).unwrap()), [2, 1]);
+        assert_eq!(counts(&link_graphs_with(doubled.to_vec(), true).unwrap()), [2, 1]);
+    }
+}
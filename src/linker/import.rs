@@ -0,0 +1,637 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::fs;
+use std::sync::Arc;
+#[cfg(feature = "cli")]
+use clap::ValueEnum;
+use fancy_regex::Regex;
+use flate2::read::MultiGzDecoder;
+#[cfg(feature = "dot")]
+use graphviz_rust::parse;
+use log::{debug, info};
+use ruzstd::decoding::StreamingDecoder;
+use tracing::{field, info_span};
+use petgraph::graph::{EdgeIndex, NodeIndex};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+#[cfg(feature = "dot")]
+use crate::linker::conversion::{graphviz_to_graph_checked, split_dot_graphs};
+use crate::linker::conversion::DuplicateNodes;
+use crate::linker::edge::{Edge, EdgeKind, EdgeLabel};
+use crate::linker::graph_link::{link_graphs_with, record_shared_source};
+use crate::linker::import::cache::ParseCache;
+use crate::linker::import::callgrind::callgrind_to_graph_with;
+use crate::linker::import::dot_stream::stream_dot_checked;
+use crate::linker::import::ftrace::ftrace_to_graph_with;
+use crate::linker::import::gprof::gprof_to_graph_with;
+use crate::linker::import::perf::perf_to_graph_with;
+#[cfg(feature = "dot")]
+use crate::linker::node::node_limit_error;
+use crate::linker::node::{check_node_limit, try_add_node, try_add_edge, CallGraph, Node};
+
+pub mod cache;
+pub mod callgrind;
+pub mod coverage;
+pub mod dot_stream;
+pub mod ftrace;
+pub mod gprof;
+pub mod kallsyms;
+pub mod nm;
+pub mod perf;
+
+/// Format of a single input file
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum InputFormat {
+    /// Graphviz .dot graph
+    Dot,
+    /// Graphviz .dot graph read statement by statement without a syntax tree, for huge graphs.
+    /// Subgraphs are not supported
+    DotStream,
+    /// Call graph section of `gprof -q` output
+    Gprof,
+    /// `perf script` output or folded call stacks
+    Perf,
+    /// Linux ftrace `function_graph` trace
+    Ftrace,
+    /// Valgrind `callgrind.out.*` profile
+    Callgrind,
+}
+
+/// `path` without a `.gz` or `.zst` compression suffix
+fn strip_compression(path: &Path) -> PathBuf {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("gz" | "zst") => path.with_extension(""),
+        _ => path.to_path_buf(),
+    }
+}
+
+impl InputFormat {
+    /// Guess input format from the file name, falling back to dot
+    #[must_use]
+    pub fn detect(path: &Path) -> Self {
+        let path = &strip_compression(path);
+        let file_name = path.file_name().and_then(|n| n.to_str());
+        if file_name.is_some_and(|n| n.starts_with("callgrind.out")) {
+            return InputFormat::Callgrind;
+        }
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("gprof") => InputFormat::Gprof,
+            Some("perf" | "folded") => InputFormat::Perf,
+            Some("ftrace") => InputFormat::Ftrace,
+            _ => InputFormat::Dot,
+        }
+    }
+}
+
+/// What to do with dot files containing several graphs
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum MultiGraph {
+    /// Link all graphs from a file into one
+    #[default]
+    Merge,
+    /// Process every graph as a separate input, `foo.dot` graphs are saved to `foo.N.out.dot`
+    Split,
+}
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Open an input file, decompressing gzip and zstd data on the fly.
+/// Compression is detected by the magic bytes, not by the extension.
+pub fn open_input(path: &Path) -> io::Result<Box<dyn Read>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut magic = [0u8; 4];
+    let mut magic_len = 0;
+    while magic_len < magic.len() {
+        let read = reader.read(&mut magic[magic_len..])?;
+        if read == 0 {
+            break;
+        }
+        magic_len += read;
+    }
+    let reader = io::Cursor::new(magic[..magic_len].to_vec()).chain(reader);
+
+    if magic[..magic_len].starts_with(&GZIP_MAGIC) {
+        Ok(Box::new(MultiGzDecoder::new(reader)))
+    } else if magic[..magic_len].starts_with(&ZSTD_MAGIC) {
+        let decoder = StreamingDecoder::new(reader)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(Box::new(decoder))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// Read the whole input file, see [`open_input`]
+pub fn read_input(path: &Path) -> io::Result<String> {
+    let mut contents = String::new();
+    open_input(path)?.read_to_string(&mut contents)?;
+    Ok(contents)
+}
+
+fn is_dot_file(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return false;
+    };
+    [".dot", ".dot.gz", ".dot.zst"].iter().any(|ext| name.ends_with(ext))
+}
+
+/// Find all dot files under `dir`, including compressed ones.
+/// Entries are visited in name order, so the result does not depend on the file system.
+/// Paths matching `exclude` are skipped, excluded directories are not entered.
+/// Symlinks to directories are not followed, so links to a parent can't loop.
+pub fn discover_inputs(dir: &Path, exclude: Option<&Regex>) -> io::Result<Vec<PathBuf>> {
+    let mut entries = fs::read_dir(dir)?
+        .map(|entry| entry.and_then(|e| Ok((e.path(), e.file_type()?))))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut found = vec![];
+    for (path, file_type) in entries {
+        let excluded = exclude.is_some_and(|re| {
+            re.is_match(&path.to_string_lossy()).unwrap_or(false)
+        });
+        if excluded {
+            continue;
+        }
+        if file_type.is_dir() {
+            found.extend(discover_inputs(&path, exclude)?);
+        } else if is_dot_file(&path) && !path.is_dir() {
+            found.push(path);
+        }
+    }
+    Ok(found)
+}
+
+/// Replace directories in the input list with the dot files beneath them
+pub fn expand_inputs(
+    paths: impl IntoIterator<Item = PathBuf>,
+    exclude: Option<&Regex>
+) -> io::Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    for path in paths {
+        if path.is_dir() {
+            files.extend(discover_inputs(&path, exclude)?);
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+/// Graphs of a dot file and the number of repeated node statements in it
+type DotGraphs = (Vec<CallGraph>, usize);
+
+/// Graphs of a dot file, stopping with [`node_limit_error`] once they get more than `max_nodes` together
+#[cfg(feature = "dot")]
+fn parse_dot_graphs(
+    path: &Path,
+    data: &str,
+    unique_edges: bool,
+    duplicates: DuplicateNodes,
+    max_nodes: Option<usize>
+) -> io::Result<DotGraphs> {
+    let mut graphs = vec![];
+    let mut repeated = 0;
+    let mut nodes = 0;
+    for text in split_dot_graphs(data) {
+        let (graph, count) = parse(text)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            .and_then(|graph| {
+                graphviz_to_graph_checked(&graph, unique_edges, duplicates, max_nodes.map(|max| max - nodes))
+            })
+            .map_err(|e| match max_nodes {
+                Some(max) if e.kind() == io::ErrorKind::FileTooLarge => node_limit_error(max),
+                _ => e,
+            })
+            .map_err(|e| io::Error::new(e.kind(), format!("Failed to parse .dot graph {}: {e}", path.display())))?;
+        nodes += graph.node_count();
+        graphs.push(graph);
+        repeated += count;
+    }
+    Ok((graphs, repeated))
+}
+
+#[cfg(not(feature = "dot"))]
+fn parse_dot_graphs(
+    path: &Path,
+    _data: &str,
+    _unique_edges: bool,
+    _duplicates: DuplicateNodes,
+    _max_nodes: Option<usize>
+) -> io::Result<DotGraphs> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("Can't read .dot graph {}: built without the dot feature", path.display())
+    ))
+}
+
+/// Convert a profile with `to_graph`, naming the file if the graph is too large
+fn read_profile(
+    path: &Path,
+    to_graph: fn(&str, Option<usize>) -> io::Result<CallGraph>,
+    max_nodes: Option<usize>
+) -> io::Result<CallGraph> {
+    to_graph(&read_input(path)?, max_nodes)
+        .map_err(|e| io::Error::new(e.kind(), format!("Failed to read {}: {e}", path.display())))
+}
+
+/// Read a single input file.
+/// Several graphs are returned only for dot files with several graphs and `MultiGraph::Split`.
+pub fn read_graphs(
+    path: &Path,
+    format: InputFormat,
+    multi_graph: MultiGraph
+) -> io::Result<Vec<CallGraph>> {
+    read_graphs_with(path, format, multi_graph, false, DuplicateNodes::Merge, None)
+}
+
+/// Same as [`read_graphs`], with `unique_edges` dot graphs get only the first edge
+/// between two nodes. Profiles get one edge per caller and callee counting its calls.
+/// Nodes of dot graphs declared several times are handled by `duplicates`,
+/// the number of repeated declarations is logged.
+/// Reading stops with [`check_node_limit`] as soon as the graphs of the file get more than `max_nodes`.
+pub fn read_graphs_with(
+    path: &Path,
+    format: InputFormat,
+    multi_graph: MultiGraph,
+    unique_edges: bool,
+    duplicates: DuplicateNodes,
+    max_nodes: Option<usize>
+) -> io::Result<Vec<CallGraph>> {
+    let graph = match format {
+        InputFormat::Dot | InputFormat::DotStream => {
+            let (graphs, repeated) = if format == InputFormat::Dot {
+                parse_dot_graphs(path, &read_input(path)?, unique_edges, duplicates, max_nodes)?
+            } else {
+                stream_dot_checked(open_input(path)?, unique_edges, duplicates, max_nodes).map_err(|e| io::Error::new(
+                    e.kind(),
+                    format!("Failed to read .dot graph {}: {e}", path.display())
+                ))?
+            };
+            if repeated > 0 {
+                info!("Merged {repeated} duplicate node declarations of {}", path.display());
+            }
+            if multi_graph == MultiGraph::Split {
+                return Ok(graphs);
+            }
+            match <[CallGraph; 1]>::try_from(graphs) {
+                Ok([graph]) => graph,
+                Err(graphs) => link_graphs_with(graphs, unique_edges)?,
+            }
+        }
+        InputFormat::Gprof => read_profile(path, gprof_to_graph_with, max_nodes)?,
+        InputFormat::Perf => read_profile(path, perf_to_graph_with, max_nodes)?,
+        InputFormat::Ftrace => read_profile(path, ftrace_to_graph_with, max_nodes)?,
+        InputFormat::Callgrind => read_profile(path, callgrind_to_graph_with, max_nodes)?,
+    };
+    Ok(vec![graph])
+}
+
+/// Read one input, pairing every graph with the path it is saved to after processing
+fn read_object(
+    path: &Path,
+    format: Option<InputFormat>,
+    multi_graph: MultiGraph,
+    unique_edges: bool,
+    duplicates: DuplicateNodes,
+    max_nodes: Option<usize>,
+    cache: Option<&ParseCache>
+) -> io::Result<Vec<(PathBuf, CallGraph)>> {
+    debug!("reading {}", path.display());
+    let format = format.unwrap_or_else(|| InputFormat::detect(path));
+    let span = info_span!(
+        "input",
+        path = %path.display(),
+        format = ?format,
+        graphs = field::Empty,
+        nodes = field::Empty,
+        edges = field::Empty
+    ).entered();
+    let graphs = match cache {
+        Some(cache) => cache.read_graphs(path, format, multi_graph, unique_edges, duplicates, max_nodes)?,
+        None => read_graphs_with(path, format, multi_graph, unique_edges, duplicates, max_nodes)?,
+    };
+    span.record("graphs", graphs.len());
+    span.record("nodes", graphs.iter().map(CallGraph::node_count).sum::<usize>());
+    span.record("edges", graphs.iter().map(CallGraph::edge_count).sum::<usize>());
+    let split = graphs.len() > 1;
+    let source = Arc::from(path);
+    Ok(graphs.into_iter()
+        .enumerate()
+        .map(|(i, mut graph)| {
+            record_shared_source(&mut graph, &source);
+            (output_path(path, split.then_some(i)), graph)
+        })
+        .collect())
+}
+
+/// File a graph read from `input` is saved to after processing: `foo.dot` becomes `foo.out.dot`
+/// and graph `i` of a split file `foo.i.out.dot`. Outputs are not compressed, `foo.dot.gz` becomes `foo.out.dot`
+#[must_use]
+pub fn output_path(input: &Path, split_index: Option<usize>) -> PathBuf {
+    let input = strip_compression(input);
+    match split_index {
+        Some(i) => input.with_extension(format!("{i}.out.dot")),
+        None => input.with_extension("out.dot"),
+    }
+}
+
+/// Read all inputs, pairing every graph with the path it is saved to after processing.
+/// If `format` is not provided, it is detected for every file.
+/// With the `parallel` feature files are read on all cores, the result keeps the input order.
+pub fn read_inputs(
+    files: &[PathBuf],
+    format: Option<InputFormat>,
+    multi_graph: MultiGraph
+) -> io::Result<Vec<(PathBuf, CallGraph)>> {
+    read_inputs_with(files, format, multi_graph, false, DuplicateNodes::Merge, None, None)
+}
+
+/// Like [`read_inputs`], reading every file with [`read_graphs_with`].
+/// Inputs found in `cache` are loaded from it and parsed ones are added.
+pub fn read_inputs_with(
+    files: &[PathBuf],
+    format: Option<InputFormat>,
+    multi_graph: MultiGraph,
+    unique_edges: bool,
+    duplicates: DuplicateNodes,
+    max_nodes: Option<usize>,
+    cache: Option<&ParseCache>
+) -> io::Result<Vec<(PathBuf, CallGraph)>> {
+    // Results are collected in input order first, so the error of the first failing input is returned
+    #[cfg(feature = "parallel")]
+    let objects = files.par_iter()
+        .map(|path| read_object(path, format, multi_graph, unique_edges, duplicates, max_nodes, cache))
+        .collect::<Vec<_>>()
+        .into_iter()
+        .collect::<io::Result<Vec<_>>>()?;
+    #[cfg(not(feature = "parallel"))]
+    let objects = files.iter()
+        .map(|path| read_object(path, format, multi_graph, unique_edges, duplicates, max_nodes, cache))
+        .collect::<io::Result<Vec<_>>>()?;
+    Ok(objects.into_iter().flatten().collect())
+}
+
+/// Graphs of readable inputs and the inputs that failed to read
+pub type ReadOutcome = (Vec<(PathBuf, CallGraph)>, Vec<(PathBuf, io::Error)>);
+
+/// Same as [`read_inputs_with`], but every input is read and the ones that fail,
+/// e.g. unparsable dot files, are returned with their errors instead of failing the batch
+pub fn read_inputs_skipping_bad(
+    files: &[PathBuf],
+    format: Option<InputFormat>,
+    multi_graph: MultiGraph,
+    unique_edges: bool,
+    duplicates: DuplicateNodes,
+    max_nodes: Option<usize>,
+    cache: Option<&ParseCache>
+) -> ReadOutcome {
+    #[cfg(feature = "parallel")]
+    let objects = files.par_iter()
+        .map(|path| read_object(path, format, multi_graph, unique_edges, duplicates, max_nodes, cache))
+        .collect::<Vec<_>>();
+    #[cfg(not(feature = "parallel"))]
+    let objects = files.iter()
+        .map(|path| read_object(path, format, multi_graph, unique_edges, duplicates, max_nodes, cache))
+        .collect::<Vec<_>>();
+    let mut graphs = vec![];
+    let mut failed = vec![];
+    for (path, object) in files.iter().zip(objects) {
+        match object {
+            Ok(object) => graphs.extend(object),
+            Err(err) => failed.push((path.clone(), err)),
+        }
+    }
+    (graphs, failed)
+}
+
+/// Helper for importers: creates nodes by name on first use.
+#[derive(Default)]
+pub(crate) struct CallGraphBuilder {
+    graph: CallGraph,
+    mapping: HashMap<Arc<str>, NodeIndex>,
+    /// First edge between two nodes added with [`Self::add_edge`] if duplicates are folded into it
+    edges: Option<HashMap<(NodeIndex, NodeIndex), EdgeIndex>>,
+    /// Adding more nodes fails with [`check_node_limit`]
+    max_nodes: Option<usize>,
+}
+
+impl CallGraphBuilder {
+    /// Builder adding only the first edge between two nodes with [`Self::add_edge`],
+    /// calls of later ones are added to its count
+    pub(crate) fn with_unique_edges(unique_edges: bool) -> Self {
+        Self { edges: unique_edges.then(HashMap::new), ..Self::default() }
+    }
+
+    /// Fail to add nodes beyond `max` instead of building the whole graph
+    pub(crate) fn with_max_nodes(self, max: Option<usize>) -> Self {
+        Self { max_nodes: max, ..self }
+    }
+
+    pub(crate) fn node(&mut self, name: &str) -> io::Result<NodeIndex> {
+        if let Some(&idx) = self.mapping.get(name) {
+            return Ok(idx);
+        }
+        check_node_limit(self.graph.node_count() + 1, self.max_nodes)?;
+        let node = Node::from(name);
+        let key = node.name.clone();
+        let idx = try_add_node(&mut self.graph, node)?;
+        self.mapping.insert(key, idx);
+        Ok(idx)
+    }
+
+    /// Add a direct edge counting `count` calls, creating missing nodes. Builders with unique edges
+    /// store the count once per caller and callee, no edge is added for zero calls
+    pub(crate) fn add_calls(&mut self, caller: &str, callee: &str, count: u64) -> io::Result<()> {
+        let from = self.node(caller)?;
+        let to = self.node(callee)?;
+        if count > 0 {
+            self.add_edge(from, to, Edge::new(EdgeKind::Direct, count))?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn add_edge(&mut self, from: NodeIndex, to: NodeIndex, edge: Edge) -> io::Result<()> {
+        let Some(edges) = &mut self.edges else {
+            try_add_edge(&mut self.graph, from, to, edge)?;
+            return Ok(());
+        };
+        match edges.get(&(from, to)) {
+            Some(&first) => self.graph[first].add_count(edge.count),
+            None => {
+                edges.insert((from, to), try_add_edge(&mut self.graph, from, to, edge)?);
+            }
+        }
+        Ok(())
+    }
+
+    /// Add edges like [`Self::add_edge`], allocating space for all of them first
+    pub(crate) fn add_edges(
+        &mut self,
+        edges: impl IntoIterator<Item = (NodeIndex, NodeIndex, Edge)>
+    ) -> io::Result<()> {
+        let edges = edges.into_iter();
+        let count = edges.size_hint().0;
+        self.graph.reserve_edges(count);
+        if let Some(unique) = &mut self.edges {
+            unique.reserve(count);
+        }
+        for (from, to, edge) in edges {
+            self.add_edge(from, to, edge)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn graph_mut(&mut self) -> &mut CallGraph {
+        &mut self.graph
+    }
+
+    pub(crate) fn build(self) -> CallGraph {
+        self.graph
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use super::*;
+
+    #[test]
+    fn test_output_path() {
+        assert_eq!(output_path(Path::new("a/foo.dot"), None), Path::new("a/foo.out.dot"));
+        assert_eq!(output_path(Path::new("a/foo.dot.gz"), None), Path::new("a/foo.out.dot"));
+        assert_eq!(output_path(Path::new("foo.dot.zst"), Some(2)), Path::new("foo.2.out.dot"));
+    }
+
+    #[test]
+    fn test_detect_compressed() {
+        assert_eq!(InputFormat::detect(Path::new("a/b.dot.gz")), InputFormat::Dot);
+        assert_eq!(InputFormat::detect(Path::new("b.gprof.zst")), InputFormat::Gprof);
+        assert_eq!(InputFormat::detect(Path::new("callgrind.out.123.gz")), InputFormat::Callgrind);
+    }
+
+    #[test]
+    fn test_builder_add_edges() {
+        let mut builder = CallGraphBuilder::with_unique_edges(true);
+        let [a, b] = ["a", "b"].map(|name| builder.node(name).unwrap());
+        let edges = [EdgeKind::Direct, EdgeKind::Direct, EdgeKind::Indirect].map(Edge::from);
+        builder.add_edges([(a, b, edges[0]), (b, a, edges[1]), (a, b, edges[2])]).unwrap();
+        builder.add_calls("b", "c", 2).unwrap();
+        builder.add_calls("b", "c", 3_000_000_000).unwrap();
+        builder.add_calls("c", "d", 0).unwrap();
+        let graph = builder.build();
+        assert_eq!((graph.node_count(), graph.edge_count()), (4, 3));
+        assert_eq!(graph.edge_weights().filter(|edge| edge.kind == EdgeKind::Indirect).count(), 0);
+        assert_eq!(graph.edge_weights().map(|edge| edge.count).collect::<Vec<_>>(), [2, 1, 3_000_000_002]);
+    }
+
+    #[test]
+    fn test_max_nodes() {
+        let mut builder = CallGraphBuilder::default().with_max_nodes(Some(2));
+        builder.add_calls("a", "b", 1).unwrap();
+        builder.add_calls("b", "a", 1).unwrap();
+        assert_eq!(builder.add_calls("b", "c", 1).unwrap_err().kind(), io::ErrorKind::FileTooLarge);
+
+        let path = crate::linker::test_path("max-nodes.dot");
+        fs::write(&path, "digraph { a -> b } digraph { b -> c }").unwrap();
+        for format in [InputFormat::Dot, InputFormat::DotStream] {
+            let read = |max| read_graphs_with(&path, format, MultiGraph::Split, false, DuplicateNodes::Merge, Some(max));
+            assert_eq!(read(4).unwrap().len(), 2);
+            let err = read(3).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::FileTooLarge);
+            assert!(err.to_string().ends_with("more than the limit of 3 nodes"), "{err}");
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_read_gzip() {
+        let path = crate::linker::test_path("read-gzip.dot.gz");
+        let mut encoder = GzEncoder::new(File::create(&path).unwrap(), Compression::default());
+        encoder.write_all(b"digraph { a -> b }").unwrap();
+        encoder.finish().unwrap();
+
+        assert_eq!(read_input(&path).unwrap(), "digraph { a -> b }");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_read_inputs_order() {
+        let root = crate::linker::test_path("read-order");
+        fs::create_dir_all(&root).unwrap();
+        let files = (0..32)
+            .map(|i| {
+                let path = root.join(format!("{i}.folded"));
+                fs::write(&path, format!("main;f{i} 1\n")).unwrap();
+                path
+            })
+            .collect::<Vec<_>>();
+
+        let objects = read_inputs(&files, None, MultiGraph::Merge).unwrap();
+        for (i, (path, graph)) in objects.iter().enumerate() {
+            assert_eq!(*path, root.join(format!("{i}.out.dot")));
+            assert!(graph.node_weights().any(|node| *node.name == format!("f{i}")));
+        }
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[cfg(feature = "dot")]
+    #[test]
+    fn test_read_inputs_skipping_bad() {
+        let root = crate::linker::test_path("skip-bad");
+        fs::create_dir_all(&root).unwrap();
+        let files = ["good.dot", "bad.dot", "missing.dot"].map(|file| root.join(file));
+        fs::write(&files[0], "digraph { a -> b }").unwrap();
+        fs::write(&files[1], "digraph { a -> }").unwrap();
+
+        // The first failing input is reported, however reading is scheduled
+        for _ in 0..20 {
+            let err = read_inputs(&files, None, MultiGraph::Merge).unwrap_err();
+            assert!(err.to_string().contains("bad.dot"));
+            let reversed = [files[2].clone(), files[1].clone()];
+            assert_eq!(read_inputs(&reversed, None, MultiGraph::Merge).unwrap_err().kind(), io::ErrorKind::NotFound);
+        }
+        let (graphs, failed) = read_inputs_skipping_bad(&files, None, MultiGraph::Merge, false, DuplicateNodes::Merge, None, None);
+        assert_eq!(graphs.len(), 1);
+        assert_eq!(graphs[0].0, root.join("good.out.dot"));
+        assert_eq!(failed.iter().map(|(path, _)| path).collect::<Vec<_>>(), [&files[1], &files[2]]);
+        let message = failed[0].1.to_string();
+        assert!(message.starts_with(&format!("Failed to parse .dot graph {}: ", files[1].display())));
+        assert_eq!(failed[1].1.kind(), io::ErrorKind::NotFound);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_discover_inputs() {
+        let root = crate::linker::test_path("discover");
+        let _ = fs::remove_dir_all(&root);
+        for dir in ["b", "a/nested", "skip"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for file in ["b/2.dot", "a/nested/1.dot.gz", "a/0.dot", "a/readme.txt", "skip/3.dot"] {
+            File::create(root.join(file)).unwrap();
+        }
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink("..", root.join("a/up")).unwrap();
+            std::os::unix::fs::symlink("../b", root.join("a/link.dot")).unwrap();
+        }
+
+        let exclude = Regex::new("skip$").unwrap();
+        let found = discover_inputs(&root, Some(&exclude)).unwrap();
+        assert_eq!(
+            found,
+            ["a/0.dot", "a/nested/1.dot.gz", "b/2.dot"].map(|f| root.join(f))
+        );
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,304 @@
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io;
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use log::{debug, warn};
+use petgraph::graph::NodeIndex;
+use crate::linker::conversion::DuplicateNodes;
+use crate::linker::edge::{Edge, EdgeKind};
+use crate::linker::import::{read_graphs_with, InputFormat, MultiGraph};
+use crate::linker::node::{check_graph_size, check_node_limit, CallGraph, Node};
+
+/// First line of every entry, entries of other versions are parsed differently and ignored.
+/// The revision changes with the conversion of inputs, e.g. since quoted dot names are unescaped
+/// or since HTML-like attributes are recorded.
+const HEADER: &str = concat!("inv-call-extract parse cache ", env!("CARGO_PKG_VERSION"), " r4");
+
+/// Suffix of files being written, so concurrent readers never see half an entry
+static TMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Directory of graphs converted from input files, keyed by file contents.
+/// Inputs that didn't change since the last run are loaded from it instead of being parsed.
+pub struct ParseCache {
+    dir: PathBuf,
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape(s: &str) -> Option<String> {
+    let mut unescaped = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        unescaped.push(match chars.next()? {
+            '\\' => '\\',
+            't' => '\t',
+            'n' => '\n',
+            'r' => '\r',
+            _ => return None,
+        });
+    }
+    Some(unescaped)
+}
+
+/// Entry format: the header, then a `g\tnodes\tedges` line per graph followed by its nodes and edges.
+/// Sizes let graphs be allocated up front, entries with bare `g` lines are still read.
+/// Nodes are `n\tname` lines with `a\tkey\tvalue` attribute and `s\tpath` source lines after them,
+/// attributes read from HTML ids have an `h\tkey` line,
+/// edges are `e\tfrom\tto\tkind` with node indices in the graph.
+fn write_entry(out: &mut impl Write, graphs: &[CallGraph]) -> io::Result<()> {
+    writeln!(out, "{HEADER}")?;
+    for graph in graphs {
+        writeln!(out, "g\t{}\t{}", graph.node_count(), graph.edge_count())?;
+        for node in graph.node_weights() {
+            writeln!(out, "n\t{}", escape(&node.name))?;
+            for (key, value) in &node.attrs {
+                writeln!(out, "a\t{}\t{}", escape(key), escape(value))?;
+                if node.html_attrs.contains(key) {
+                    writeln!(out, "h\t{}", escape(key))?;
+                }
+            }
+            for source in &node.sources {
+                writeln!(out, "s\t{}", escape(&source.to_string_lossy()))?;
+            }
+        }
+        for edge in graph.raw_edges() {
+            let (source, target) = (edge.source().index(), edge.target().index());
+            writeln!(out, "e\t{source}\t{target}\t{}\t{}", edge.weight.kind, edge.weight.count)?;
+        }
+    }
+    Ok(())
+}
+
+/// `None` if the entry is from another version or malformed
+fn parse_entry(data: &str) -> Option<Vec<CallGraph>> {
+    let mut lines = data.lines();
+    if lines.next()? != HEADER {
+        return None;
+    }
+    let mut graphs: Vec<CallGraph> = vec![];
+    // Nodes of one input share its path, like after reading it
+    let mut sources: HashMap<String, Arc<Path>> = HashMap::new();
+    for line in lines {
+        let mut fields = line.split('\t');
+        let tag = fields.next()?;
+        if tag == "g" {
+            let nodes = fields.next().map_or(Some(0), |count| count.parse().ok())?;
+            let edges = fields.next().map_or(Some(0), |count| count.parse().ok())?;
+            check_graph_size(nodes, edges).ok()?;
+            graphs.push(CallGraph::with_capacity(nodes, edges));
+            continue;
+        }
+        let graph = graphs.last_mut()?;
+        match tag {
+            "n" => {
+                graph.add_node(Node::new(&unescape(fields.next()?)?));
+            }
+            "a" => {
+                let key = unescape(fields.next()?)?;
+                let value = unescape(fields.next()?)?;
+                let last = NodeIndex::new(graph.node_count().checked_sub(1)?);
+                graph[last].attrs.insert(key, value);
+            }
+            "h" => {
+                let key = unescape(fields.next()?)?;
+                let last = NodeIndex::new(graph.node_count().checked_sub(1)?);
+                graph[last].html_attrs.insert(key);
+            }
+            "s" => {
+                let source = unescape(fields.next()?)?;
+                let last = NodeIndex::new(graph.node_count().checked_sub(1)?);
+                let source = sources.entry(source).or_insert_with_key(|source| Arc::from(Path::new(source)));
+                graph[last].sources.push(source.clone());
+            }
+            "e" => {
+                let from = NodeIndex::new(fields.next()?.parse().ok()?);
+                let to = NodeIndex::new(fields.next()?.parse().ok()?);
+                let kind = fields.next()?.parse::<EdgeKind>().ok()?;
+                let count = fields.next()?.parse::<u64>().ok()?;
+                if from.index() >= graph.node_count() || to.index() >= graph.node_count() {
+                    return None;
+                }
+                graph.add_edge(from, to, Edge::new(kind, count));
+            }
+            _ => return None,
+        }
+        if fields.next().is_some() {
+            return None;
+        }
+    }
+    Some(graphs)
+}
+
+impl ParseCache {
+    /// Use `dir` for the cache, creating it if needed
+    pub fn new(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    /// Key of an input: hash of its contents and of the settings affecting conversion
+    pub fn key(
+        path: &Path,
+        format: InputFormat,
+        multi_graph: MultiGraph,
+        unique_edges: bool,
+        duplicates: DuplicateNodes
+    ) -> io::Result<u64> {
+        let mut hasher = DefaultHasher::new();
+        format.hash(&mut hasher);
+        multi_graph.hash(&mut hasher);
+        unique_edges.hash(&mut hasher);
+        // Inputs are only checked while parsing, entries of a lenient policy don't pass a stricter one
+        duplicates.hash(&mut hasher);
+        let mut file = File::open(path)?;
+        let mut buffer = vec![0; 1 << 16];
+        loop {
+            let read = file.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            hasher.write(&buffer[..read]);
+        }
+        Ok(hasher.finish())
+    }
+
+    fn entry_path(&self, key: u64) -> PathBuf {
+        self.dir.join(format!("{key:016x}.graphs"))
+    }
+
+    /// Graphs stored for `key` by this version of the tool
+    #[must_use]
+    pub fn load(&self, key: u64) -> Option<Vec<CallGraph>> {
+        let data = fs::read_to_string(self.entry_path(key)).ok()?;
+        let graphs = parse_entry(&data);
+        if graphs.is_none() {
+            debug!("Ignoring stale or broken cache entry {key:016x}");
+        }
+        graphs
+    }
+
+    /// Store graphs for `key`, replacing an existing entry
+    pub fn store(&self, key: u64, graphs: &[CallGraph]) -> io::Result<()> {
+        let tmp = self.dir.join(format!(
+            "{key:016x}.{}.{}.tmp",
+            std::process::id(),
+            TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let result = File::create(&tmp).and_then(|file| {
+            let mut out = BufWriter::new(file);
+            write_entry(&mut out, graphs)?;
+            out.flush()
+        });
+        match result {
+            Ok(()) => fs::rename(&tmp, self.entry_path(key)),
+            Err(err) => {
+                let _ = fs::remove_file(&tmp);
+                Err(err)
+            }
+        }
+    }
+
+    /// [`read_graphs_with`] through the cache, failing to store an entry only logs a warning.
+    /// Cached graphs are checked against `max_nodes` after loading them, inputs over it are not stored
+    pub fn read_graphs(
+        &self,
+        path: &Path,
+        format: InputFormat,
+        multi_graph: MultiGraph,
+        unique_edges: bool,
+        duplicates: DuplicateNodes,
+        max_nodes: Option<usize>
+    ) -> io::Result<Vec<CallGraph>> {
+        let key = Self::key(path, format, multi_graph, unique_edges, duplicates)?;
+        if let Some(graphs) = self.load(key) {
+            debug!("{} loaded from cache", path.display());
+            check_node_limit(graphs.iter().map(CallGraph::node_count).sum(), max_nodes)
+                .map_err(|e| io::Error::new(e.kind(), format!("Failed to read {}: {e}", path.display())))?;
+            return Ok(graphs);
+        }
+        let graphs = read_graphs_with(path, format, multi_graph, unique_edges, duplicates, max_nodes)?;
+        if let Err(err) = self.store(key, &graphs) {
+            warn!("Failed to cache {}: {err}", path.display());
+        }
+        Ok(graphs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_round_trip() {
+        let mut graph = CallGraph::new();
+        let a = graph.add_node("with\ttab\\".into());
+        let b = graph.add_node("b".into());
+        graph[a].attrs.insert("label".to_string(), "line\nbreak".to_string());
+        graph[b].attrs.insert("label".to_string(), "<<i>b</i>>".to_string());
+        graph[b].html_attrs.insert("label".to_string());
+        graph[b].sources.push(Arc::from(Path::new("dir/b.dot")));
+        graph.add_edge(a, b, EdgeKind::Indirect.into());
+        graph.add_edge(b, b, Edge::new(EdgeKind::Direct, 7));
+        let graphs = [graph, CallGraph::new()];
+
+        let mut data = vec![];
+        write_entry(&mut data, &graphs).unwrap();
+        let data = String::from_utf8(data).unwrap();
+        let parsed = parse_entry(&data).unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert!(parsed[0].node_weights().eq(graphs[0].node_weights()));
+        assert_eq!(
+            parsed[0].raw_edges().iter().map(|e| (e.source(), e.target(), e.weight)).collect::<Vec<_>>(),
+            [(a, b, EdgeKind::Indirect.into()), (b, b, Edge::new(EdgeKind::Direct, 7))]
+        );
+        assert_eq!(parsed[1].node_count(), 0);
+
+        let stale = data.replacen(env!("CARGO_PKG_VERSION"), "0.0.0-old", 1);
+        assert!(parse_entry(&stale).is_none());
+        assert!(parse_entry(&format!("{data}e\t0\t7\tdirect\t1\n")).is_none());
+    }
+
+    #[test]
+    fn test_read_graphs() {
+        let root = crate::linker::test_path("parse-cache");
+        let _ = fs::remove_dir_all(&root);
+        let cache = ParseCache::new(root.join("cache")).unwrap();
+        let input = root.join("input.dot");
+        fs::write(&input, "digraph { a -> b }").unwrap();
+
+        let key = ParseCache::key(&input, InputFormat::DotStream, MultiGraph::Merge, false, DuplicateNodes::Merge).unwrap();
+        assert!(cache.load(key).is_none());
+        let graphs = cache.read_graphs(&input, InputFormat::DotStream, MultiGraph::Merge, false, DuplicateNodes::Merge, None).unwrap();
+        assert_eq!(graphs[0].edge_count(), 1);
+        assert_eq!(cache.load(key).unwrap()[0].node_count(), 2);
+        assert_ne!(key, ParseCache::key(&input, InputFormat::DotStream, MultiGraph::Split, false, DuplicateNodes::Merge).unwrap());
+
+        fs::write(&input, "digraph { a -> b -> c }").unwrap();
+        let graphs = cache.read_graphs(&input, InputFormat::DotStream, MultiGraph::Merge, false, DuplicateNodes::Merge, None).unwrap();
+        assert_eq!(graphs[0].edge_count(), 2);
+        assert_eq!(fs::read_dir(root.join("cache")).unwrap().count(), 2);
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
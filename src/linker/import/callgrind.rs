@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+use std::io;
+use crate::linker::import::CallGraphBuilder;
+use crate::linker::node::CallGraph;
+
+/// Resolve `(id) name`, `(id)` and plain `name` function specifications
+fn resolve_name<'a>(spec: &'a str, names: &mut HashMap<&'a str, &'a str>) -> Option<&'a str> {
+    let spec = spec.trim();
+    let Some(rest) = spec.strip_prefix('(') else {
+        return Some(spec);
+    };
+    let (id, name) = rest.split_once(')')?;
+    let name = name.trim();
+    if name.is_empty() {
+        names.get(id).copied()
+    } else {
+        names.insert(id, name);
+        Some(name)
+    }
+}
+
+/// Build a call graph from a `callgrind.out.*` profile.
+/// Every caller -> callee pair gets one edge, `calls=N` records of the pair are summed up in its count.
+pub fn callgrind_to_graph(data: &str) -> io::Result<CallGraph> {
+    callgrind_to_graph_with(data, None)
+}
+
+/// Same as [`callgrind_to_graph`], failing as soon as the graph gets more than `max_nodes`
+/// with [`check_node_limit`](crate::linker::node::check_node_limit)
+pub fn callgrind_to_graph_with(data: &str, max_nodes: Option<usize>) -> io::Result<CallGraph> {
+    let mut builder = CallGraphBuilder::with_unique_edges(true).with_max_nodes(max_nodes);
+    let mut names: HashMap<&str, &str> = HashMap::new();
+    let mut current: Option<&str> = None;
+    let mut callee: Option<&str> = None;
+
+    for line in data.lines() {
+        if let Some(spec) = line.strip_prefix("fn=") {
+            current = resolve_name(spec, &mut names);
+            if let Some(name) = current {
+                builder.node(name)?;
+            }
+        } else if let Some(spec) = line.strip_prefix("cfn=") {
+            callee = resolve_name(spec, &mut names);
+        } else if let Some(calls) = line.strip_prefix("calls=") {
+            let count = calls
+                .split_whitespace()
+                .next()
+                .and_then(|c| c.parse::<u64>().ok());
+            if let (Some(caller), Some(callee), Some(count)) = (current, callee, count) {
+                builder.add_calls(caller, callee, count)?;
+            }
+        }
+    }
+    Ok(builder.build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calls(graph: &CallGraph, from: &str, to: &str) -> Vec<u64> {
+        graph.raw_edges()
+            .iter()
+            .filter(|e| &*graph[e.source()].name == from && &*graph[e.target()].name == to)
+            .map(|e| e.weight.count)
+            .collect()
+    }
+
+    #[test]
+    fn test_callgrind_compressed_names() {
+        let data = "\
+version: 1
+creator: callgrind-3.22.0
+events: Ir
+
+fl=(1) main.c
+fn=(1) main
+16 20
+cfl=(1)
+cfn=(2) foo
+calls=3 0x401000
+16 400
+cfn=(3) bar
+calls=1 20
+17 30
+cfn=(3)
+calls=4 21
+18 40
+
+fn=(2)
+5 10
+cfn=(3)
+calls=2 20
+6 10
+
+fn=(3)
+20 5
+";
+        let graph = callgrind_to_graph(data).unwrap();
+        assert_eq!(graph.node_count(), 3);
+        assert_eq!(calls(&graph, "main", "foo"), [3]);
+        assert_eq!(calls(&graph, "main", "bar"), [5]);
+        assert_eq!(calls(&graph, "foo", "bar"), [2]);
+        assert_eq!(graph.edge_count(), 3);
+        assert!(graph.node_weights().all(|node| node.attrs.is_empty()));
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::HashSet;
+
+/// lcov tracefile records other than function counts
+const LCOV_KEYWORDS: [&str; 14] = [
+    "TN", "SF", "FN", "FNL", "FNA", "FNF", "FNH", "DA", "LF", "LH", "BRDA", "BRF", "BRH", "VER"
+];
+
+/// Whether an execution count is a positive number
+fn executed(count: &str) -> bool {
+    count.parse::<u64>().is_ok_and(|count| count > 0)
+}
+
+fn is_lcov_record(line: &str) -> bool {
+    line.split_once(':').is_some_and(|(keyword, _)| LCOV_KEYWORDS.contains(&keyword))
+}
+
+/// Whether a `Lines executed:P% of N` line of a gcov summary reports any executed line
+fn lines_executed(summary: &str) -> bool {
+    summary.split_once('%').and_then(|(percent, _)| percent.parse::<f64>().ok()).is_some_and(|p| p > 0.0)
+}
+
+/// Functions executed at least once according to a coverage report:
+/// - lcov tracefiles (`lcov --capture`, `llvm-cov export -format=lcov`): `FNDA:count,name`
+/// - `gcov -f` output: `Function 'name'` followed by `Lines executed:P% of N`
+/// - `.gcov` files written with `gcov -b`: `function name called count ...`
+/// - plain lists of covered function names, one per line
+#[must_use]
+pub fn covered_functions(data: &str) -> HashSet<String> {
+    let mut covered = HashSet::new();
+    // Function of the `gcov -f` summary being read
+    let mut summary = None;
+    for line in data.lines().map(str::trim) {
+        if let Some(record) = line.strip_prefix("FNDA:") {
+            if let Some((_, name)) = record.split_once(',').filter(|(count, _)| executed(count)) {
+                covered.insert(name.to_string());
+            }
+            continue;
+        }
+        if let Some(name) = line.strip_prefix("Function '").and_then(|name| name.strip_suffix('\'')) {
+            summary = Some(name);
+            continue;
+        }
+        if let Some(lines) = line.strip_prefix("Lines executed:") {
+            if let Some(name) = summary.take().filter(|_| lines_executed(lines)) {
+                covered.insert(name.to_string());
+            }
+            continue;
+        }
+        match line.split_whitespace().collect::<Vec<_>>()[..] {
+            ["function", name, "called", count, ..] if executed(count) => {
+                covered.insert(name.to_string());
+            }
+            [name] if name != "end_of_record" && !name.starts_with('#') && !is_lcov_record(name) => {
+                covered.insert(name.to_string());
+            }
+            _ => {}
+        }
+    }
+    covered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_covered_functions() {
+        let lcov = "\
+TN:
+SF:/src/main.c
+FN:3,main
+FN:10,unused
+FNDA:4,main
+FNDA:0,unused
+FNF:2
+FNH:1
+DA:3,4
+end_of_record
+";
+        assert_eq!(covered_functions(lcov), HashSet::from(["main".to_string()]));
+
+        let gcov = "\
+function parse called 12 returned 100% blocks executed 80%
+function cleanup called 0 returned 0% blocks executed 0%
+";
+        assert_eq!(covered_functions(gcov), HashSet::from(["parse".to_string()]));
+
+        // gcov -f m.c
+        let summary = "\
+Function 'main'
+Lines executed:100.00% of 1
+
+Function 'parse'
+Lines executed:100.00% of 1
+
+Function 'unused'
+Lines executed:0.00% of 1
+
+File 'm.c'
+Lines executed:66.67% of 3
+Creating 'm.c.gcov'
+
+Lines executed:66.67% of 3
+";
+        assert_eq!(covered_functions(summary), HashSet::from(["main", "parse"].map(String::from)));
+
+        let list = "# covered\nfoo\nns::bar\n";
+        assert_eq!(covered_functions(list), HashSet::from(["foo", "ns::bar"].map(String::from)));
+    }
+}
@@ -0,0 +1,441 @@
+use std::borrow::Cow;
+use std::io;
+use std::io::{BufReader, Bytes, Read};
+use std::iter::Peekable;
+use crate::linker::conversion::{edge_weight, unescape_dot, Declarations, DuplicateNodes};
+use crate::linker::import::CallGraphBuilder;
+use crate::linker::node::{node_limit_error, CallGraph};
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    /// Identifier, quoted strings are unescaped and HTML ids keep their angle brackets
+    Id { value: String, quoted: bool, html: bool },
+    LBrace,
+    RBrace,
+    LBracket,
+    RBracket,
+    Semicolon,
+    Comma,
+    Equals,
+    Colon,
+    /// `->` or `--`
+    Edge,
+}
+
+struct Lexer<R: Read> {
+    bytes: Peekable<Bytes<BufReader<R>>>,
+    line: usize,
+    peeked: Option<Token>,
+}
+
+fn is_id_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_' || b == b'.' || b >= 0x80
+}
+
+impl<R: Read> Lexer<R> {
+    fn new(reader: R) -> Self {
+        Self { bytes: BufReader::new(reader).bytes().peekable(), line: 1, peeked: None }
+    }
+
+    fn error(&self, message: &str) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {message}", self.line))
+    }
+
+    fn peek_byte(&mut self) -> io::Result<Option<u8>> {
+        match self.bytes.peek() {
+            Some(Ok(b)) => Ok(Some(*b)),
+            Some(Err(_)) => self.next_byte(),
+            None => Ok(None),
+        }
+    }
+
+    fn next_byte(&mut self) -> io::Result<Option<u8>> {
+        let byte = self.bytes.next().transpose()?;
+        if byte == Some(b'\n') {
+            self.line += 1;
+        }
+        Ok(byte)
+    }
+
+    /// Skip whitespace and comments
+    fn skip_blank(&mut self) -> io::Result<()> {
+        while let Some(b) = self.peek_byte()? {
+            match b {
+                b'#' => self.skip_line()?,
+                b'/' => {
+                    self.next_byte()?;
+                    match self.next_byte()? {
+                        Some(b'/') => self.skip_line()?,
+                        Some(b'*') => self.skip_block_comment()?,
+                        _ => return Err(self.error("unexpected '/'")),
+                    }
+                }
+                _ if b.is_ascii_whitespace() => {
+                    self.next_byte()?;
+                }
+                _ => break,
+            }
+        }
+        Ok(())
+    }
+
+    fn skip_line(&mut self) -> io::Result<()> {
+        while let Some(b) = self.next_byte()? {
+            if b == b'\n' {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn skip_block_comment(&mut self) -> io::Result<()> {
+        let mut star = false;
+        loop {
+            match self.next_byte()? {
+                Some(b'/') if star => return Ok(()),
+                Some(b) => star = b == b'*',
+                None => return Err(self.error("unterminated comment")),
+            }
+        }
+    }
+
+    fn id(&self, bytes: Vec<u8>, quoted: bool) -> io::Result<Token> {
+        let mut value = String::from_utf8(bytes).map_err(|_| self.error("identifier is not valid UTF-8"))?;
+        if quoted {
+            if let Cow::Owned(unescaped) = unescape_dot(&value) {
+                value = unescaped;
+            }
+        }
+        Ok(Token::Id { value, quoted, html: false })
+    }
+
+    /// Like graphviz, a quote right after any backslash is escaped, backslashes are not paired
+    fn quoted(&mut self) -> io::Result<Token> {
+        let mut value = vec![];
+        loop {
+            match self.next_byte()? {
+                Some(b'"') if value.last() != Some(&b'\\') => return self.id(value, true),
+                Some(b) => value.push(b),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    fn html(&mut self) -> io::Result<Token> {
+        let mut value = vec![b'<'];
+        let mut depth = 1;
+        while depth > 0 {
+            let b = self.next_byte()?.ok_or_else(|| self.error("unterminated HTML id"))?;
+            match b {
+                b'<' => depth += 1,
+                b'>' => depth -= 1,
+                _ => {}
+            }
+            value.push(b);
+        }
+        let value = String::from_utf8(value).map_err(|_| self.error("identifier is not valid UTF-8"))?;
+        Ok(Token::Id { value, quoted: true, html: true })
+    }
+
+    fn read_token(&mut self) -> io::Result<Option<Token>> {
+        self.skip_blank()?;
+        let Some(b) = self.next_byte()? else {
+            return Ok(None);
+        };
+        let token = match b {
+            b'{' => Token::LBrace,
+            b'}' => Token::RBrace,
+            b'[' => Token::LBracket,
+            b']' => Token::RBracket,
+            b';' => Token::Semicolon,
+            b',' => Token::Comma,
+            b'=' => Token::Equals,
+            b':' => Token::Colon,
+            b'"' => self.quoted()?,
+            b'<' => self.html()?,
+            b'-' if matches!(self.peek_byte()?, Some(b'>' | b'-')) => {
+                self.next_byte()?;
+                Token::Edge
+            }
+            _ if is_id_byte(b) || b == b'-' => {
+                let mut value = vec![b];
+                while let Some(b) = self.peek_byte()? {
+                    if !is_id_byte(b) {
+                        break;
+                    }
+                    value.push(b);
+                    self.next_byte()?;
+                }
+                self.id(value, false)?
+            }
+            _ => return Err(self.error(&format!("unexpected '{}'", char::from(b)))),
+        };
+        Ok(Some(token))
+    }
+
+    fn next(&mut self) -> io::Result<Option<Token>> {
+        match self.peeked.take() {
+            Some(token) => Ok(Some(token)),
+            None => self.read_token(),
+        }
+    }
+
+    fn peek(&mut self) -> io::Result<Option<&Token>> {
+        if self.peeked.is_none() {
+            self.peeked = self.read_token()?;
+        }
+        Ok(self.peeked.as_ref())
+    }
+
+    fn expect(&mut self, expected: &Token) -> io::Result<()> {
+        match self.next()? {
+            Some(token) if token == *expected => Ok(()),
+            token => Err(self.error(&format!("expected {expected:?}, found {token:?}"))),
+        }
+    }
+
+    fn expect_id(&mut self) -> io::Result<String> {
+        Ok(self.expect_value()?.0)
+    }
+
+    /// Identifier with whether it was an HTML id
+    fn expect_value(&mut self) -> io::Result<(String, bool)> {
+        match self.next()? {
+            Some(Token::Id { value, html, .. }) => Ok((value, html)),
+            token => Err(self.error(&format!("expected identifier, found {token:?}"))),
+        }
+    }
+}
+
+fn is_keyword(token: &Token, keyword: &str) -> bool {
+    matches!(token, Token::Id { value, quoted: false, .. } if value.eq_ignore_ascii_case(keyword))
+}
+
+/// Node id of a statement, ports are dropped
+fn node_id<R: Read>(lexer: &mut Lexer<R>, name: String) -> io::Result<String> {
+    while lexer.peek()? == Some(&Token::Colon) {
+        lexer.next()?;
+        lexer.expect_id()?;
+    }
+    Ok(name)
+}
+
+/// Read `[key = value, ...]` lists following a statement, values are marked if they are HTML ids
+fn attr_lists<R: Read>(lexer: &mut Lexer<R>) -> io::Result<Vec<(String, String, bool)>> {
+    let mut attrs = vec![];
+    while lexer.peek()? == Some(&Token::LBracket) {
+        lexer.next()?;
+        loop {
+            match lexer.next()? {
+                Some(Token::RBracket) => break,
+                Some(Token::Comma | Token::Semicolon) => {}
+                Some(Token::Id { value: key, .. }) => {
+                    lexer.expect(&Token::Equals)?;
+                    let (value, html) = lexer.expect_value()?;
+                    attrs.push((key, value, html));
+                }
+                token => return Err(lexer.error(&format!("unexpected {token:?} in attributes"))),
+            }
+        }
+    }
+    Ok(attrs)
+}
+
+fn statements<R: Read>(
+    lexer: &mut Lexer<R>,
+    unique_edges: bool,
+    declarations: &mut Declarations,
+    max_nodes: Option<usize>
+) -> io::Result<CallGraph> {
+    let mut builder = CallGraphBuilder::with_unique_edges(unique_edges).with_max_nodes(max_nodes);
+    loop {
+        let token = lexer.next()?.ok_or_else(|| lexer.error("unterminated graph"))?;
+        let name = match token {
+            Token::RBrace => return Ok(builder.build()),
+            Token::Semicolon | Token::Comma => continue,
+            Token::LBrace => return Err(lexer.error("subgraphs are not supported by the streaming reader")),
+            _ if is_keyword(&token, "subgraph") => {
+                return Err(lexer.error("subgraphs are not supported by the streaming reader"));
+            }
+            _ if ["graph", "node", "edge"].iter().any(|k| is_keyword(&token, k)) => {
+                attr_lists(lexer)?;
+                continue;
+            }
+            Token::Id { value, .. } => value,
+            token => return Err(lexer.error(&format!("unexpected {token:?}"))),
+        };
+        let name = node_id(lexer, name)?;
+        match lexer.peek()? {
+            Some(Token::Equals) => {
+                // graph attribute
+                lexer.next()?;
+                lexer.expect_id()?;
+            }
+            Some(Token::Edge) => {
+                let mut chain = vec![builder.node(&name)?];
+                while lexer.peek()? == Some(&Token::Edge) {
+                    lexer.next()?;
+                    let name = lexer.expect_id()?;
+                    chain.push(builder.node(&node_id(lexer, name)?)?);
+                }
+                let weight = edge_weight(attr_lists(lexer)?.into_iter().map(|(key, value, _)| (key, value)));
+                builder.add_edges(chain.windows(2).map(|pair| (pair[0], pair[1], weight)))?;
+            }
+            _ => {
+                let idx = builder.node(&name)?;
+                let attrs = attr_lists(lexer)?;
+                declarations.declare(idx, &mut builder.graph_mut()[idx], attrs).map_err(|e| lexer.error(&e))?;
+            }
+        }
+    }
+}
+
+/// Read dot graphs one statement at a time, without building a syntax tree,
+/// so memory use is bounded by the resulting graphs.
+/// Supports nodes, edge chains, attributes and comments; subgraphs are rejected.
+pub fn stream_dot(reader: impl Read) -> io::Result<Vec<CallGraph>> {
+    stream_dot_with(reader, false)
+}
+
+/// Same as [`stream_dot`], with `unique_edges` only the first edge between two nodes is added
+pub fn stream_dot_with(reader: impl Read, unique_edges: bool) -> io::Result<Vec<CallGraph>> {
+    stream_dot_checked(reader, unique_edges, DuplicateNodes::Merge, None).map(|(graphs, _)| graphs)
+}
+
+/// Same as [`stream_dot_with`], applying `duplicates` to nodes declared several times.
+/// Returns the number of repeated node statements along with the graphs.
+/// Reading stops with [`node_limit_error`] once all graphs get more than `max_nodes` together.
+pub fn stream_dot_checked(
+    reader: impl Read,
+    unique_edges: bool,
+    duplicates: DuplicateNodes,
+    max_nodes: Option<usize>
+) -> io::Result<(Vec<CallGraph>, usize)> {
+    let mut lexer = Lexer::new(reader);
+    let mut graphs = vec![];
+    let mut repeated = 0;
+    let mut nodes = 0;
+    while let Some(mut token) = lexer.next()? {
+        if is_keyword(&token, "strict") {
+            token = lexer.next()?.ok_or_else(|| lexer.error("expected graph"))?;
+        }
+        if !is_keyword(&token, "digraph") && !is_keyword(&token, "graph") {
+            return Err(lexer.error(&format!("expected graph, found {token:?}")));
+        }
+        if matches!(lexer.peek()?, Some(Token::Id { .. })) {
+            lexer.next()?;
+        }
+        lexer.expect(&Token::LBrace)?;
+        let mut declarations = Declarations::new(duplicates);
+        let graph = statements(&mut lexer, unique_edges, &mut declarations, max_nodes.map(|max| max - nodes))
+            .map_err(|e| match max_nodes {
+                Some(max) if e.kind() == io::ErrorKind::FileTooLarge => node_limit_error(max),
+                _ => e,
+            })?;
+        nodes += graph.node_count();
+        graphs.push(graph);
+        repeated += declarations.duplicates;
+    }
+    Ok((graphs, repeated))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::linker::edge::EdgeKind;
+    use super::*;
+
+    fn edges(graph: &CallGraph) -> Vec<(&str, &str, EdgeKind)> {
+        graph.raw_edges()
+            .iter()
+            .map(|e| (graph[e.source()].name.as_ref(), graph[e.target()].name.as_ref(), e.weight.kind))
+            .collect()
+    }
+
+    #[test]
+    fn test_stream_dot() {
+        let data = r#"
+            // leading comment
+            digraph "calls" {
+                graph [rankdir = LR]; node [shape = box]
+                rankdir = LR
+                a [label = "A", shape = box]; a [shape = oval]
+                "with \" quote" -> b:port -> c [kind = indirect]
+                b -> d /* inline */ [label = <<b>x</b>>, kind = "synthetic"]
+            }
+            digraph { x -> y }
+        "#;
+        let graphs = stream_dot(data.as_bytes()).unwrap();
+        assert_eq!(graphs.len(), 2);
+        let graph = &graphs[0];
+        assert_eq!(graph.node_count(), 5);
+        assert_eq!(graph[petgraph::graph::NodeIndex::new(0)].attrs["label"], "A");
+        assert_eq!(graph[petgraph::graph::NodeIndex::new(0)].attrs["shape"], "oval");
+        assert_eq!(edges(graph), [
+            (r#"with " quote"#, "b", EdgeKind::Indirect),
+            ("b", "c", EdgeKind::Indirect),
+            ("b", "d", EdgeKind::Synthetic),
+        ]);
+        assert_eq!(edges(&graphs[1]), [("x", "y", EdgeKind::Direct)]);
+
+        let unique = stream_dot_with("digraph { a -> b -> a; a -> b [kind = indirect] }".as_bytes(), true).unwrap();
+        assert_eq!(edges(&unique[0]), [("a", "b", EdgeKind::Direct), ("b", "a", EdgeKind::Direct)]);
+        assert_eq!(unique[0].edge_weights().map(|e| e.count).collect::<Vec<_>>(), [2, 1]);
+    }
+
+    #[test]
+    fn test_stream_html_labels() {
+        let data = r#"digraph { a [label = "<init>"]; b [label = <<b>x</b>>] }"#;
+        let graphs = stream_dot(data.as_bytes()).unwrap();
+        let mut out = vec![];
+        crate::linker::conversion::write_dot(&graphs[0], &mut out).unwrap();
+        let again = stream_dot(out.as_slice()).unwrap();
+        assert!(again[0].node_weights().eq(graphs[0].node_weights()));
+        assert_eq!(again[0][petgraph::graph::NodeIndex::new(0)].attrs["label"], "<init>");
+        assert!(again[0][petgraph::graph::NodeIndex::new(1)].html_attrs.contains("label"));
+    }
+
+    #[test]
+    fn test_stream_escapes() {
+        // Graphviz reads a quote after any backslash as part of the string
+        let graphs = stream_dot(r#"digraph { "slash \\" quote" -> "end\\
+" }"#.as_bytes()).unwrap();
+        assert_eq!(edges(&graphs[0]), [(r#"slash \" quote"#, "end\\", EdgeKind::Direct)]);
+        let mut out = vec![];
+        crate::linker::conversion::write_dot(&graphs[0], &mut out).unwrap();
+        let again = stream_dot(out.as_slice()).unwrap();
+        assert!(again[0].node_weights().eq(graphs[0].node_weights()));
+    }
+
+    #[test]
+    fn test_stream_dot_errors() {
+        let error = stream_dot("digraph {\n a -> subgraph { b } }".as_bytes()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().starts_with("line 2:"));
+        assert!(stream_dot("digraph { a -> b".as_bytes()).is_err());
+        assert!(stream_dot("digraph { a [label] }".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_stream_duplicate_nodes() {
+        let data = "digraph { a [label = A]; a; b }\ndigraph { b [shape = box]\n b [shape = oval] }";
+        let (graphs, repeated) = stream_dot_checked(data.as_bytes(), false, DuplicateNodes::Merge, None).unwrap();
+        assert_eq!(graphs.len(), 2);
+        assert_eq!(repeated, 2);
+        let err = stream_dot_checked(data.as_bytes(), false, DuplicateNodes::Error, None).unwrap_err();
+        assert_eq!(err.to_string(), r#"line 3: node "b" declared again with shape = "oval", was "box""#);
+    }
+
+    #[test]
+    fn test_stream_max_nodes() {
+        let data = "digraph { a -> b }\ndigraph { c -> d }\n";
+        let (graphs, _) = stream_dot_checked(data.as_bytes(), false, DuplicateNodes::Merge, Some(4)).unwrap();
+        assert_eq!(graphs.len(), 2);
+        let err = stream_dot_checked(data.as_bytes(), false, DuplicateNodes::Merge, Some(3)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::FileTooLarge);
+        assert_eq!(err.to_string(), "more than the limit of 3 nodes");
+        // Reading stops at the limit, a syntax error after it isn't reached
+        let data = "digraph { a -> b -> c ->";
+        let err = stream_dot_checked(data.as_bytes(), false, DuplicateNodes::Merge, Some(2)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::FileTooLarge);
+    }
+}
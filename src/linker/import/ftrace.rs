@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use std::io;
+use crate::linker::import::CallGraphBuilder;
+use crate::linker::node::CallGraph;
+
+/// Call stack of a single CPU.
+/// `None` marks an interrupt boundary: its handlers are not called by the interrupted function.
+type CpuStack<'a> = Vec<Option<&'a str>>;
+
+fn cpu_id(line: &str) -> Option<usize> {
+    line.split_whitespace()
+        .find_map(|token| token.strip_suffix(')')?.parse::<usize>().ok())
+}
+
+/// Reconstruct observed caller -> callee edges from an ftrace `function_graph` trace.
+/// Every caller -> callee pair gets one edge counting its observed calls,
+/// calls on different CPUs are tracked separately.
+/// Fails if edges don't fit in [`MAX_GRAPH_SIZE`](crate::linker::node::MAX_GRAPH_SIZE).
+pub fn ftrace_to_graph(data: &str) -> io::Result<CallGraph> {
+    ftrace_to_graph_with(data, None)
+}
+
+/// Same as [`ftrace_to_graph`], failing as soon as the graph gets more than `max_nodes`
+/// with [`check_node_limit`](crate::linker::node::check_node_limit)
+pub fn ftrace_to_graph_with(data: &str, max_nodes: Option<usize>) -> io::Result<CallGraph> {
+    let mut builder = CallGraphBuilder::with_unique_edges(true).with_max_nodes(max_nodes);
+    let mut stacks: HashMap<usize, CpuStack> = HashMap::new();
+
+    for line in data.lines() {
+        if line.trim_start().starts_with('#') {
+            continue;
+        }
+        let Some((prefix, call)) = line.rsplit_once('|') else {
+            continue;
+        };
+        let Some(cpu) = cpu_id(prefix) else {
+            continue;
+        };
+        let stack = stacks.entry(cpu).or_default();
+        let call = call.trim();
+
+        if prefix.contains("==========>") {
+            stack.push(None);
+            continue;
+        }
+        if prefix.contains("<==========") {
+            while let Some(Some(_)) = stack.pop() {}
+            continue;
+        }
+        if call.starts_with('}') {
+            stack.pop();
+            continue;
+        }
+        let (name, nested) = if let Some(name) = call.strip_suffix("() {") {
+            (name, true)
+        } else if let Some(name) = call.strip_suffix("();") {
+            (name, false)
+        } else {
+            // Trace markers and other comments
+            continue;
+        };
+        if let Some(Some(caller)) = stack.last() {
+            builder.add_calls(caller, name, 1)?;
+        } else {
+            builder.node(name)?;
+        }
+        if nested {
+            stack.push(Some(name));
+        }
+    }
+    Ok(builder.build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edges(graph: &CallGraph) -> Vec<(&str, &str)> {
+        let mut edges = graph.raw_edges()
+            .iter()
+            .map(|e| (graph[e.source()].name.as_ref(), graph[e.target()].name.as_ref()))
+            .collect::<Vec<_>>();
+        edges.sort_unstable();
+        edges
+    }
+
+    #[test]
+    fn test_function_graph() {
+        let data = "\
+# tracer: function_graph
+#
+# CPU  DURATION                  FUNCTION CALLS
+# |     |   |                     |   |   |   |
+ 0)               |  ksys_read() {
+ 1)               |  do_idle() {
+ 0)   0.123 us    |    fdget_pos();
+ 0)               |    vfs_read() {
+ 1)   0.500 us    |    tick_nohz_idle_enter();
+ 0) ==========> |
+ 0)               |  irq_enter() {
+ 0)   0.100 us    |    rcu_irq_enter();
+ 0)   0.300 us    |  }
+ 0) <========== |
+ 0)   0.1 us      |      rw_verify_area();
+ 0)   1.234 us    |    } /* vfs_read */
+ 0)   0.200 us    |    fdput_pos();
+ 0)   0.100 us    |    fdget_pos();
+ 0)   2.000 us    |  }
+ 1)   3.000 us    |  }
+";
+        let graph = ftrace_to_graph(data).unwrap();
+        assert_eq!(edges(&graph), vec![
+            ("do_idle", "tick_nohz_idle_enter"),
+            ("irq_enter", "rcu_irq_enter"),
+            ("ksys_read", "fdget_pos"),
+            ("ksys_read", "fdput_pos"),
+            ("ksys_read", "vfs_read"),
+            ("vfs_read", "rw_verify_area"),
+        ]);
+        let counts = graph.raw_edges().iter().map(|e| e.weight.count).collect::<Vec<_>>();
+        assert_eq!(counts, [2, 1, 1, 1, 1, 1]);
+    }
+}
@@ -0,0 +1,172 @@
+use std::io;
+use crate::linker::import::CallGraphBuilder;
+use crate::linker::node::CallGraph;
+
+/// Strip trailing `[index]` and `<cycle N>` markers from a gprof name
+fn strip_name(name: &str) -> &str {
+    let mut name = name.trim();
+    if name.ends_with(']') {
+        if let Some((prefix, _)) = name.rsplit_once('[') {
+            name = prefix.trim_end();
+        }
+    }
+    if name.ends_with('>') && !name.starts_with('<') {
+        if let Some((prefix, _)) = name.rsplit_once(" <cycle") {
+            name = prefix.trim_end();
+        }
+    }
+    name
+}
+
+fn is_call_count(token: &str) -> bool {
+    token.chars().all(|c| c.is_ascii_digit() || c == '/' || c == '+')
+}
+
+/// Parse "a/b", "a+b" or "a" into the number of calls along this edge
+fn parse_call_count(token: &str) -> Option<u64> {
+    let calls = token.split_once('/').map_or(token, |(calls, _)| calls);
+    calls.split('+')
+        .map(str::parse::<u64>)
+        .sum::<Result<u64, _>>()
+        .ok()
+}
+
+/// Split an entry line into its "called" field and the function name.
+/// Leading timing columns are skipped.
+fn split_entry(line: &str) -> (Option<&str>, &str) {
+    let mut rest = line.trim_start();
+    if rest.starts_with('[') {
+        rest = rest.split_once(']').map_or("", |(_, r)| r);
+    }
+    loop {
+        let trimmed = rest.trim_start();
+        let (token, tail) = trimmed
+            .split_once(char::is_whitespace)
+            .unwrap_or((trimmed, ""));
+        if token.contains('.') && token.parse::<f64>().is_ok() {
+            rest = tail;
+            continue;
+        }
+        if !token.is_empty() && is_call_count(token) {
+            return (Some(token), strip_name(tail));
+        }
+        return (None, strip_name(trimmed));
+    }
+}
+
+fn is_pseudo_function(name: &str) -> bool {
+    name.is_empty() || name.starts_with('<')
+}
+
+/// Build a call graph from the call graph section(s) of `gprof -q` output.
+/// Every caller -> callee pair gets one edge counting its calls.
+pub fn gprof_to_graph(data: &str) -> io::Result<CallGraph> {
+    gprof_to_graph_with(data, None)
+}
+
+/// Same as [`gprof_to_graph`], failing as soon as the graph gets more than `max_nodes`
+/// with [`check_node_limit`](crate::linker::node::check_node_limit)
+pub fn gprof_to_graph_with(data: &str, max_nodes: Option<usize>) -> io::Result<CallGraph> {
+    let mut builder = CallGraphBuilder::with_unique_edges(true).with_max_nodes(max_nodes);
+    let mut in_table = false;
+    let mut current: Option<&str> = None;
+
+    for line in data.lines() {
+        let trimmed = line.trim();
+        if !in_table {
+            in_table = trimmed.starts_with("index")
+                && trimmed.contains("called")
+                && trimmed.ends_with("name");
+            current = None;
+            continue;
+        }
+        if trimmed.is_empty() || line.starts_with('\x0c') {
+            in_table = false;
+        } else if trimmed.starts_with("---") {
+            current = None;
+        } else if trimmed.starts_with('[') {
+            let (_, name) = split_entry(line);
+            current = (!is_pseudo_function(name)).then_some(name);
+        } else if let Some(caller) = current {
+            // Lines after the primary line describe children
+            let (Some(count), callee) = split_entry(line) else {
+                continue;
+            };
+            if is_pseudo_function(callee) {
+                continue;
+            }
+            if let Some(count) = parse_call_count(count) {
+                builder.add_calls(caller, callee, count)?;
+            }
+        }
+    }
+    Ok(builder.build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "\
+\t\t     Call graph (explanation follows)
+
+
+granularity: each sample hit covers 2 byte(s) for 1.14% of 0.88 seconds
+
+index % time    self  children    called     name
+                                                 <spontaneous>
+[1]    100.0    0.00    0.88                 main [1]
+                0.00    0.88       1/1           foo [2]
+                0.00    0.00       2/3           bar [3]
+-----------------------------------------------
+                0.00    0.88       1/1           main [1]
+[2]    100.0    0.00    0.88       1         foo [2]
+                0.00    0.00       1/3           bar [3]
+-----------------------------------------------
+                                   4             bar [3]
+                0.00    0.00       2/3           main [1]
+                0.00    0.00       1/3           foo [2]
+[3]      0.0    0.00    0.00       3+4       bar [3]
+                                   4             bar [3]
+-----------------------------------------------
+
+\x0c
+ This table describes the call tree of the program, and was sorted by
+ the total amount of time spent in each function and its children.
+                0.00    0.88       7/7           ignored [9]
+";
+
+    fn calls(graph: &CallGraph, from: &str, to: &str) -> Vec<u64> {
+        graph.raw_edges()
+            .iter()
+            .filter(|e| &*graph[e.source()].name == from && &*graph[e.target()].name == to)
+            .map(|e| e.weight.count)
+            .collect()
+    }
+
+    #[test]
+    fn test_gprof_call_counts() {
+        let graph = gprof_to_graph(SAMPLE).unwrap();
+        assert_eq!(graph.node_count(), 3);
+        assert_eq!(calls(&graph, "main", "foo"), [1]);
+        assert_eq!(calls(&graph, "main", "bar"), [2]);
+        assert_eq!(calls(&graph, "foo", "bar"), [1]);
+        assert_eq!(calls(&graph, "bar", "bar"), [4]);
+        assert_eq!(graph.edge_count(), 4);
+    }
+
+    #[test]
+    fn test_gprof_large_counts() {
+        let data = SAMPLE.replace("1/1           foo [2]", "4000000000/4000000000 foo [2]");
+        let graph = gprof_to_graph(&data).unwrap();
+        assert_eq!(calls(&graph, "main", "foo"), [4_000_000_000]);
+        assert_eq!(graph.edge_count(), 4);
+    }
+
+    #[test]
+    fn test_gprof_cycle_names() {
+        assert_eq!(strip_name("foo <cycle 1> [4]"), "foo");
+        assert_eq!(split_entry("[5]  50.0  0.10  0.20  1+2  <cycle 1 as a whole> [5]").1,
+                   "<cycle 1 as a whole>");
+    }
+}
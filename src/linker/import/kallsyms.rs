@@ -0,0 +1,49 @@
+use std::collections::{BTreeSet, HashMap};
+
+/// Module name used for symbols built into the kernel image
+pub const BUILTIN_MODULE: &str = "vmlinux";
+
+/// Parse `/proc/kallsyms` or `System.map`: `address type name [module]`.
+/// Returns modules owning every symbol, static symbols may live in several ones.
+#[must_use]
+pub fn symbols_from_kallsyms(data: &str) -> HashMap<String, BTreeSet<String>> {
+    let mut symbols: HashMap<String, BTreeSet<String>> = HashMap::new();
+    for line in data.lines() {
+        let mut tokens = line.split_whitespace();
+        let (Some(_address), Some(_kind), Some(name)) = (tokens.next(), tokens.next(), tokens.next()) else {
+            continue;
+        };
+        let module = tokens
+            .next()
+            .and_then(|m| m.strip_prefix('[')?.strip_suffix(']'))
+            .unwrap_or(BUILTIN_MODULE);
+        symbols.entry(name.to_string())
+            .or_default()
+            .insert(module.to_string());
+    }
+    symbols
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kallsyms() {
+        let data = "\
+ffffffff81000000 T _stext
+ffffffff81001000 t init
+ffffffffc0a01000 t init\t[ext4]
+ffffffffc0a02000 T ext4_read\t[ext4]
+ffffffffc0b01000 t init\t[btrfs]
+";
+        let symbols = symbols_from_kallsyms(data);
+        assert_eq!(symbols.len(), 3);
+        assert_eq!(symbols["_stext"], BTreeSet::from([BUILTIN_MODULE.to_string()]));
+        assert_eq!(symbols["ext4_read"], BTreeSet::from(["ext4".to_string()]));
+        assert_eq!(
+            symbols["init"],
+            BTreeSet::from(["btrfs", "ext4", BUILTIN_MODULE].map(String::from))
+        );
+    }
+}
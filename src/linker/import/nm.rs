@@ -0,0 +1,89 @@
+use std::collections::HashSet;
+
+/// Drop symbol version suffix: `printf@GLIBC_2.2.5` -> `printf`
+fn strip_version(name: &str) -> &str {
+    name.split_once('@').map_or(name, |(name, _)| name)
+}
+
+/// Parse `objdump -t` line: `addr flags section\tsize name`
+fn parse_objdump_line(line: &str) -> Option<&str> {
+    let (left, right) = line.split_once('\t')?;
+    let tokens = left.split_whitespace().collect::<Vec<_>>();
+    let (section, flags) = tokens.get(1..)?.split_last()?;
+    // Skip undefined and source file name symbols
+    if *section == "*UND*" || flags.iter().any(|f| f.contains('f')) {
+        return None;
+    }
+    right.split_whitespace().last()
+}
+
+/// Parse `nm` line: `addr type name`, undefined symbols have no address
+fn parse_nm_line(line: &str) -> Option<&str> {
+    let tokens = line.split_whitespace().collect::<Vec<_>>();
+    let (kind, name) = match tokens[..] {
+        [_, kind, name] | [kind, name] => (kind, name),
+        _ => return None,
+    };
+    // U - undefined, v/w - undefined weak
+    match kind {
+        "U" | "v" | "w" => None,
+        _ if kind.len() == 1 => Some(name),
+        _ => None,
+    }
+}
+
+/// Collect symbols defined in `nm` or `objdump -t` output,
+/// undefined references are skipped.
+#[must_use]
+pub fn symbols_from_nm(data: &str) -> HashSet<String> {
+    data.lines()
+        .filter_map(|line| {
+            if line.contains('\t') {
+                parse_objdump_line(line)
+            } else {
+                parse_nm_line(line)
+            }
+        })
+        .map(|name| strip_version(name).to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nm() {
+        let data = "\
+0000000000001139 T main
+0000000000001120 t helper
+                 U printf@GLIBC_2.2.5
+                 w __gmon_start__
+0000000000004010 D data_var
+
+libfoo.o:
+0000000000000000 W weak_impl
+";
+        assert_eq!(
+            symbols_from_nm(data),
+            HashSet::from(["main", "helper", "data_var", "weak_impl"].map(String::from))
+        );
+    }
+
+    #[test]
+    fn test_objdump() {
+        let data = "\
+a.out:     file format elf64-x86-64
+
+SYMBOL TABLE:
+0000000000000000 l    df *ABS*\t0000000000000000 crt1.c
+0000000000001139 g     F .text\t000000000000001e main
+0000000000001120 l     F .text\t0000000000000010 .hidden helper
+0000000000000000       F *UND*\t0000000000000000 printf@GLIBC_2.2.5
+";
+        assert_eq!(
+            symbols_from_nm(data),
+            HashSet::from(["main", "helper"].map(String::from))
+        );
+    }
+}
@@ -0,0 +1,157 @@
+use std::io;
+use crate::linker::import::CallGraphBuilder;
+use crate::linker::node::CallGraph;
+
+const UNKNOWN_SYMBOL: &str = "[unknown]";
+
+/// Extract symbol from a `perf script` frame line: `addr symbol+0xoff (dso)`
+fn frame_symbol(line: &str) -> Option<&str> {
+    let (_, rest) = line.trim().split_once(char::is_whitespace)?;
+    let symbol = rest.rsplit_once(" (").map_or(rest, |(sym, _)| sym).trim();
+    let symbol = match symbol.rsplit_once("+0x") {
+        Some((sym, off)) if off.chars().all(|c| c.is_ascii_hexdigit()) => sym,
+        _ => symbol,
+    };
+    (!symbol.is_empty() && symbol != UNKNOWN_SYMBOL).then_some(symbol)
+}
+
+/// Detect a `perf script` sample header: `comm pid [cpu] time: period event:`.
+/// The comm is right-aligned, so headers may be indented like frames; they are told apart
+/// by the `seconds.micros:` timestamp
+fn is_sample_header(line: &str) -> bool {
+    line.split_whitespace().any(|token| {
+        token.strip_suffix(':')
+            .and_then(|time| time.split_once('.'))
+            .is_some_and(|(secs, micros)| {
+                !secs.is_empty() && !micros.is_empty()
+                    && secs.chars().chain(micros.chars()).all(|c| c.is_ascii_digit())
+            })
+    })
+}
+
+/// Parse a folded stack line: `root;caller;callee count`
+fn parse_folded(line: &str) -> Option<(Vec<&str>, u64)> {
+    let (stack, count) = line.trim().rsplit_once(char::is_whitespace)?;
+    let count = count.parse::<u64>().ok()?;
+    let stack = stack.trim().trim_start_matches("---");
+    Some((stack.split(';').collect(), count))
+}
+
+/// Add edges for a stack listed from the outermost caller to the leaf.
+/// Unknown frames split the chain instead of producing fake edges.
+fn add_stack(builder: &mut CallGraphBuilder, stack: &[Option<&str>], count: u64) -> io::Result<()> {
+    for pair in stack.windows(2) {
+        if let [Some(caller), Some(callee)] = pair {
+            builder.add_calls(caller, callee, count)?;
+        }
+    }
+    Ok(())
+}
+
+/// Build a weighted call graph from `perf script` output or folded stacks
+/// (`perf report -g folded`, `stackcollapse-perf.pl`).
+/// Every caller -> callee pair gets one edge counting the samples passing through it.
+pub fn perf_to_graph(data: &str) -> io::Result<CallGraph> {
+    perf_to_graph_with(data, None)
+}
+
+/// Same as [`perf_to_graph`], failing as soon as the graph gets more than `max_nodes`
+/// with [`check_node_limit`](crate::linker::node::check_node_limit)
+pub fn perf_to_graph_with(data: &str, max_nodes: Option<usize>) -> io::Result<CallGraph> {
+    let mut builder = CallGraphBuilder::with_unique_edges(true).with_max_nodes(max_nodes);
+    // perf script lists frames from the leaf to the outermost caller
+    let mut frames: Vec<Option<&str>> = vec![];
+
+    for line in data.lines() {
+        let header = is_sample_header(line);
+        if !header && line.starts_with(char::is_whitespace) && !line.trim().is_empty() {
+            frames.push(frame_symbol(line));
+            continue;
+        }
+        if !frames.is_empty() {
+            frames.reverse();
+            add_stack(&mut builder, &frames, 1)?;
+            frames.clear();
+        }
+        if header {
+            continue;
+        }
+        if let Some((stack, count)) = parse_folded(line) {
+            let stack = stack.into_iter()
+                .map(|s| (!s.is_empty() && s != UNKNOWN_SYMBOL).then_some(s))
+                .collect::<Vec<_>>();
+            add_stack(&mut builder, &stack, count)?;
+        }
+    }
+    if !frames.is_empty() {
+        frames.reverse();
+        add_stack(&mut builder, &frames, 1)?;
+    }
+    Ok(builder.build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calls(graph: &CallGraph, from: &str, to: &str) -> u64 {
+        let edges = graph.raw_edges()
+            .iter()
+            .filter(|e| &*graph[e.source()].name == from && &*graph[e.target()].name == to)
+            .collect::<Vec<_>>();
+        assert_eq!(edges.len(), 1, "one edge from {from} to {to}");
+        edges[0].weight.count
+    }
+
+    #[test]
+    fn test_perf_script() {
+        let data = "\
+prog  1234 12345.678901:     250000 cpu-clock:
+\t    55d4c0 bar+0x10 (/usr/bin/prog)
+\t    55d4d0 foo+0x20 (/usr/bin/prog)
+\t    55d4e0 main+0x30 (/usr/bin/prog)
+
+prog  1234 12345.679901:     250000 cpu-clock:
+\t    55d4c4 bar+0x14 (/usr/bin/prog)
+\t    55d4d0 [unknown] (/usr/bin/prog)
+\t    55d4e0 main+0x30 (/usr/bin/prog)
+";
+        let graph = perf_to_graph(data).unwrap();
+        assert_eq!(calls(&graph, "main", "foo"), 1);
+        assert_eq!(calls(&graph, "foo", "bar"), 1);
+        assert_eq!(graph.edge_count(), 2);
+    }
+
+    #[test]
+    fn test_indented_headers() {
+        let data = "\
+         swapper     0 [000] 12345.678901:     250000 cpu-clock:
+\t    55d4c0 idle+0x10 ([kernel.kallsyms])
+\t    55d4d0 cpu_startup+0x20 ([kernel.kallsyms])
+
+            prog  1234 12345.679901:     250000 cpu-clock:
+\t    55d4c4 bar+0x14 (/usr/bin/prog)
+\t    55d4e0 main+0x30 (/usr/bin/prog)
+            prog  1234 12345.680901:     250000 cpu-clock:
+\t    55d4c4 bar+0x14 (/usr/bin/prog)
+\t    55d4e0 main+0x30 (/usr/bin/prog)
+";
+        let graph = perf_to_graph(data).unwrap();
+        assert_eq!(calls(&graph, "cpu_startup", "idle"), 1);
+        assert_eq!(calls(&graph, "main", "bar"), 2);
+        assert_eq!(graph.node_count(), 4);
+        assert_eq!(graph.edge_count(), 2);
+    }
+
+    #[test]
+    fn test_folded_stacks() {
+        let graph = perf_to_graph("main;foo;bar 3\nmain;bar 2\n").unwrap();
+        assert_eq!(calls(&graph, "main", "foo"), 3);
+        assert_eq!(calls(&graph, "foo", "bar"), 3);
+        assert_eq!(calls(&graph, "main", "bar"), 2);
+        assert_eq!(graph.node_count(), 3);
+
+        let graph = perf_to_graph("main;foo 5000000000\nmain;foo 1\n").unwrap();
+        assert_eq!(calls(&graph, "main", "foo"), 5_000_000_001);
+    }
+}
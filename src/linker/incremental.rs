@@ -0,0 +1,206 @@
+use std::fs;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use log::{info, warn};
+use crate::linker::conversion::DuplicateNodes;
+use crate::linker::graph_link::link_graphs_with;
+use crate::linker::import::cache::ParseCache;
+use crate::linker::import::{read_inputs_skipping_bad, read_inputs_with, InputFormat, MultiGraph};
+use crate::linker::node::CallGraph;
+use crate::linker::pipeline::Pipeline;
+use crate::linker::profile;
+use crate::linker::report::PassReport;
+
+/// Inputs with a path hash divisible by this end a chunk. Chunks have about this many inputs,
+/// and adding or removing an input only changes the chunk it belongs to.
+const CHUNK_BOUNDARY: u64 = 16;
+
+/// Outcome of [`run_incremental`]
+#[derive(Debug, Default)]
+pub struct IncrementalRun {
+    pub graph: CallGraph,
+    /// Reports of passes before the link step only cover re-linked chunks
+    pub reports: Vec<(String, PassReport)>,
+    pub graphs_read: usize,
+    pub chunks: usize,
+    /// Chunks loaded from the cache
+    pub reused_chunks: usize,
+    /// Inputs that failed to read with `skip_bad_inputs`, their chunks are not cached
+    pub skipped_inputs: Vec<(PathBuf, io::Error)>,
+}
+
+fn hash_file(path: &Path, hasher: &mut impl Hasher) -> io::Result<()> {
+    hasher.write(&fs::read(path)?);
+    Ok(())
+}
+
+/// Hash of the config text and of the contents of every existing file named in it,
+/// e.g. node lists, so editing them invalidates cached chunks
+pub fn config_fingerprint(config: &Path) -> io::Result<u64> {
+    let mut hasher = DefaultHasher::new();
+    let text = fs::read_to_string(config)?;
+    text.hash(&mut hasher);
+    for arg in text.lines().flat_map(|line| line.split_whitespace().skip(1)) {
+        if Path::new(arg).is_file() {
+            hash_file(Path::new(arg), &mut hasher)?;
+        }
+    }
+    Ok(hasher.finish())
+}
+
+fn ends_chunk(path: &Path) -> bool {
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+    hasher.finish().is_multiple_of(CHUNK_BOUNDARY)
+}
+
+fn chunk_key(
+    chunk: &[PathBuf],
+    format: Option<InputFormat>,
+    multi_graph: MultiGraph,
+    unique_edges: bool,
+    duplicates: DuplicateNodes,
+    fingerprint: u64
+) -> io::Result<u64> {
+    let mut hasher = DefaultHasher::new();
+    "linked chunk".hash(&mut hasher);
+    fingerprint.hash(&mut hasher);
+    for path in chunk {
+        path.hash(&mut hasher);
+        let format = format.unwrap_or_else(|| InputFormat::detect(path));
+        ParseCache::key(path, format, multi_graph, unique_edges, duplicates)?.hash(&mut hasher);
+    }
+    Ok(hasher.finish())
+}
+
+/// Run a linked `pipeline` on `files`, reusing work of earlier runs.
+/// Inputs are split into chunks, every chunk is linked after the passes before the link step
+/// and kept in `cache`, keyed by its inputs and `fingerprint` of the config.
+/// Only chunks with changed inputs are read again, the result is the same as of a full run.
+/// With `skip_bad_inputs` inputs failing to read are left out like in
+/// [`read_inputs_skipping_bad`] instead of failing the run.
+/// Duplicate edges are skipped while reading and linking if the pipeline has
+/// [`unique_edges`](Pipeline::unique_edges) set, nodes declared several times are handled by `duplicates`.
+#[allow(clippy::too_many_arguments)]
+pub fn run_incremental(
+    pipeline: &Pipeline,
+    cache: &ParseCache,
+    files: &[PathBuf],
+    format: Option<InputFormat>,
+    multi_graph: MultiGraph,
+    duplicates: DuplicateNodes,
+    fingerprint: u64,
+    skip_bad_inputs: bool
+) -> io::Result<IncrementalRun> {
+    let mut chunks = vec![];
+    let mut start = 0;
+    for (i, path) in files.iter().enumerate() {
+        if ends_chunk(path) || i + 1 == files.len() {
+            chunks.push(&files[start..=i]);
+            start = i + 1;
+        }
+    }
+    let mut run = IncrementalRun {
+        reports: pipeline.before_link()
+            .iter()
+            .map(|pass| (pass.name(), PassReport::default()))
+            .collect(),
+        chunks: chunks.len(),
+        ..IncrementalRun::default()
+    };
+
+    let unique_edges = pipeline.has_unique_edges();
+    let mut merged = vec![];
+    for chunk in chunks {
+        let key = chunk_key(chunk, format, multi_graph, unique_edges, duplicates, fingerprint)?;
+        if let Some(mut graphs) = cache.load(key).filter(|graphs| graphs.len() == 1) {
+            merged.push(graphs.pop().unwrap_or_default());
+            run.reused_chunks += 1;
+            continue;
+        }
+        let phase = profile::phase("parse", "");
+        let (objects, skipped) = if skip_bad_inputs {
+            read_inputs_skipping_bad(chunk, format, multi_graph, unique_edges, duplicates, None, Some(cache))
+        } else {
+            (read_inputs_with(chunk, format, multi_graph, unique_edges, duplicates, None, Some(cache))?, vec![])
+        };
+        let mut graphs = objects.into_iter().map(|(_, graph)| graph).collect::<Vec<_>>();
+        drop(phase);
+        run.graphs_read += graphs.len();
+        let reports = pipeline.run_before_link(&mut graphs)?;
+        for ((_, total), (_, report)) in run.reports.iter_mut().zip(&reports) {
+            total.merge(report);
+        }
+        let graph = link_graphs_with(graphs, unique_edges)?;
+        if skipped.is_empty() {
+            if let Err(err) = cache.store(key, std::slice::from_ref(&graph)) {
+                warn!("Failed to cache linked chunk: {err}");
+            }
+        }
+        run.skipped_inputs.extend(skipped);
+        merged.push(graph);
+    }
+    info!("Reused {} of {} input chunks", run.reused_chunks, run.chunks);
+
+    pipeline.run_link(&mut merged)?;
+    run.reports.extend(pipeline.run_after_link(&mut merged)?);
+    run.graph = merged.pop().unwrap_or_default();
+    Ok(run)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::linker::pass::RemoveNodePass;
+    use super::*;
+
+    #[test]
+    fn test_run_incremental() {
+        let root = crate::linker::test_path("incremental");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let cache = ParseCache::new(root.join("cache")).unwrap();
+        // Enough inputs for several chunks whatever the temp dir is
+        let files = (0..200)
+            .map(|i| {
+                let path = root.join(format!("{i}.dot"));
+                fs::write(&path, format!("digraph {{ f{i} -> common; common -> x{i} }}")).unwrap();
+                path
+            })
+            .collect::<Vec<_>>();
+        let pipeline = Pipeline::new()
+            .add(Box::new(RemoveNodePass::new_from_str("^x1$").unwrap()))
+            .link();
+        let run = |fingerprint| {
+            let format = Some(InputFormat::DotStream);
+            let duplicates = DuplicateNodes::Merge;
+            run_incremental(&pipeline, &cache, &files, format, MultiGraph::Merge, duplicates, fingerprint, false).unwrap()
+        };
+        let edges = |graph: &CallGraph| graph.raw_edges()
+            .iter()
+            .map(|e| (graph[e.source()].name.to_string(), graph[e.target()].name.to_string()))
+            .collect::<Vec<_>>();
+
+        let first = run(0);
+        assert!(first.chunks > 1);
+        assert_eq!((first.reused_chunks, first.graphs_read), (0, 200));
+        assert_eq!(first.reports[0].1.nodes_removed, 1);
+        assert_eq!(first.graph.node_count(), 200 + 1 + 199);
+
+        fs::write(&files[7], "digraph { f7 -> common; common -> y7 }").unwrap();
+        let second = run(0);
+        assert_eq!(second.reused_chunks, second.chunks - 1);
+        assert!(second.graphs_read < 200);
+        let mut full = files.iter()
+            .map(|path| crate::linker::import::read_graphs(path, InputFormat::DotStream, MultiGraph::Merge).unwrap())
+            .map(|mut graphs| graphs.pop().unwrap())
+            .collect();
+        pipeline.run(&mut full).unwrap();
+        assert_eq!(edges(&second.graph), edges(&full[0]));
+        let common = second.graph.node_weights().find(|node| &*node.name == "common").unwrap();
+        assert_eq!(common.sources.len(), 200);
+
+        assert_eq!(run(1).reused_chunks, 0);
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,100 @@
+use std::collections::HashSet;
+use std::hash::{BuildHasher, RandomState};
+use std::sync::{Arc, LazyLock, PoisonError, RwLock};
+
+/// Number of independently locked parts of the interner, threads creating nodes
+/// of different graphs rarely wait for each other
+const SHARDS: usize = 64;
+
+/// Every name interned by this process and still in use, see [`release_unused`]
+static NAMES: LazyLock<Interner> = LazyLock::new(Interner::default);
+
+struct Interner {
+    hasher: RandomState,
+    shards: [RwLock<HashSet<Arc<str>>>; SHARDS],
+}
+
+impl Default for Interner {
+    fn default() -> Self {
+        Self { hasher: RandomState::new(), shards: std::array::from_fn(|_| RwLock::default()) }
+    }
+}
+
+impl Interner {
+    fn shard(&self, name: &str) -> &RwLock<HashSet<Arc<str>>> {
+        #[allow(clippy::cast_possible_truncation)]
+        let hash = self.hasher.hash_one(name) as usize;
+        &self.shards[hash % SHARDS]
+    }
+}
+
+/// Shared copy of `name`, so equal names of all graphs and passes are stored once.
+/// Known names only take a read lock.
+#[must_use]
+pub fn intern(name: &str) -> Arc<str> {
+    let shard = NAMES.shard(name);
+    if let Some(interned) = shard.read().unwrap_or_else(PoisonError::into_inner).get(name) {
+        return interned.clone();
+    }
+    let mut names = shard.write().unwrap_or_else(PoisonError::into_inner);
+    if let Some(interned) = names.get(name) {
+        return interned.clone();
+    }
+    let interned: Arc<str> = Arc::from(name);
+    names.insert(interned.clone());
+    interned
+}
+
+/// Forget names not used outside the interner anymore, e.g. after the graphs of a run are dropped.
+/// Returns the number of released names
+pub fn release_unused() -> usize {
+    let mut released = 0;
+    for shard in &NAMES.shards {
+        let mut names = shard.write().unwrap_or_else(PoisonError::into_inner);
+        let before = names.len();
+        names.retain(|name| Arc::strong_count(name) > 1);
+        released += before - names.len();
+    }
+    released
+}
+
+/// Calls [`release_unused`] when dropped, so names of a run are released once its graphs are gone
+/// however it ends. Values declared after it are dropped first
+pub struct Scope(());
+
+impl Scope {
+    #[must_use]
+    pub fn new() -> Self {
+        Self(())
+    }
+}
+
+impl Default for Scope {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        release_unused();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern() {
+        let first = intern("do_sys_open");
+        let second = intern(&String::from("do_sys_open"));
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(!Arc::ptr_eq(&first, &intern("do_sys_openat2")));
+
+        let unused = Arc::downgrade(&intern("test_intern_unused"));
+        release_unused();
+        assert!(unused.upgrade().is_none());
+        assert!(Arc::ptr_eq(&first, &intern("do_sys_open")));
+    }
+}
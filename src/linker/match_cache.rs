@@ -0,0 +1,50 @@
+use std::collections::HashMap;
+use std::sync::{Arc, PoisonError, RwLock};
+
+/// Regex matches of node names remembered for one run, so passes matching the same regex list,
+/// e.g. one noise list used by several passes, test every name only once.
+/// Passes get it with their `with_match_cache` builders, results are dropped with the last pass.
+#[derive(Default)]
+pub struct MatchCache {
+    /// Match results by regex list id and node name
+    results: RwLock<HashMap<u64, HashMap<Arc<str>, bool>>>,
+}
+
+impl MatchCache {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remembered results for `names` in the regex list `list`, looked up under a single lock
+    pub(crate) fn get_all<'a>(&self, list: u64, names: impl Iterator<Item = &'a str>) -> Vec<Option<bool>> {
+        let results = self.results.read().unwrap_or_else(PoisonError::into_inner);
+        let Some(results) = results.get(&list) else {
+            return names.map(|_| None).collect();
+        };
+        names.map(|name| results.get(name).copied()).collect()
+    }
+
+    /// Remember results of the regex list `list`, all stored under a single lock
+    pub(crate) fn insert_all(&self, list: u64, matches: Vec<(Arc<str>, bool)>) {
+        if matches.is_empty() {
+            return;
+        }
+        self.results.write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(list)
+            .or_default()
+            .extend(matches);
+    }
+
+    /// Number of remembered results of all regex lists
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.results.read().unwrap_or_else(PoisonError::into_inner).values().map(HashMap::len).sum()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
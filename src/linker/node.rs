@@ -0,0 +1,257 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use petgraph::Graph;
+use petgraph::graph::{EdgeIndex, NodeIndex};
+use crate::linker::edge::Edge;
+use crate::linker::intern::intern;
+
+/// Graph used by the whole pipeline: inputs, passes, linking and output.
+///
+/// Node order is reproducible and never depends on hashing: importers add nodes
+/// in the order they are first mentioned, linking keeps the order of the first input
+/// mentioning a node, and passes of this crate removing nodes or edges keep the
+/// relative order of the rest. Edges keep the order they were added in the same way.
+pub type CallGraph = Graph<Node, Edge>;
+
+/// Most nodes and most edges a graph can have. All graphs use petgraph's default `u32` indices,
+/// half the size of `usize` ones, and the largest index is reserved for a missing node
+pub const MAX_GRAPH_SIZE: usize = u32::MAX as usize;
+
+/// Fail if a graph with `nodes` nodes and `edges` edges doesn't fit in `u32` indices
+pub fn check_graph_size(nodes: usize, edges: usize) -> io::Result<()> {
+    for (count, what) in [(nodes, "nodes"), (edges, "edges")] {
+        if count > MAX_GRAPH_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("graph has more than {MAX_GRAPH_SIZE} {what}, the limit of 32-bit indices")
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Error of an input with more than `max` nodes, of kind [`io::ErrorKind::FileTooLarge`]
+#[must_use]
+pub fn node_limit_error(max: usize) -> io::Error {
+    io::Error::new(io::ErrorKind::FileTooLarge, format!("more than the limit of {max} nodes"))
+}
+
+/// Fail with [`node_limit_error`] if an input with `nodes` nodes is over the limit of `max`
+pub fn check_node_limit(nodes: usize, max: Option<usize>) -> io::Result<()> {
+    match max {
+        Some(max) if nodes > max => Err(node_limit_error(max)),
+        _ => Ok(()),
+    }
+}
+
+/// Check invariants every graph of the pipeline keeps: edges connect existing nodes
+/// and no two nodes share a name, linking and importers rely on names being unique
+pub fn check_invariants(graph: &CallGraph) -> Result<(), String> {
+    let nodes = graph.node_count();
+    if let Some(edge) = graph.raw_edges().iter().find(|e| e.source().index() >= nodes || e.target().index() >= nodes) {
+        return Err(format!(
+            "edge {} -> {} points outside of {nodes} nodes",
+            edge.source().index(),
+            edge.target().index()
+        ));
+    }
+    let mut names = HashSet::with_capacity(nodes);
+    if let Some(node) = graph.node_weights().find(|node| !names.insert(&node.name)) {
+        return Err(format!("several nodes are named \"{}\"", node.name));
+    }
+    Ok(())
+}
+
+/// Same as [`Graph::add_node`], but fails with [`check_graph_size`] instead of panicking
+/// when the graph is full
+pub fn try_add_node<N, E>(graph: &mut Graph<N, E>, weight: N) -> io::Result<NodeIndex> {
+    check_graph_size(graph.node_count() + 1, 0)?;
+    Ok(graph.add_node(weight))
+}
+
+/// Same as [`Graph::add_edge`], but fails with [`check_graph_size`] instead of panicking
+/// when the graph is full
+pub fn try_add_edge<N, E>(
+    graph: &mut Graph<N, E>,
+    from: NodeIndex,
+    to: NodeIndex,
+    weight: E
+) -> io::Result<EdgeIndex> {
+    check_graph_size(0, graph.edge_count() + 1)?;
+    Ok(graph.add_edge(from, to, weight))
+}
+
+/// Keep nodes with `keep_node[index]` and edges with `keep_edge(index)` between kept nodes,
+/// keeping the order of both. Weights are moved into the rebuilt graph, not cloned
+pub fn retain_in_order<N, E>(graph: &mut Graph<N, E>, keep_node: &[bool], keep_edge: impl Fn(EdgeIndex) -> bool) {
+    let (nodes, edges) = std::mem::take(graph).into_nodes_edges();
+    let kept = keep_node.iter().filter(|&&keep| keep).count();
+    let mut retained = Graph::with_capacity(kept, edges.len());
+    let mut new_index = vec![None; nodes.len()];
+    for ((node, &keep), new) in nodes.into_iter().zip(keep_node).zip(&mut new_index) {
+        if keep {
+            *new = Some(retained.add_node(node.weight));
+        }
+    }
+    for (i, edge) in edges.into_iter().enumerate() {
+        let endpoints = (new_index[edge.source().index()], new_index[edge.target().index()]);
+        if let (Some(source), Some(target)) = endpoints {
+            if keep_edge(EdgeIndex::new(i)) {
+                retained.add_edge(source, target, edge.weight);
+            }
+        }
+    }
+    *graph = retained;
+}
+
+/// Function in the call graph.
+/// Attributes are taken from the input DOT and written back on output.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Node {
+    /// Interned with [`intern`], nodes of all graphs with the same name share it
+    pub name: Arc<str>,
+    pub attrs: BTreeMap<String, String>,
+    /// Keys of `attrs` read from HTML-like ids, written back without quotes
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub html_attrs: BTreeSet<String>,
+    /// Input files this node was read from, in input order
+    pub sources: Vec<Arc<Path>>,
+}
+
+impl Node {
+    #[must_use]
+    pub fn new(name: &str) -> Self {
+        Self { name: intern(name), attrs: BTreeMap::new(), html_attrs: BTreeSet::new(), sources: vec![] }
+    }
+}
+
+/// Node data passes can work with
+pub trait NodeLabel: Clone {
+    fn name(&self) -> &str;
+
+    /// Name shared with other nodes, types without an interned name allocate it
+    fn shared_name(&self) -> Arc<str> {
+        Arc::from(self.name())
+    }
+
+    /// Store a node attribute, types without attributes drop it
+    fn set_attr(&mut self, _key: &str, _value: String) {}
+
+    /// Node attribute, types without attributes have none
+    fn attr(&self, _key: &str) -> Option<&str> {
+        None
+    }
+}
+
+impl NodeLabel for Node {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn shared_name(&self) -> Arc<str> {
+        self.name.clone()
+    }
+
+    fn set_attr(&mut self, key: &str, value: String) {
+        self.html_attrs.remove(key);
+        self.attrs.insert(key.to_string(), value);
+    }
+
+    fn attr(&self, key: &str) -> Option<&str> {
+        self.attrs.get(key).map(String::as_str)
+    }
+}
+
+impl NodeLabel for String {
+    fn name(&self) -> &str {
+        self
+    }
+}
+
+impl NodeLabel for Arc<str> {
+    fn name(&self) -> &str {
+        self
+    }
+}
+
+impl From<&str> for Node {
+    fn from(name: &str) -> Self {
+        Self::new(name)
+    }
+}
+
+impl From<String> for Node {
+    fn from(name: String) -> Self {
+        Self::new(&name)
+    }
+}
+
+impl AsRef<str> for Node {
+    fn as_ref(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Display for Node {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_graph_size() {
+        assert!(check_graph_size(MAX_GRAPH_SIZE, MAX_GRAPH_SIZE).is_ok());
+        let err = check_graph_size(10, MAX_GRAPH_SIZE + 1).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "graph has more than 4294967295 edges, the limit of 32-bit indices");
+        assert!(check_graph_size(MAX_GRAPH_SIZE + 1, 0).unwrap_err().to_string().contains(" nodes"));
+
+        let mut graph = CallGraph::new();
+        let a = try_add_node(&mut graph, "a".into()).unwrap();
+        try_add_edge(&mut graph, a, a, Edge::default()).unwrap();
+        assert_eq!((graph.node_count(), graph.edge_count()), (1, 1));
+    }
+
+    #[test]
+    fn test_retain_in_order() {
+        // Weights without Clone are moved
+        struct Weight(&'static str);
+        let mut graph = Graph::<Weight, Weight>::new();
+        let [a, b, c, d] = ["a", "b", "c", "d"].map(|name| graph.add_node(Weight(name)));
+        graph.add_edge(c, d, Weight("c-d"));
+        let removed = graph.add_edge(a, c, Weight("a-c"));
+        graph.add_edge(a, b, Weight("a-b"));
+        graph.add_edge(d, a, Weight("d-a"));
+        retain_in_order(&mut graph, &[true, false, true, true], |idx| idx != removed);
+        assert_eq!(graph.node_weights().map(|w| w.0).collect::<Vec<_>>(), ["a", "c", "d"]);
+        assert_eq!(graph.edge_weights().map(|w| w.0).collect::<Vec<_>>(), ["c-d", "d-a"]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        use crate::linker::edge::EdgeKind;
+
+        let mut graph = CallGraph::new();
+        let a = graph.add_node("a".into());
+        let b = graph.add_node("b".into());
+        graph[b].attrs.insert("label".to_string(), "B".to_string());
+        graph[b].sources.push(Arc::from(Path::new("b.dot")));
+        let edge = Edge::new(EdgeKind::Indirect, 3);
+        graph.add_edge(a, b, edge);
+
+        let json = serde_json::to_string(&graph).unwrap();
+        assert!(json.contains("\"indirect\""));
+        let parsed: CallGraph = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.node_weights().collect::<Vec<_>>(), graph.node_weights().collect::<Vec<_>>());
+        assert_eq!(parsed.edge_weights().collect::<Vec<_>>(), [&edge]);
+    }
+}
@@ -1,49 +1,254 @@
-use std::collections::HashSet;
-use std::hash::Hash;
-use log::{debug, info, error};
-use petgraph::adj::DefaultIx;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::str::FromStr;
+use std::sync::Arc;
+use log::{debug, info};
 use petgraph::Graph;
-use petgraph::graph::NodeIndex;
-use petgraph::prelude::{Dfs, EdgeRef};
+use petgraph::graph::{EdgeIndex, NodeIndex};
+use petgraph::prelude::EdgeRef;
 use fancy_regex::Regex;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use regex::RegexSet;
+use crate::linker::analysis::components;
+use crate::linker::import::coverage::covered_functions;
+use crate::linker::import::kallsyms::symbols_from_kallsyms;
+use crate::linker::import::nm::symbols_from_nm;
+use crate::linker::cancel::CancellationToken;
+use crate::linker::csr::CsrGraph;
+use crate::linker::edge::{Edge, EdgeKind, EdgeLabel};
+use crate::linker::error::{PassError, RuleError, RuleErrorKind};
+use crate::linker::match_cache::MatchCache;
+use crate::linker::node::{retain_in_order, CallGraph, Node, NodeLabel};
+use crate::linker::report::{graph_size, PassReport};
 
-pub trait Pass {
-    fn run_pass(&self, graph: &mut Graph<String, ()>);
+/// Graph transformation. Passes that only need node names and edge kinds
+/// work on any [`NodeLabel`] and [`EdgeLabel`].
+/// Passes are shared between threads running them on different graphs.
+pub trait Pass<N = Node, E = Edge>: Send + Sync {
+    fn run_pass(&self, graph: &mut Graph<N, E>) -> Result<PassReport, PassError>;
+
+    /// Same as [`Pass::run_pass`], failing with [`PassError::Cancelled`] once `cancel` is triggered.
+    /// Passes with long loops check `cancel` inside them, the graph is left unchanged then.
+    fn run_pass_cancellable(
+        &self,
+        graph: &mut Graph<N, E>,
+        cancel: &CancellationToken
+    ) -> Result<PassReport, PassError> {
+        cancel.check()?;
+        self.run_pass(graph)
+    }
 
     fn name(&self) -> String;
 }
 
+/// Match `text` against `re`, reporting runtime failures instead of panicking
+fn is_match(re: &Regex, text: &str) -> Result<bool, PassError> {
+    re.is_match(text).map_err(|e| PassError::Regex {
+        pattern: re.as_str().to_string(),
+        subject: text.to_string(),
+        source: Box::new(e),
+    })
+}
+
+/// Pattern matching whole strings only, so `sys_read` doesn't match `sys_readahead`
+fn full_match_pattern(pattern: &str) -> String {
+    format!("^(?:{pattern})$")
+}
+
+/// Check whether `text` matches any of `rules`
+fn any_match(rules: &[Regex], text: &str) -> Result<bool, PassError> {
+    for re in rules {
+        if is_match(re, text)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Regexes matched together. Patterns supported by the `regex` crate share one [`RegexSet`],
+/// which scans a name once for all of them, patterns using fancy-regex features are tried one by one.
+struct RegexList {
+    /// Hash of all patterns, identifies the list in [`MatchCache`]
+    id: u64,
+    regexes: Vec<Regex>,
+    set: RegexSet,
+    /// Index in `regexes` of every pattern of `set`
+    in_set: Vec<usize>,
+    /// Indices of patterns missing from `set`
+    fallback: Vec<usize>,
+    /// Results shared with equal lists of other passes
+    cache: Option<Arc<MatchCache>>,
+}
+
+impl Default for RegexList {
+    fn default() -> Self {
+        Self::new(vec![])
+    }
+}
+
+impl RegexList {
+    fn new(regexes: Vec<Regex>) -> Self {
+        let mut hasher = DefaultHasher::new();
+        regexes.iter().for_each(|re| re.as_str().hash(&mut hasher));
+        let id = hasher.finish();
+        let (in_set, fallback): (Vec<_>, Vec<_>) = (0..regexes.len())
+            .partition(|&i| regex::Regex::new(regexes[i].as_str()).is_ok());
+        match RegexSet::new(in_set.iter().map(|&i| regexes[i].as_str())) {
+            Ok(set) => Self { id, regexes, set, in_set, fallback, cache: None },
+            Err(e) => {
+                debug!("Matching regexes one by one: {e}");
+                let fallback = (0..regexes.len()).collect();
+                Self { id, regexes, set: RegexSet::empty(), in_set: vec![], fallback, cache: None }
+            }
+        }
+    }
+
+    /// Check whether `text` matches any regex
+    fn is_match(&self, text: &str) -> Result<bool, PassError> {
+        if self.set.is_match(text) {
+            return Ok(true);
+        }
+        for &i in &self.fallback {
+            if is_match(&self.regexes[i], text)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Whether the name of every node matches any regex. With a [`MatchCache`] names are looked up first,
+    /// all new results are stored at once
+    fn match_nodes<N: NodeLabel, E>(
+        &self,
+        graph: &Graph<N, E>,
+        cancel: &CancellationToken
+    ) -> Result<Vec<bool>, PassError> {
+        let Some(cache) = &self.cache else {
+            return graph.node_weights()
+                .map(|node| {
+                    cancel.check()?;
+                    self.is_match(node.name())
+                })
+                .collect();
+        };
+        let known = cache.get_all(self.id, graph.node_weights().map(NodeLabel::name));
+        let mut matched = Vec::with_capacity(known.len());
+        let mut new = vec![];
+        for (node, known) in graph.node_weights().zip(known) {
+            cancel.check()?;
+            let is_match = match known {
+                Some(is_match) => is_match,
+                None => {
+                    let is_match = self.is_match(node.name())?;
+                    new.push((node.shared_name(), is_match));
+                    is_match
+                }
+            };
+            matched.push(is_match);
+        }
+        cache.insert_all(self.id, new);
+        Ok(matched)
+    }
+
+    /// Indices of all matching regexes in ascending order
+    fn matches(&self, text: &str) -> Result<Vec<usize>, PassError> {
+        let mut matched = self.set
+            .matches(text)
+            .iter()
+            .map(|i| self.in_set[i])
+            .collect::<Vec<_>>();
+        for &i in &self.fallback {
+            if is_match(&self.regexes[i], text)? {
+                matched.push(i);
+            }
+        }
+        matched.sort_unstable();
+        Ok(matched)
+    }
+}
+
 /// Make all listed functions terminal, after this pass there will be no such nodes.
 pub struct RemoveNodePass {
-    terminate_funcs: Vec<Regex>
+    terminate_funcs: RegexList
 }
 
 impl RemoveNodePass {
-    pub fn new(iter: &mut dyn Iterator<Item = &str>) -> Self {
-        Self {
-            terminate_funcs: iter.map(|s| Regex::new(s).unwrap()).collect()
+    /// Pass for patterns of `lines`, patterns on one line are separated by whitespace.
+    /// Fails with every invalid pattern, see [`Self::new_lenient`] to skip them instead.
+    pub fn new(lines: &mut dyn Iterator<Item = &str>) -> Result<Self, Vec<RuleError>> {
+        Self::new_with(lines, false)
+    }
+
+    /// Same as [`Self::new`], with `full_match` a name must match a pattern as a whole
+    pub fn new_with(lines: &mut dyn Iterator<Item = &str>, full_match: bool) -> Result<Self, Vec<RuleError>> {
+        let (pass, errors) = Self::new_lenient(lines, full_match);
+        if errors.is_empty() { Ok(pass) } else { Err(errors) }
+    }
+
+    /// Pass with valid patterns of `lines` only, along with errors of the invalid ones
+    pub fn new_lenient(lines: &mut dyn Iterator<Item = &str>, full_match: bool) -> (Self, Vec<RuleError>) {
+        let mut regexes = vec![];
+        let mut errors = vec![];
+        for (i, line) in lines.enumerate() {
+            for pattern in line.split_whitespace() {
+                let regex = if full_match {
+                    Regex::new(&full_match_pattern(pattern))
+                } else {
+                    Regex::new(pattern)
+                };
+                match regex {
+                    Ok(regex) => regexes.push(regex),
+                    Err(e) => errors.push(RuleError {
+                        line_number: i + 1,
+                        line: line.to_string(),
+                        kind: RuleErrorKind::Regex(Box::new(e)),
+                    }),
+                }
+            }
         }
+        (Self { terminate_funcs: RegexList::new(regexes) }, errors)
     }
 
+    pub fn new_from_str(s: &str) -> Result<Self, Vec<RuleError>> {
+        Self::new(&mut s.lines())
+    }
+
+    pub fn new_from_str_with(s: &str, full_match: bool) -> Result<Self, Vec<RuleError>> {
+        Self::new_with(&mut s.lines(), full_match)
+    }
+
+    /// Share match results with other passes of a run
     #[must_use]
-    pub fn new_from_str(s: &str) -> Self {
-        Self::new(&mut s.split_whitespace())
+    pub fn with_match_cache(mut self, cache: Arc<MatchCache>) -> Self {
+        self.terminate_funcs.cache = Some(cache);
+        self
     }
 }
 
-impl Pass for RemoveNodePass {
-    fn run_pass(&self, graph: &mut Graph<String, ()>) {
-        *graph = graph.filter_map(
-            |_, name| if self.terminate_funcs
-                .iter()
-                .any(|re| re.is_match(name).unwrap()) {
-                debug!("Terminating node {name}");
-                None
-            } else {
-                Some(name.clone())
-            },
-            |_, ()| Some(())
-        );
+impl<N: NodeLabel, E: EdgeLabel> Pass<N, E> for RemoveNodePass {
+    fn run_pass(&self, graph: &mut Graph<N, E>) -> Result<PassReport, PassError> {
+        self.run_pass_cancellable(graph, &CancellationToken::new())
+    }
+
+    fn run_pass_cancellable(
+        &self,
+        graph: &mut Graph<N, E>,
+        cancel: &CancellationToken
+    ) -> Result<PassReport, PassError> {
+        let before = graph_size(graph);
+        let terminated = self.terminate_funcs.match_nodes(graph, cancel)?;
+        for idx in graph.node_indices().filter(|idx| terminated[idx.index()]) {
+            debug!("Terminating node {}", graph[idx].name());
+        }
+        if !terminated.contains(&true) {
+            return Ok(PassReport::default());
+        }
+        // Unlike removing nodes one by one, keeps the order of remaining nodes and edges
+        let keep = terminated.iter().map(|&t| !t).collect::<Vec<_>>();
+        retain_in_order(graph, &keep, |_| true);
+        Ok(PassReport::from_size_change(before, graph))
     }
 
     fn name(&self) -> String {
@@ -51,21 +256,24 @@ impl Pass for RemoveNodePass {
     }
 }
 
+/// Symbols to connect to names matching a rule, without duplicates. They are resolved
+/// to nodes in graph order, so generated edges keep the same order between runs
 pub enum RegexMatchAction<T>
 where T : Hash + Eq {
-    AddIncoming(HashSet<T>),
-    AddOutgoing(HashSet<T>),
+    AddIncoming(Vec<T>),
+    AddOutgoing(Vec<T>),
 }
 
 impl RegexMatchAction<String> {
-    fn to_idx_list(&self, graph: &Graph<String, ()>) -> RegexMatchAction<NodeIndex> {
+    fn to_idx_list<N: NodeLabel, E>(&self, graph: &Graph<N, E>) -> RegexMatchAction<NodeIndex> {
         let required_symbols = match &self {
             RegexMatchAction::AddIncoming(l)
             | RegexMatchAction::AddOutgoing(l) => l
         };
+        let required_symbols = required_symbols.iter().map(String::as_str).collect::<HashSet<_>>();
         let matched = graph
             .node_indices()
-            .filter(|&idx| required_symbols.contains(&graph[idx]))
+            .filter(|&idx| required_symbols.contains(graph[idx].name()))
             .collect();
         match &self {
             RegexMatchAction::AddIncoming(_) => RegexMatchAction::AddIncoming(matched),
@@ -76,7 +284,11 @@ impl RegexMatchAction<String> {
 
 #[derive(Default)]
 pub struct RegexEdgeGenPass {
-    rules: Vec<(Regex, RegexMatchAction<String>)>
+    rules: Vec<(Regex, RegexMatchAction<String>)>,
+    /// Regexes of `rules` in the same order, rebuilt when rules are added
+    regexes: RegexList,
+    /// Rules added from lines match whole names only
+    full_match: bool,
 }
 
 impl RegexEdgeGenPass {
@@ -85,17 +297,43 @@ impl RegexEdgeGenPass {
         Self::default()
     }
 
-    #[must_use]
-    pub fn new_from_str(data: &str) -> Self {
-        let mut result = Self::new();
-        for line in data.lines() {
-            result.add_rule_from_line(line);
+    /// Pass with a rule per line of `data`, blank lines are skipped.
+    /// Fails with every invalid rule, see [`Self::new_lenient`] to skip them
+    pub fn new_from_str(data: &str) -> Result<Self, Vec<RuleError>> {
+        Self::new_from_str_with(data, false)
+    }
+
+    /// Same as [`Self::new_from_str`], with `full_match` rule regexes must match whole names
+    pub fn new_from_str_with(data: &str, full_match: bool) -> Result<Self, Vec<RuleError>> {
+        let (pass, errors) = Self::new_lenient(data, full_match);
+        if errors.is_empty() { Ok(pass) } else { Err(errors) }
+    }
+
+    /// Pass with valid rules of `data` only, along with errors of the invalid ones
+    pub fn new_lenient(data: &str, full_match: bool) -> (Self, Vec<RuleError>) {
+        let mut result = Self { full_match, ..Self::new() };
+        let mut errors = vec![];
+        for (i, line) in data.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match result.parse_rule_line(line) {
+                Ok(rule) => result.rules.push(rule),
+                Err(kind) => errors.push(RuleError { line_number: i + 1, line: line.to_string(), kind }),
+            }
         }
-        result
+        result.build_regexes();
+        (result, errors)
     }
 
+    /// Add a rule, rebuilding the regexes of all rules. Create passes with many rules with [`Self::new_from_str`]
     pub fn add_rule(&mut self, rule: (Regex, RegexMatchAction<String>)) {
         self.rules.push(rule);
+        self.build_regexes();
+    }
+
+    fn build_regexes(&mut self) {
+        self.regexes = RegexList::new(self.rules.iter().map(|(re, _)| re.clone()).collect());
     }
     
     fn split_line(line: &str) -> Option<(&str, &str, bool)> {
@@ -108,79 +346,82 @@ impl RegexEdgeGenPass {
         }
     }
 
-    pub fn add_rule_from_line(&mut self, line: &str) {
-        let Some((regex_part, list_part, incoming)) = Self::split_line(line) else {
-            error!("Rule line does not contain '->' or '<-' separator, discarding it: \"{}\"", line);
-            return;
-        };
+    /// Add a `"regex" -> names` or `"regex" <- names` rule
+    pub fn add_rule_from_line(&mut self, line: &str) -> Result<(), RuleErrorKind> {
+        let rule = self.parse_rule_line(line)?;
+        self.add_rule(rule);
+        Ok(())
+    }
+
+    fn parse_rule_line(&self, line: &str) -> Result<(Regex, RegexMatchAction<String>), RuleErrorKind> {
+        let (regex_part, list_part, incoming) = Self::split_line(line).ok_or(RuleErrorKind::MissingSeparator)?;
         let regex_str = regex_part.trim();
         if !regex_str.starts_with('\"') 
             || !regex_str.ends_with('\"') 
             || regex_str.len() < 2 {
-            error!("Regex part is not wrapped with quotes, discarding it: \"{}\"", line);
-            return;
+            return Err(RuleErrorKind::UnquotedPattern);
         }
-        let Ok(regex) = Regex::new(&regex_str[1..regex_str.len() - 1]) else {
-            error!("Regex is incorrect, discarding it: \"{}\"", line);
-            return;
+        let pattern = &regex_str[1..regex_str.len() - 1];
+        let regex = if self.full_match {
+            Regex::new(&full_match_pattern(pattern))
+        } else {
+            Regex::new(pattern)
         };
+        let regex = regex.map_err(|e| RuleErrorKind::Regex(Box::new(e)))?;
+        let mut seen = HashSet::new();
         let symlist = list_part.split_whitespace()
+            .filter(|name| seen.insert(*name))
             .map(ToString::to_string)
             .collect();
 
         if incoming {
-            self.rules.push((
-                regex,
-                RegexMatchAction::AddIncoming(symlist)
-            ));
+            Ok((regex, RegexMatchAction::AddIncoming(symlist)))
         } else {
-            self.rules.push((
-                regex,
-                RegexMatchAction::AddOutgoing(symlist)
-            ));
+            Ok((regex, RegexMatchAction::AddOutgoing(symlist)))
         }
     }
 }
 
-impl Pass for RegexEdgeGenPass {
-    fn run_pass(&self, graph: &mut Graph<String, ()>) {
-        let resolved_rules: Vec<(&Regex, RegexMatchAction<NodeIndex>)> = self.rules
+impl<N: NodeLabel, E: EdgeLabel> Pass<N, E> for RegexEdgeGenPass {
+    fn run_pass(&self, graph: &mut Graph<N, E>) -> Result<PassReport, PassError> {
+        self.run_pass_cancellable(graph, &CancellationToken::new())
+    }
+
+    fn run_pass_cancellable(
+        &self,
+        graph: &mut Graph<N, E>,
+        cancel: &CancellationToken
+    ) -> Result<PassReport, PassError> {
+        let before = graph_size(graph);
+        let regexes = &self.regexes;
+        let resolved_rules: Vec<RegexMatchAction<NodeIndex>> = self.rules
             .iter()
-            .map(|(r, action)| (r, action.to_idx_list(graph)))
+            .map(|(_, action)| action.to_idx_list(graph))
             .collect();
-        let mut total_resolved: usize = 0;
-
-        for idx in graph.node_indices() {
-            for (re, links) in &resolved_rules {
-                if !re.is_match(&graph[idx]).unwrap() {
-                    continue;
-                }
-                // This function matched regex
-                let this_f_id = HashSet::from([idx]);
-                let from_funcs: &HashSet<NodeIndex>;
-                let to_funcs: &HashSet<NodeIndex>;
-
-                match links {
-                    RegexMatchAction::AddIncoming(l) => {
-                        from_funcs = l;
-                        to_funcs = &this_f_id;
-                    }
-                    RegexMatchAction::AddOutgoing(l) => {
-                        from_funcs = &this_f_id;
-                        to_funcs = l;
-                    }
-                }
-
-                for &src in from_funcs {
-                    for &dst in to_funcs {
-                        total_resolved += 1;
-                        debug!("Adding {} -> {}", graph[src], graph[dst]);
-                        graph.add_edge(src, dst, ());
-                    }
+        // Only names are shared between threads, so node types don't have to be `Sync`
+        let names = graph.node_weights().map(NodeLabel::name).collect::<Vec<_>>();
+        let edges_of = |(i, name): (usize, &&str)| -> Result<Vec<(NodeIndex, NodeIndex)>, PassError> {
+            cancel.check()?;
+            let idx = NodeIndex::new(i);
+            let mut edges = vec![];
+            for rule in regexes.matches(name)? {
+                match &resolved_rules[rule] {
+                    RegexMatchAction::AddIncoming(l) => edges.extend(l.iter().map(|&src| (src, idx))),
+                    RegexMatchAction::AddOutgoing(l) => edges.extend(l.iter().map(|&dst| (idx, dst))),
                 }
             }
+            Ok(edges)
+        };
+        #[cfg(feature = "parallel")]
+        let new_edges = names.par_iter().enumerate().map(edges_of).collect::<Result<Vec<_>, _>>()?;
+        #[cfg(not(feature = "parallel"))]
+        let new_edges = names.iter().enumerate().map(edges_of).collect::<Result<Vec<_>, _>>()?;
+
+        for (src, dst) in new_edges.into_iter().flatten() {
+            debug!("Adding {} -> {}", graph[src].name(), graph[dst].name());
+            graph.add_edge(src, dst, E::synthetic());
         }
-        info!("RegexNodePass resolved {} calls", total_resolved);
+        Ok(PassReport::from_size_change(before, graph))
     }
 
     fn name(&self) -> String {
@@ -188,9 +429,13 @@ impl Pass for RegexEdgeGenPass {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CutDegPass {
     max_incoming: usize,
     max_outgoing: usize,
+    /// Count distinct callers and callees instead of edges
+    #[cfg_attr(feature = "serde", serde(default))]
+    distinct_neighbors: bool,
 }
 
 impl CutDegPass {
@@ -199,50 +444,87 @@ impl CutDegPass {
         Self {
             max_incoming: max_incoming.unwrap_or(usize::MAX),
             max_outgoing: max_outgoing.unwrap_or(usize::MAX),
+            distinct_neighbors: false,
         }
     }
+
+    /// Count distinct neighbors, so parallel edges from one caller count once
+    #[must_use]
+    pub fn with_distinct_neighbors(mut self) -> Self {
+        self.distinct_neighbors = true;
+        self
+    }
 }
 
-impl Pass for CutDegPass {
-    fn run_pass(&self, graph: &mut Graph<String, ()>) {
+impl<N: NodeLabel, E: EdgeLabel> Pass<N, E> for CutDegPass {
+    fn run_pass(&self, graph: &mut Graph<N, E>) -> Result<PassReport, PassError> {
+        let before = graph_size(graph);
         // (deg-in; deg-out)
-        let mut deg: Vec<(usize, usize)> = vec![(0, 0); graph.node_count()];
+        let mut deg: Vec<(u32, u32)> = vec![(0, 0); graph.node_count()];
+        let mut seen: HashSet<(NodeIndex, NodeIndex)> = HashSet::new();
         for edge in graph.edge_references() {
+            if self.distinct_neighbors && !seen.insert((edge.source(), edge.target())) {
+                continue;
+            }
             deg[edge.source().index()].1 += 1;
             deg[edge.target().index()].0 += 1;
         }
-        graph.retain_nodes(
-            |_, v| deg[v.index()].0 <= self.max_incoming &&
-                deg[v.index()].1 <= self.max_outgoing,
-        );
+        let keep = deg.iter()
+            .map(|&(incoming, outgoing)| incoming as usize <= self.max_incoming && outgoing as usize <= self.max_outgoing)
+            .collect::<Vec<_>>();
+        if !keep.contains(&false) {
+            return Ok(PassReport::default());
+        }
+        retain_in_order(graph, &keep, |_| true);
+        Ok(PassReport::from_size_change(before, graph))
     }
 
     fn name(&self) -> String {
+        // Omitted bounds are stored as usize::MAX
+        let bound = |max: usize| match max {
+            usize::MAX => " unlimited".to_string(),
+            max => format!(" < {}", max + 1),
+        };
         format!(
-            "degree filtering(incoming < {}, outgoing < {})",
-            self.max_incoming + 1,
-            self.max_outgoing + 1
+            "degree filtering(incoming{}, outgoing{}{})",
+            bound(self.max_incoming),
+            bound(self.max_outgoing),
+            if self.distinct_neighbors { ", distinct neighbors" } else { "" }
         )
     }
 }
 
+/// Keep one edge between two nodes, calls of the others are added to its count
 #[derive(Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct UniqueEdgesPass {}
 
-impl Pass for UniqueEdgesPass {
-    fn run_pass(&self, graph: &mut Graph<String, ()>) {
-        let mut added_nodes: HashSet<(usize, usize)> = HashSet::new();
-        *graph = graph.filter_map(
-            |_, v| Some(v.clone()),
-            |idx, ()| {
-                let (src, dst) = graph.edge_endpoints(idx)?;
-                if added_nodes.insert((src.index(), dst.index())) {
-                    Some(())
-                } else {
-                    None
+impl<N: NodeLabel, E: EdgeLabel> Pass<N, E> for UniqueEdgesPass {
+    fn run_pass(&self, graph: &mut Graph<N, E>) -> Result<PassReport, PassError> {
+        let before = graph_size(graph);
+        // Calls of duplicates are added to the first edge between two nodes
+        let mut first: HashMap<(NodeIndex, NodeIndex), EdgeIndex> = HashMap::new();
+        let mut duplicates = vec![];
+        for edge in graph.edge_references() {
+            match first.entry((edge.source(), edge.target())) {
+                Entry::Occupied(entry) => duplicates.push((*entry.get(), edge.id())),
+                Entry::Vacant(entry) => {
+                    entry.insert(edge.id());
                 }
             }
-        );
+        }
+        if duplicates.is_empty() {
+            return Ok(PassReport::default());
+        }
+        let mut removed = vec![false; graph.edge_count()];
+        for (first, duplicate) in duplicates {
+            let count = graph[duplicate].count();
+            graph[first].add_count(count);
+            removed[duplicate.index()] = true;
+        }
+        let keep = vec![true; graph.node_count()];
+        retain_in_order(graph, &keep, |idx| !removed[idx.index()]);
+        Ok(PassReport::from_size_change(before, graph))
     }
 
     fn name(&self) -> String {
@@ -250,6 +532,7 @@ impl Pass for UniqueEdgesPass {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SubgraphExtractionPass {
     tags: HashSet<String>,
 }
@@ -270,37 +553,16 @@ impl SubgraphExtractionPass {
     }
 }
 
-impl Pass for SubgraphExtractionPass {
-    fn run_pass(&self, graph: &mut Graph<String, ()>) {
-        let tagged_nodes = graph.node_weights()
-            .enumerate()
-            .filter_map(|(i, node)| {
-                if self.tags.contains(node) {
-                    Some(i)
-                } else {
-                    None
-                }
-            })
-            .collect::<Vec<_>>();
-        let mut dfs_visitor = Dfs::empty(&*graph);
-        let mut visited = HashSet::new();
-        for v in tagged_nodes {
-            #[allow(clippy::cast_possible_truncation)]
-            dfs_visitor.move_to(NodeIndex::from(v as DefaultIx));
-            while let Some(reached) = dfs_visitor.next(&*graph) {
-                visited.insert(reached);
-            }
+impl<N: NodeLabel, E: EdgeLabel> Pass<N, E> for SubgraphExtractionPass {
+    fn run_pass(&self, graph: &mut Graph<N, E>) -> Result<PassReport, PassError> {
+        let before = graph_size(graph);
+        let tagged_nodes = graph.node_indices().filter(|&idx| self.tags.contains(graph[idx].name()));
+        let reached = CsrGraph::from_graph(graph).reachable(tagged_nodes);
+        if !reached.contains(&false) {
+            return Ok(PassReport::default());
         }
-        *graph = graph.filter_map(
-            |idx, value| {
-                if visited.contains(&idx) {
-                    Some(value.clone())
-                } else {
-                    None
-                }
-            },
-            |_, ()| Some(())
-        );
+        retain_in_order(graph, &reached, |_| true);
+        Ok(PassReport::from_size_change(before, graph))
     }
 
     fn name(&self) -> String {
@@ -309,11 +571,13 @@ impl Pass for SubgraphExtractionPass {
 }
 
 #[derive(Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ReverseGraphPass {}
 
-impl Pass for ReverseGraphPass {
-    fn run_pass(&self, graph: &mut Graph<String, ()>) {
+impl<N: NodeLabel, E: EdgeLabel> Pass<N, E> for ReverseGraphPass {
+    fn run_pass(&self, graph: &mut Graph<N, E>) -> Result<PassReport, PassError> {
         graph.reverse();
+        Ok(PassReport::default())
     }
 
     fn name(&self) -> String {
@@ -325,50 +589,68 @@ impl Pass for ReverseGraphPass {
 // If we have v -> matched -> u, then an edge v -> u is added
 // All nodes are preserved
 pub struct ReparentGraphPass {
-    reparent_rules: Vec<Regex>
+    reparent_rules: RegexList
 }
 
 impl ReparentGraphPass {
-    #[must_use]
-    pub fn new_from_str(data: &str) -> Self {
-        Self {
-            reparent_rules: data.lines()
-                .flat_map(|l| {
-                    Regex::new(l).inspect_err(|e| error!("Wrong regex \"{}\": {}", l, e))
-                })
-                .collect(),
-        }
+    /// Pass with a regex per line of `data`. Fails with every invalid regex, see [`Self::new_lenient`] to skip them
+    pub fn new_from_str(data: &str) -> Result<Self, Vec<RuleError>> {
+        let (pass, errors) = Self::new_lenient(data);
+        if errors.is_empty() { Ok(pass) } else { Err(errors) }
     }
-}
 
-impl Pass for ReparentGraphPass {
-    fn run_pass(&self, graph: &mut Graph<String, ()>) {
-        let mut new_graph = graph.clone();
-        let mut matched_nodes = HashSet::new();
-        let mut reparanted = 0usize;
-        for node in graph.node_indices() {
-            if self.reparent_rules.iter()
-                .any(|rule| rule.is_match(&graph[node]).unwrap()) {
-                matched_nodes.insert(node);
+    /// Pass with valid regexes of `data` only, along with errors of the invalid ones
+    pub fn new_lenient(data: &str) -> (Self, Vec<RuleError>) {
+        let mut regexes = vec![];
+        let mut errors = vec![];
+        for (i, line) in data.lines().enumerate() {
+            match Regex::new(line) {
+                Ok(regex) => regexes.push(regex),
+                Err(e) => errors.push(RuleError {
+                    line_number: i + 1,
+                    line: line.to_string(),
+                    kind: RuleErrorKind::Regex(Box::new(e)),
+                }),
             }
         }
+        (Self { reparent_rules: RegexList::new(regexes) }, errors)
+    }
+
+    /// Share match results with other passes of a run
+    #[must_use]
+    pub fn with_match_cache(mut self, cache: Arc<MatchCache>) -> Self {
+        self.reparent_rules.cache = Some(cache);
+        self
+    }
+}
+
+impl<N: NodeLabel, E: EdgeLabel> Pass<N, E> for ReparentGraphPass {
+    fn run_pass(&self, graph: &mut Graph<N, E>) -> Result<PassReport, PassError> {
+        self.run_pass_cancellable(graph, &CancellationToken::new())
+    }
+
+    fn run_pass_cancellable(
+        &self,
+        graph: &mut Graph<N, E>,
+        cancel: &CancellationToken
+    ) -> Result<PassReport, PassError> {
+        let before = graph_size(graph);
+        let mut new_edges = vec![];
+        let matched = self.reparent_rules.match_nodes(graph, cancel)?;
+        let matched_nodes = graph.node_indices().filter(|idx| matched[idx.index()]).collect::<HashSet<_>>();
         for v in graph.node_indices() {
             for next in graph
                 .neighbors(v)
                 .filter(|n| matched_nodes.contains(n)) {
                 // need to reparent all next children
-                debug!("Reparent {} children to {}", graph[next], graph[v]);
-                for child in graph.neighbors(next) {
-                    new_graph.add_edge(v, child, ());
-                    reparanted += 1;
-                }
+                debug!("Reparent {} children to {}", graph[next].name(), graph[v].name());
+                new_edges.extend(graph.neighbors(next).map(|child| (v, child)));
             }
         }
-        info!(
-            "Reparent pass matched {} nodes and added {} new edges", 
-            matched_nodes.len(), reparanted
-        );
-        *graph = new_graph;
+        for (v, child) in new_edges {
+            graph.add_edge(v, child, E::synthetic());
+        }
+        Ok(PassReport::from_size_change(before, graph).with("matched", matched_nodes.len()))
     }
 
     fn name(&self) -> String {
@@ -380,28 +662,87 @@ impl Pass for ReparentGraphPass {
 pub struct RemoveEdgesPass {
     /// List of regular expressions in format (from_re\0to_re)
     rules: Vec<Regex>,
+    /// Remove only edges of this kind. Without rules all edges of this kind are removed
+    kind: Option<EdgeKind>,
+    /// Rules added from strings match whole names only
+    full_match: bool,
+}
+
+/// Whitespace separated patterns of a rule, `"..."` patterns may contain spaces.
+/// Quotes are kept in the pattern if escaped with a backslash.
+fn rule_patterns(rule: &str) -> Result<Vec<&str>, RuleErrorKind> {
+    let mut patterns = vec![];
+    let mut rest = rule.trim_start();
+    while !rest.is_empty() {
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let mut escaped = false;
+            let end = quoted.char_indices()
+                .find(|&(_, c)| {
+                    let end = c == '"' && !escaped;
+                    escaped = c == '\\' && !escaped;
+                    end
+                })
+                .ok_or(RuleErrorKind::UnterminatedQuote)?
+                .0;
+            patterns.push(&quoted[..end]);
+            rest = &quoted[end + 1..];
+        } else {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            patterns.push(&rest[..end]);
+            rest = &rest[end..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(patterns)
 }
 
 impl RemoveEdgesPass {
-    pub fn new_from_str(data: &str) -> Self {
-        let mut result = RemoveEdgesPass { rules: Vec::new() };
-        for line in data.lines() {
-            result.add_rule_from_str(line);
+    /// Pass with a rule per line of `data`, blank lines are skipped
+    pub fn new_from_str(data: &str) -> Result<Self, RuleError> {
+        Self::new_from_str_with(data, false)
+    }
+
+    /// Same as [`Self::new_from_str`], with `full_match` both patterns of a rule must match whole names
+    pub fn new_from_str_with(data: &str, full_match: bool) -> Result<Self, RuleError> {
+        let mut result = RemoveEdgesPass { full_match, ..RemoveEdgesPass::default() };
+        for (i, line) in data.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            result.add_rule_from_str(line).map_err(|kind| RuleError {
+                line_number: i + 1,
+                line: line.to_string(),
+                kind,
+            })?;
         }
-        result
+        Ok(result)
     }
 
-    pub fn add_rule_from_str(&mut self, rule: &str) {
-        let (l, r) = rule.split_once(' ').unwrap();
-        self.rules.push(
-            Regex::new(&Self::get_edge_string(l, r)).unwrap()
-        );
+    #[must_use]
+    pub fn with_kind(mut self, kind: EdgeKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    /// Add a `src_regex dst_regex` rule, patterns containing spaces are quoted: `"a b" c`
+    pub fn add_rule_from_str(&mut self, rule: &str) -> Result<(), RuleErrorKind> {
+        let (from, to) = match rule_patterns(rule)?[..] {
+            [from, to] => (from, to),
+            [] | [_] => return Err(RuleErrorKind::MissingPattern),
+            _ => return Err(RuleErrorKind::ExtraPattern),
+        };
+        let pattern = if self.full_match {
+            full_match_pattern(&Self::get_edge_string(&format!("(?:{from})"), &format!("(?:{to})")))
+        } else {
+            Self::get_edge_string(from, to)
+        };
+        let regex = Regex::new(&pattern).map_err(|e| RuleErrorKind::Regex(Box::new(e)))?;
+        self.rules.push(regex);
+        Ok(())
     }
 
-    fn edge_matches(&self, from_label: &str, to_label: &str) -> bool {
-        self.rules.iter().any(|re| {
-            re.is_match(&Self::get_edge_string(from_label, to_label)).unwrap()
-        })
+    fn edge_matches(&self, from_label: &str, to_label: &str) -> Result<bool, PassError> {
+        any_match(&self.rules, &Self::get_edge_string(from_label, to_label))
     }
 
     fn get_edge_string(from_label: &str, to_label: &str) -> String {
@@ -409,23 +750,43 @@ impl RemoveEdgesPass {
     }
 }
 
-impl Pass for RemoveEdgesPass {
-    fn run_pass(&self, graph: &mut Graph<String, ()>) {
-        let mut edges_removed = 0u32;
-        *graph = graph.filter_map(
-            |_, name| Some(name.clone()),
-            |e_idx, ()| {
-                let (from, to) = graph.edge_endpoints(e_idx)?;
-                if self.edge_matches(graph[from].as_ref(), graph[to].as_ref()) {
-                    debug!("Terminating edge {} -> {}", graph[from], graph[to]);
-                    edges_removed += 1;
-                    None
+impl<N: NodeLabel, E: EdgeLabel> Pass<N, E> for RemoveEdgesPass {
+    fn run_pass(&self, graph: &mut Graph<N, E>) -> Result<PassReport, PassError> {
+        self.run_pass_cancellable(graph, &CancellationToken::new())
+    }
+
+    fn run_pass_cancellable(
+        &self,
+        graph: &mut Graph<N, E>,
+        cancel: &CancellationToken
+    ) -> Result<PassReport, PassError> {
+        let before = graph_size(graph);
+        let removed = graph.raw_edges()
+            .iter()
+            .map(|e| {
+                cancel.check()?;
+                if self.kind.is_some_and(|k| k != e.weight.kind()) {
+                    Ok(false)
+                } else if self.rules.is_empty() {
+                    Ok(self.kind.is_some())
                 } else {
-                    Some(())
+                    self.edge_matches(graph[e.source()].name(), graph[e.target()].name())
                 }
-            }
-        );
-        debug!("Removed {edges_removed} edges");
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if !removed.contains(&true) {
+            return Ok(PassReport::default());
+        }
+        for (edge, _) in graph.raw_edges().iter().zip(&removed).filter(|(_, &removed)| removed) {
+            debug!(
+                "Terminating {} edge {} -> {}",
+                edge.weight.kind(),
+                graph[edge.source()].name(),
+                graph[edge.target()].name()
+            );
+        }
+        retain_in_order(graph, &vec![true; graph.node_count()], |idx| !removed[idx.index()]);
+        Ok(PassReport::from_size_change(before, graph))
     }
 
     fn name(&self) -> String {
@@ -433,34 +794,588 @@ impl Pass for RemoveEdgesPass {
     }
 }
 
+/// Keep only nodes for symbols defined in a binary, as listed by `nm` or `objdump -t`
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct KeepDefinedPass {
+    symbols: HashSet<String>,
+}
+
+impl KeepDefinedPass {
+    #[must_use]
+    pub fn new(symbols: HashSet<String>) -> Self {
+        Self { symbols }
+    }
+
+    #[must_use]
+    pub fn new_from_str(data: &str) -> Self {
+        Self::new(symbols_from_nm(data))
+    }
+}
+
+impl<N: NodeLabel, E: EdgeLabel> Pass<N, E> for KeepDefinedPass {
+    fn run_pass(&self, graph: &mut Graph<N, E>) -> Result<PassReport, PassError> {
+        let before = graph_size(graph);
+        let keep = graph.node_weights().map(|node| self.symbols.contains(node.name())).collect::<Vec<_>>();
+        if !keep.contains(&false) {
+            return Ok(PassReport::default());
+        }
+        retain_in_order(graph, &keep, |_| true);
+        Ok(PassReport::from_size_change(before, graph))
+    }
+
+    fn name(&self) -> String {
+        "keep defined symbols".to_string()
+    }
+}
+
+/// Keep only nodes for symbols present in `/proc/kallsyms` or `System.map`
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct KeepInKallsymsPass {
+    symbols: HashMap<String, BTreeSet<String>>,
+}
+
+impl KeepInKallsymsPass {
+    #[must_use]
+    pub fn new_from_str(data: &str) -> Self {
+        Self { symbols: symbols_from_kallsyms(data) }
+    }
+}
+
+impl<N: NodeLabel, E: EdgeLabel> Pass<N, E> for KeepInKallsymsPass {
+    fn run_pass(&self, graph: &mut Graph<N, E>) -> Result<PassReport, PassError> {
+        let before = graph_size(graph);
+        let keep = graph.node_weights().map(|node| self.symbols.contains_key(node.name())).collect::<Vec<_>>();
+        if !keep.contains(&false) {
+            return Ok(PassReport::default());
+        }
+        retain_in_order(graph, &keep, |_| true);
+        Ok(PassReport::from_size_change(before, graph))
+    }
+
+    fn name(&self) -> String {
+        "keep kallsyms symbols".to_string()
+    }
+}
+
+/// Store modules owning every symbol in the `module` node attribute.
+/// Symbols built into the kernel belong to `vmlinux`.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AnnotateModulesPass {
+    symbols: HashMap<String, BTreeSet<String>>,
+}
+
+impl AnnotateModulesPass {
+    #[must_use]
+    pub fn new_from_str(data: &str) -> Self {
+        Self { symbols: symbols_from_kallsyms(data) }
+    }
+}
+
+impl<N: NodeLabel, E: EdgeLabel> Pass<N, E> for AnnotateModulesPass {
+    fn run_pass(&self, graph: &mut Graph<N, E>) -> Result<PassReport, PassError> {
+        let mut annotated = 0;
+        for node in graph.node_weights_mut() {
+            if let Some(modules) = self.symbols.get(node.name()) {
+                let modules = modules.iter().cloned().collect::<Vec<_>>().join(",");
+                node.set_attr("module", modules);
+                annotated += 1;
+            }
+        }
+        Ok(PassReport::default().with("annotated", annotated))
+    }
+
+    fn name(&self) -> String {
+        "annotate modules".to_string()
+    }
+}
+
+/// Store input files every node came from in its `source` attribute
+#[derive(Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AnnotateSourcesPass {}
+
+impl Pass for AnnotateSourcesPass {
+    fn run_pass(&self, graph: &mut CallGraph) -> Result<PassReport, PassError> {
+        let mut annotated = 0;
+        for node in graph.node_weights_mut() {
+            if node.sources.is_empty() {
+                continue;
+            }
+            let sources = node.sources
+                .iter()
+                .map(|s| s.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(",");
+            node.attrs.insert("source".to_string(), sources);
+            annotated += 1;
+        }
+        Ok(PassReport::default().with("annotated", annotated))
+    }
+
+    fn name(&self) -> String {
+        "annotate sources".to_string()
+    }
+}
+
+/// Name or label of the node LLVM's `-dot-callgraph` adds as the caller of every address-taken function
+pub const EXTERNAL_NODE: &str = "external node";
+
+/// Detect LLVM's "external node" by its name or by its label, record labels like `{external node}` included
+fn is_external_node<N: NodeLabel>(node: &N) -> bool {
+    node.name() == EXTERNAL_NODE
+        || node.attr("label").is_some_and(|label| label.trim_matches(['{', '}']) == EXTERNAL_NODE)
+}
+
+/// What [`ExternalNodePass`] does with the "external node"
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum ExternalNodePolicy {
+    /// Remove the node with all its edges
+    #[default]
+    Drop,
+    /// Leave the graph unchanged, only count such nodes
+    Keep,
+    /// Remove the node, marking functions it calls with `address_taken="true"`
+    Annotate,
+}
+
+impl FromStr for ExternalNodePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "drop" => Ok(ExternalNodePolicy::Drop),
+            "keep" => Ok(ExternalNodePolicy::Keep),
+            "annotate" => Ok(ExternalNodePolicy::Annotate),
+            _ => Err(format!("unknown external node policy \"{s}\"")),
+        }
+    }
+}
+
+/// Handle the synthetic "external node" of LLVM call graphs, which calls every
+/// address-taken function and so skews degrees and reachability
+#[derive(Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExternalNodePass {
+    policy: ExternalNodePolicy,
+}
+
+impl ExternalNodePass {
+    #[must_use]
+    pub fn new(policy: ExternalNodePolicy) -> Self {
+        Self { policy }
+    }
+}
+
+impl<N: NodeLabel, E: EdgeLabel> Pass<N, E> for ExternalNodePass {
+    fn run_pass(&self, graph: &mut Graph<N, E>) -> Result<PassReport, PassError> {
+        let before = graph_size(graph);
+        let external = graph.node_weights().map(is_external_node).collect::<Vec<_>>();
+        let found = external.iter().filter(|&&e| e).count();
+        if self.policy == ExternalNodePolicy::Keep || found == 0 {
+            return Ok(PassReport::default().with("external nodes", found));
+        }
+        let mut annotated = 0;
+        if self.policy == ExternalNodePolicy::Annotate {
+            let targets = graph.edge_references()
+                .filter(|e| external[e.source().index()] && !external[e.target().index()])
+                .map(|e| e.target())
+                .collect::<BTreeSet<_>>();
+            for idx in targets {
+                graph[idx].set_attr("address_taken", "true".to_string());
+                annotated += 1;
+            }
+        }
+        let keep = external.iter().map(|&e| !e).collect::<Vec<_>>();
+        retain_in_order(graph, &keep, |_| true);
+        Ok(PassReport::from_size_change(before, graph)
+            .with("external nodes", found)
+            .with("annotated", annotated))
+    }
+
+    fn name(&self) -> String {
+        let policy = match self.policy {
+            ExternalNodePolicy::Drop => "drop",
+            ExternalNodePolicy::Keep => "keep",
+            ExternalNodePolicy::Annotate => "annotate",
+        };
+        format!("external node({policy})")
+    }
+}
+
+/// What [`OverlayCoveragePass`] does with covered and uncovered functions
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum CoverageOverlay {
+    /// Mark every node with `covered="true"` or `covered="false"`
+    #[default]
+    Annotate,
+    /// Keep covered functions only
+    Covered,
+    /// Keep uncovered functions only
+    Uncovered,
+}
+
+impl FromStr for CoverageOverlay {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "annotate" => Ok(CoverageOverlay::Annotate),
+            "covered" => Ok(CoverageOverlay::Covered),
+            "uncovered" => Ok(CoverageOverlay::Uncovered),
+            _ => Err(format!("unknown coverage overlay \"{s}\"")),
+        }
+    }
+}
+
+/// Overlay a coverage report on the graph. With a `frontier` distance, uncovered functions
+/// called within that many calls from covered ones are logged, nearest first, and annotated with
+/// their distance in `coverage_frontier`: the next targets for fuzzing
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OverlayCoveragePass {
+    covered: HashSet<String>,
+    overlay: CoverageOverlay,
+    frontier: Option<usize>,
+}
+
+impl OverlayCoveragePass {
+    #[must_use]
+    pub fn new(covered: HashSet<String>, overlay: CoverageOverlay, frontier: Option<usize>) -> Self {
+        Self { covered, overlay, frontier }
+    }
+
+    /// Pass for a report read by [`covered_functions`]
+    #[must_use]
+    pub fn new_from_str(data: &str, overlay: CoverageOverlay, frontier: Option<usize>) -> Self {
+        Self::new(covered_functions(data), overlay, frontier)
+    }
+}
+
+impl<N: NodeLabel, E: EdgeLabel> Pass<N, E> for OverlayCoveragePass {
+    fn run_pass(&self, graph: &mut Graph<N, E>) -> Result<PassReport, PassError> {
+        let before = graph_size(graph);
+        let covered = graph.node_weights().map(|node| self.covered.contains(node.name())).collect::<Vec<_>>();
+        let covered_count = covered.iter().filter(|&&c| c).count();
+
+        // Calls from the nearest covered function, breadth first through uncovered ones
+        let mut frontier = vec![None; graph.node_count()];
+        if let Some(max_distance) = self.frontier {
+            let csr = CsrGraph::from_graph(graph);
+            let mut queue = graph.node_indices().filter(|idx| covered[idx.index()]).collect::<VecDeque<_>>();
+            let mut distance = vec![None; graph.node_count()];
+            for idx in &queue {
+                distance[idx.index()] = Some(0);
+            }
+            while let Some(idx) = queue.pop_front() {
+                let next_distance = distance[idx.index()].map_or(0, |d: usize| d + 1);
+                if next_distance > max_distance {
+                    continue;
+                }
+                for next in csr.neighbors(idx) {
+                    if distance[next.index()].is_none() {
+                        distance[next.index()] = Some(next_distance);
+                        frontier[next.index()] = Some(next_distance);
+                        queue.push_back(next);
+                    }
+                }
+            }
+            let mut reported = graph.node_indices()
+                .filter_map(|idx| Some((frontier[idx.index()]?, graph[idx].name())))
+                .collect::<Vec<_>>();
+            reported.sort_unstable();
+            for (distance, name) in reported {
+                info!("Uncovered function \"{name}\" is {distance} calls from covered code");
+            }
+        }
+        let frontier_count = frontier.iter().flatten().count();
+
+        match self.overlay {
+            CoverageOverlay::Annotate => {
+                for idx in graph.node_indices() {
+                    graph[idx].set_attr("covered", covered[idx.index()].to_string());
+                    if let Some(distance) = frontier[idx.index()] {
+                        graph[idx].set_attr("coverage_frontier", distance.to_string());
+                    }
+                }
+            }
+            CoverageOverlay::Covered | CoverageOverlay::Uncovered => {
+                let keep_covered = self.overlay == CoverageOverlay::Covered;
+                let keep = covered.iter().map(|&c| c == keep_covered).collect::<Vec<_>>();
+                for idx in graph.node_indices() {
+                    if let (true, Some(distance)) = (keep[idx.index()], frontier[idx.index()]) {
+                        graph[idx].set_attr("coverage_frontier", distance.to_string());
+                    }
+                }
+                if keep.contains(&false) {
+                    retain_in_order(graph, &keep, |_| true);
+                }
+            }
+        }
+        Ok(PassReport::from_size_change(before, graph)
+            .with("covered", covered_count)
+            .with("uncovered", covered.len() - covered_count)
+            .with("frontier", frontier_count))
+    }
+
+    fn name(&self) -> String {
+        let overlay = match self.overlay {
+            CoverageOverlay::Annotate => "annotate",
+            CoverageOverlay::Covered => "covered",
+            CoverageOverlay::Uncovered => "uncovered",
+        };
+        match self.frontier {
+            Some(frontier) => format!("overlay coverage({overlay}, frontier {frontier})"),
+            None => format!("overlay coverage({overlay})"),
+        }
+    }
+}
+
+/// Report weakly connected components without changing the graph,
+/// the `top` largest ones are logged with their size and representative node
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ComponentsPass {
+    top: usize,
+}
+
+impl ComponentsPass {
+    #[must_use]
+    pub fn new(top: usize) -> Self {
+        Self { top }
+    }
+}
+
+impl Default for ComponentsPass {
+    fn default() -> Self {
+        Self::new(10)
+    }
+}
+
+impl Pass for ComponentsPass {
+    fn run_pass(&self, graph: &mut CallGraph) -> Result<PassReport, PassError> {
+        let components = components(graph);
+        for component in components.iter().take(self.top) {
+            info!("Component of {} nodes around \"{}\"", component.size, component.representative);
+        }
+        Ok(PassReport::default()
+            .with("components", components.len())
+            .with_max("largest component", components.first().map_or(0, |component| component.size))
+            .with("single nodes", components.iter().filter(|component| component.size == 1).count()))
+    }
+
+    fn name(&self) -> String {
+        "components".to_string()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_regex_list() {
+        let list = RegexList::new(
+            ["^spin_(?!lock)", "^mutex_", "_lock$"].map(|p| Regex::new(p).unwrap()).to_vec()
+        );
+        assert_eq!((list.in_set.as_slice(), list.fallback.as_slice()), ([1, 2].as_slice(), [0].as_slice()));
+        assert_eq!(list.matches("spin_unlock").unwrap(), [0]);
+        assert_eq!(list.matches("mutex_lock").unwrap(), [1, 2]);
+        assert_eq!(list.matches("spin_lock").unwrap(), [2]);
+        assert!(!list.is_match("schedule").unwrap());
+    }
+
+    #[test]
+    fn test_match_cache() {
+        let cache = Arc::new(MatchCache::new());
+        let mut graph = CallGraph::new();
+        let names = ["__foo", "bar", "__baz"].map(|name| graph.add_node(name.into()));
+        graph.add_edge(names[1], names[0], EdgeKind::Direct.into());
+        let pass = RemoveNodePass::new_from_str("^__").unwrap().with_match_cache(cache.clone());
+        pass.run_pass(&mut graph.clone()).unwrap();
+        assert_eq!(cache.len(), 3);
+        let known = cache.get_all(pass.terminate_funcs.id, ["__foo", "bar", "qux"].into_iter());
+        assert_eq!(known, [Some(true), Some(false), None]);
+
+        let same = ReparentGraphPass::new_from_str("^__").unwrap().with_match_cache(cache.clone());
+        assert_eq!(same.reparent_rules.id, pass.terminate_funcs.id);
+        same.run_pass(&mut graph).unwrap();
+        assert_eq!(cache.len(), 3);
+        assert_eq!(graph.node_count(), 3);
+    }
+
     #[test]
     fn test_remove_nodes() {
-        let mut graph: Graph<String, ()> = Graph::new();
-        graph.add_node("aba".to_string());
-        graph.add_node("abc".to_string());
-        graph.add_node("123".to_string());
-        graph.add_node("xy1".to_string());
+        let mut graph = CallGraph::new();
+        let aba = graph.add_node("aba".into());
+        let abc = graph.add_node("abc".into());
+        let num = graph.add_node("123".into());
+        let xy1 = graph.add_node("xy1".into());
+        graph.add_node("zz".into());
+        graph.add_edge(abc, xy1, EdgeKind::Direct.into());
+        graph.add_edge(aba, abc, EdgeKind::Direct.into());
+        graph.add_edge(xy1, num, EdgeKind::Direct.into());
+        graph.add_edge(xy1, abc, EdgeKind::Indirect.into());
 
-        let pass = RemoveNodePass::new_from_str("^\\d+$ (\\w).\\1");
-        pass.run_pass(&mut graph);
+        let pass = RemoveNodePass::new_from_str("^\\d+$ (\\w).\\1").unwrap();
+        let report = pass.run_pass(&mut graph).unwrap();
+        assert_eq!((report.nodes_removed, report.edges_removed), (2, 2));
 
         assert_eq!(
-            graph.node_weights().collect::<HashSet<_>>(),
-            ["abc".to_string(), "xy1".to_string()].iter().collect::<HashSet<_>>()
+            graph.node_weights().map(|node| node.name.as_ref()).collect::<HashSet<_>>(),
+            HashSet::from(["abc", "xy1", "zz"])
         );
+        let edges = graph.raw_edges()
+            .iter()
+            .map(|e| (graph[e.source()].name.as_ref(), graph[e.target()].name.as_ref(), e.weight.kind))
+            .collect::<HashSet<_>>();
+        assert_eq!(edges, HashSet::from([("abc", "xy1", EdgeKind::Direct), ("xy1", "abc", EdgeKind::Indirect)]));
+    }
+
+    #[test]
+    fn test_cut_deg_distinct() {
+        let mut graph = CallGraph::new();
+        let [main, helper, memcpy] = ["main", "helper", "memcpy"].map(|name| graph.add_node(name.into()));
+        for _ in 0..3 {
+            graph.add_edge(main, memcpy, EdgeKind::Direct.into());
+        }
+        graph.add_edge(helper, memcpy, EdgeKind::Direct.into());
+
+        let mut edges = graph.clone();
+        CutDegPass::new(Some(2), None).run_pass(&mut edges).unwrap();
+        assert_eq!(edges.node_count(), 2);
+
+        let pass = CutDegPass::new(Some(2), Some(1)).with_distinct_neighbors();
+        pass.run_pass(&mut graph).unwrap();
+        assert_eq!(graph.node_count(), 3);
+        assert_eq!(Pass::<Node, EdgeKind>::name(&pass), "degree filtering(incoming < 3, outgoing < 2, distinct neighbors)");
+    }
+
+    #[test]
+    fn test_remove_nodes_errors() {
+        let errors = RemoveNodePass::new_from_str("^main$\n(a b[\nok (c\n").err().unwrap();
+        assert_eq!(errors.iter().map(|e| e.line_number).collect::<Vec<_>>(), [2, 2, 3]);
+        assert!(errors.iter().all(|e| matches!(e.kind, RuleErrorKind::Regex(_))));
+        assert_eq!(errors[2].line, "ok (c");
+
+        let mut graph = CallGraph::new();
+        graph.add_node("ok".into());
+        graph.add_node("main".into());
+        let (pass, errors) = RemoveNodePass::new_lenient(&mut "^ok$ (\n".lines(), false);
+        assert_eq!(errors.len(), 1);
+        pass.run_pass(&mut graph).unwrap();
+        assert_eq!(graph.node_count(), 1);
+    }
+
+    #[test]
+    fn test_removal_keeps_order() {
+        let mut graph = CallGraph::new();
+        let v = ["a", "x", "b", "c", "y", "d"].map(|name| graph.add_node(name.into()));
+        for pair in v.windows(2) {
+            graph.add_edge(pair[0], pair[1], EdgeKind::Direct.into());
+        }
+        graph.add_edge(v[0], v[5], EdgeKind::Direct.into());
+        graph.add_edge(v[2], v[3], EdgeKind::Direct.into());
+
+        RemoveNodePass::new_from_str("^x$ ^y$").unwrap().run_pass(&mut graph).unwrap();
+        let names = |g: &CallGraph| g.node_weights().map(|n| n.name.to_string()).collect::<Vec<_>>();
+        assert_eq!(names(&graph), ["a", "b", "c", "d"]);
+        let edges = |g: &CallGraph| g.raw_edges()
+            .iter()
+            .map(|e| (g[e.source()].name.to_string(), g[e.target()].name.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(edges(&graph), [("b", "c"), ("a", "d"), ("b", "c")].map(|(a, b)| (a.to_string(), b.to_string())));
+
+        CutDegPass::new(None, Some(1)).run_pass(&mut graph).unwrap();
+        assert_eq!(names(&graph), ["a", "c", "d"]);
+    }
+
+    #[test]
+    fn test_external_node() {
+        let mut graph = CallGraph::new();
+        let [ext, main, handler, puts] = ["Node0x1", "main", "handler", "puts"].map(|name| graph.add_node(name.into()));
+        graph[ext].attrs.insert("label".to_string(), "{external node}".to_string());
+        graph.add_edge(ext, main, EdgeKind::Direct.into());
+        graph.add_edge(ext, handler, EdgeKind::Direct.into());
+        graph.add_edge(main, handler, EdgeKind::Direct.into());
+        graph.add_edge(puts, ext, EdgeKind::Direct.into());
+
+        let mut kept = graph.clone();
+        let report = ExternalNodePass::new(ExternalNodePolicy::Keep).run_pass(&mut kept).unwrap();
+        assert_eq!(report.custom["external nodes"], 1);
+        assert_eq!(kept.edge_count(), 4);
+
+        let mut dropped = graph.clone();
+        ExternalNodePass::default().run_pass(&mut dropped).unwrap();
+        assert_eq!(dropped.node_count(), 3);
+        assert_eq!(dropped.edge_count(), 1);
+        assert!(dropped.node_weights().all(|n| !n.attrs.contains_key("address_taken")));
+
+        let report = ExternalNodePass::new("annotate".parse().unwrap()).run_pass(&mut graph).unwrap();
+        assert_eq!(report.custom["annotated"], 2);
+        let taken = graph.node_weights()
+            .filter(|n| n.attrs.get("address_taken").is_some_and(|v| v == "true"))
+            .map(|n| &*n.name)
+            .collect::<Vec<_>>();
+        assert_eq!(taken, ["main", "handler"]);
+        assert_eq!(graph.edge_count(), 1);
+        assert!("remove".parse::<ExternalNodePolicy>().is_err());
+    }
+
+    #[test]
+    fn test_overlay_coverage() {
+        let mut graph = CallGraph::new();
+        let [main, parse, error, print, unused] = ["main", "parse", "error", "print", "unused"]
+            .map(|name| graph.add_node(name.into()));
+        for (from, to) in [(main, parse), (parse, error), (error, print), (unused, main)] {
+            graph.add_edge(from, to, EdgeKind::Direct.into());
+        }
+        let coverage = "FNDA:1,main\nFNDA:3,parse\nFNDA:0,error\n";
+
+        let mut annotated = graph.clone();
+        let pass = OverlayCoveragePass::new_from_str(coverage, CoverageOverlay::Annotate, Some(1));
+        let report = pass.run_pass(&mut annotated).unwrap();
+        assert_eq!((report.custom["covered"], report.custom["uncovered"], report.custom["frontier"]), (2, 3, 1));
+        assert_eq!(annotated[parse].attrs["covered"], "true");
+        assert_eq!(annotated[unused].attrs["covered"], "false");
+        assert_eq!(annotated[error].attrs["coverage_frontier"], "1");
+        assert!(!annotated[print].attrs.contains_key("coverage_frontier"));
+
+        let pass = OverlayCoveragePass::new_from_str(coverage, "uncovered".parse().unwrap(), Some(2));
+        let report = pass.run_pass(&mut graph).unwrap();
+        assert_eq!(report.custom["frontier"], 2);
+        assert_eq!(graph.node_weights().map(|node| &*node.name).collect::<Vec<_>>(), ["error", "print", "unused"]);
+        assert_eq!(graph.edge_count(), 1);
+        assert!(graph.node_weights().all(|node| !node.attrs.contains_key("covered")));
+        assert!("partial".parse::<CoverageOverlay>().is_err());
+    }
+
+    #[test]
+    fn test_components_pass() {
+        let mut graph = CallGraph::new();
+        let [a, b, c, _] = ["a", "b", "c", "d"].map(|name| graph.add_node(name.into()));
+        graph.add_edge(a, b, EdgeKind::Direct.into());
+        graph.add_edge(c, b, EdgeKind::Direct.into());
+        let report = ComponentsPass::default().run_pass(&mut graph).unwrap();
+        assert_eq!(report.custom["components"], 2);
+        assert_eq!(report.custom["largest component"], 3);
+        assert_eq!(report.custom["single nodes"], 1);
+        assert_eq!(graph.node_count(), 4);
+
+        // Reports of separate inputs add up components, but not sizes
+        let mut total = report.clone();
+        total.merge(&report);
+        assert_eq!((total.custom["components"], total.custom["largest component"]), (4, 3));
     }
 
     #[test]
     fn test_unique_edges() {
-        let mut graph = Graph::new();
+        let mut graph = CallGraph::new();
         let v = [
-            graph.add_node("1".to_string()),
-            graph.add_node("2".to_string()),
-            graph.add_node("3".to_string())
+            graph.add_node("1".into()),
+            graph.add_node("2".into()),
+            graph.add_node("3".into())
         ];
         
         // 0 -> (1, 2)
@@ -474,41 +1389,45 @@ mod tests {
         adj_matrix[2][1] = 1;
         adj_matrix[2][2] = 1;
         
-        graph.add_edge(v[0], v[2], ());
-        graph.add_edge(v[0], v[2], ());
-        graph.add_edge(v[0], v[1], ());
-        graph.add_edge(v[0], v[2], ());
+        graph.add_edge(v[0], v[2], EdgeKind::Direct.into());
+        graph.add_edge(v[0], v[2], Edge::new(EdgeKind::Direct, 5));
+        graph.add_edge(v[0], v[1], EdgeKind::Direct.into());
+        graph.add_edge(v[0], v[2], EdgeKind::Direct.into());
         
-        graph.add_edge(v[1], v[0], ());
-        graph.add_edge(v[1], v[2], ());
+        graph.add_edge(v[1], v[0], EdgeKind::Direct.into());
+        graph.add_edge(v[1], v[2], EdgeKind::Direct.into());
         
-        graph.add_edge(v[2], v[2], ());
-        graph.add_edge(v[2], v[1], ());
-        graph.add_edge(v[2], v[2], ());
-        graph.add_edge(v[2], v[1], ());
+        graph.add_edge(v[2], v[2], EdgeKind::Direct.into());
+        graph.add_edge(v[2], v[1], EdgeKind::Direct.into());
+        graph.add_edge(v[2], v[2], EdgeKind::Direct.into());
+        graph.add_edge(v[2], v[1], EdgeKind::Direct.into());
         
         let pass = UniqueEdgesPass::default();
-        pass.run_pass(&mut graph);
+        pass.run_pass(&mut graph).unwrap();
         for i in 0..3 {
             for j in 0..3 {
                 assert_eq!(adj_matrix[i][j], graph.edges_connecting(v[i], v[j]).count());
             }
         }
+        // Calls of removed duplicates are kept
+        let count = |from: NodeIndex, to: NodeIndex| graph.edges_connecting(from, to).next().unwrap().weight().count;
+        assert_eq!((count(v[0], v[2]), count(v[2], v[2]), count(v[1], v[0])), (7, 2, 1));
+        assert_eq!(pass.run_pass(&mut graph).unwrap(), PassReport::default());
     }
 
     #[test]
     fn test_reparent() {
-        let mut graph: Graph<String, ()> = Graph::new();
+        let mut graph = CallGraph::new();
         let v = [
-            graph.add_node("0".to_string()),
-            graph.add_node("1".to_string()),
-            graph.add_node("reparent1".to_string()),
-            graph.add_node("reparent2".to_string()),
-            graph.add_node("4".to_string()),
+            graph.add_node("0".into()),
+            graph.add_node("1".into()),
+            graph.add_node("reparent1".into()),
+            graph.add_node("reparent2".into()),
+            graph.add_node("4".into()),
         ];
         macro_rules! add_edge {
             ($v : expr, $u : expr) => {
-                graph.add_edge(v[$v], v[$u], ())
+                graph.add_edge(v[$v], v[$u], EdgeKind::Direct.into())
             };
         }
         add_edge!(0, 1);
@@ -520,15 +1439,15 @@ mod tests {
 
         let mut orig_graph = graph.clone();
 
-        let pass = ReparentGraphPass::new_from_str("reparent.*");
-        pass.run_pass(&mut graph);
+        let pass = ReparentGraphPass::new_from_str("reparent.*").unwrap();
+        pass.run_pass(&mut graph).unwrap();
 
         // From reparent1
-        orig_graph.add_edge(v[0], v[4], ());
-        orig_graph.add_edge(v[3], v[4], ());
+        orig_graph.add_edge(v[0], v[4], EdgeKind::Direct.into());
+        orig_graph.add_edge(v[3], v[4], EdgeKind::Direct.into());
         // From reparent2
-        orig_graph.add_edge(v[0], v[1], ());
-        orig_graph.add_edge(v[0], v[2], ());
+        orig_graph.add_edge(v[0], v[1], EdgeKind::Direct.into());
+        orig_graph.add_edge(v[0], v[2], EdgeKind::Direct.into());
 
         for node in v {
             let mut n1 = orig_graph.edges(node)
@@ -545,28 +1464,229 @@ mod tests {
 
     #[test]
     fn test_remove_edges() {
-        let mut graph = Graph::new();
+        let mut graph = CallGraph::new();
         let v = [
-            graph.add_node("a_1".to_string()),
-            graph.add_node("a_2".to_string()),
-            graph.add_node("b_2".to_string()),
-            graph.add_node("x".to_string()),
-            graph.add_node("y".to_string()),
+            graph.add_node("a_1".into()),
+            graph.add_node("a_2".into()),
+            graph.add_node("b_2".into()),
+            graph.add_node("x".into()),
+            graph.add_node("y".into()),
         ];
         for &i in &v {
-            graph.add_edge(v[0], i, ());
+            graph.add_edge(v[0], i, EdgeKind::Direct.into());
         }
         let mut pass = RemoveEdgesPass::default();
-        pass.add_rule_from_str("a_(.*) b.*");
-        pass.add_rule_from_str(r"a_(.*) a_(?!\1)");
-        pass.add_rule_from_str("^a.* [a-x]$");
+        pass.add_rule_from_str("a_(.*) b.*").unwrap();
+        pass.add_rule_from_str(r"a_(.*) a_(?!\1)").unwrap();
+        pass.add_rule_from_str("^a.* [a-x]$").unwrap();
 
-        pass.run_pass(&mut graph);
+        pass.run_pass(&mut graph).unwrap();
 
         // need a_1 -> a_1, a_1 -> x
         assert_eq!(
-            graph.neighbors(v[0]).map(|e| graph[e].as_ref()).collect::<HashSet<_>>(),
+            graph.neighbors(v[0]).map(|e| graph[e].name.as_ref()).collect::<HashSet<_>>(),
             HashSet::from(["a_1", "y"])
         );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_remove_edges_rules() {
+        let mut graph = CallGraph::new();
+        let v = ["main", "say hi", "log \"x\"", "exit"].map(|name| graph.add_node(name.into()));
+        for &i in &v[1..] {
+            graph.add_edge(v[0], i, EdgeKind::Direct.into());
+        }
+        let pass = RemoveEdgesPass::new_from_str("\n^main \"say hi$\"\n  \n  main\t\"log \\\"\" \n").unwrap();
+        pass.run_pass(&mut graph).unwrap();
+        assert_eq!(graph.neighbors(v[0]).collect::<Vec<_>>(), [v[3]]);
+
+        let err = RemoveEdgesPass::new_from_str("a b\n\njust_one\n").err().unwrap();
+        assert_eq!(err.line_number, 3);
+        assert!(matches!(err.kind, RuleErrorKind::MissingPattern));
+        assert_eq!(err.to_string(), "line 3: expected a source and a target pattern: \"just_one\"");
+        let err = RemoveEdgesPass::new_from_str("a \"b c").err().unwrap();
+        assert!(matches!(err.kind, RuleErrorKind::UnterminatedQuote));
+        let err = RemoveEdgesPass::new_from_str("a b c").err().unwrap();
+        assert!(matches!(err.kind, RuleErrorKind::ExtraPattern));
+        let err = RemoveEdgesPass::new_from_str("a (b").err().unwrap();
+        assert!(matches!(err.kind, RuleErrorKind::Regex(_)));
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        let errors = RegexEdgeGenPass::new_from_str("\"a\" -> b\n\nc -> d\n\"e\" d\n\"(f\" <- g").err().unwrap();
+        let lines = errors.iter().map(|e| e.line_number).collect::<Vec<_>>();
+        assert_eq!(lines, [3, 4, 5]);
+        assert!(matches!(errors[0].kind, RuleErrorKind::UnquotedPattern));
+        assert!(matches!(errors[1].kind, RuleErrorKind::MissingSeparator));
+        assert!(matches!(errors[2].kind, RuleErrorKind::Regex(_)));
+        let (pass, errors) = RegexEdgeGenPass::new_lenient("\"a\" -> b\n\"(f\" <- g", false);
+        assert_eq!((pass.rules.len(), errors.len()), (1, 1));
+        assert!(matches!(RegexEdgeGenPass::new().add_rule_from_line("\"a\" b"), Err(RuleErrorKind::MissingSeparator)));
+
+        let errors = ReparentGraphPass::new_from_str("^a$\n(b").err().unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line_number, 2);
+        assert_eq!(ReparentGraphPass::new_lenient("^a$\n(b").1.len(), 1);
+    }
+
+    #[test]
+    fn test_full_match() {
+        let names = ["sys_read", "sys_readahead", "__x64_sys_readlinkat", "main"];
+        let mut graph = CallGraph::new();
+        let v = names.map(|name| graph.add_node(name.into()));
+        for &i in &v[..3] {
+            graph.add_edge(v[3], i, EdgeKind::Direct.into());
+        }
+
+        let mut substring = graph.clone();
+        RemoveNodePass::new_from_str("sys_read").unwrap().run_pass(&mut substring).unwrap();
+        assert_eq!(substring.node_count(), 1);
+        let mut full = graph.clone();
+        RemoveNodePass::new_from_str_with("sys_read x64_.*", true).unwrap().run_pass(&mut full).unwrap();
+        assert_eq!(full.node_count(), 3);
+
+        let mut full = graph.clone();
+        RemoveEdgesPass::new_from_str_with("main sys_read|__x64_.*", true).unwrap()
+            .run_pass(&mut full).unwrap();
+        assert_eq!(full.neighbors(v[3]).collect::<Vec<_>>(), [v[1]]);
+
+        let mut full = graph.clone();
+        RegexEdgeGenPass::new_from_str_with("\"sys_read\" -> main", true).unwrap().run_pass(&mut full).unwrap();
+        assert_eq!(full.edges_connecting(v[0], v[3]).count(), 1);
+        assert_eq!(full.edge_count(), 4);
+    }
+
+    #[test]
+    fn test_keep_defined() {
+        let mut graph = CallGraph::new();
+        let main = graph.add_node("main".into());
+        let printf = graph.add_node("printf".into());
+        let helper = graph.add_node("helper".into());
+        graph.add_edge(main, printf, EdgeKind::Direct.into());
+        graph.add_edge(main, helper, EdgeKind::Direct.into());
+
+        let pass = KeepDefinedPass::new_from_str(
+            "0000000000001139 T main\n0000000000001120 t helper\n                 U printf\n"
+        );
+        pass.run_pass(&mut graph).unwrap();
+
+        assert_eq!(graph.node_count(), 2);
+        assert_eq!(graph.edge_count(), 1);
+        assert!(graph.node_weights().all(|node| &*node.name != "printf"));
+    }
+
+    #[test]
+    fn test_kallsyms_passes() {
+        let mut graph = CallGraph::new();
+        let read = graph.add_node("ext4_read".into());
+        let init = graph.add_node("init".into());
+        let missing = graph.add_node("missing".into());
+        graph.add_edge(read, init, EdgeKind::Direct.into());
+        graph.add_edge(read, missing, EdgeKind::Direct.into());
+
+        let kallsyms = "ffffffff81001000 t init\nffffffffc0a01000 t init\t[ext4]\n\
+                        ffffffffc0a02000 T ext4_read\t[ext4]\n";
+        AnnotateModulesPass::new_from_str(kallsyms).run_pass(&mut graph).unwrap();
+        KeepInKallsymsPass::new_from_str(kallsyms).run_pass(&mut graph).unwrap();
+
+        assert_eq!(graph.node_count(), 2);
+        assert_eq!(graph.edge_count(), 1);
+        let modules = graph.node_weights()
+            .map(|n| (n.name.as_ref(), n.attrs["module"].as_str()))
+            .collect::<HashSet<_>>();
+        assert_eq!(modules, HashSet::from([("ext4_read", "ext4"), ("init", "ext4,vmlinux")]));
+    }
+
+    #[test]
+    fn test_remove_edges_by_kind() {
+        let mut graph = CallGraph::new();
+        let v = [
+            graph.add_node("a".into()),
+            graph.add_node("b".into()),
+            graph.add_node("c".into()),
+        ];
+        graph.add_edge(v[0], v[1], EdgeKind::Direct.into());
+        graph.add_edge(v[0], v[1], EdgeKind::Indirect.into());
+        graph.add_edge(v[0], v[2], EdgeKind::Indirect.into());
+        graph.add_edge(v[1], v[2], EdgeKind::Synthetic.into());
+
+        RemoveEdgesPass::new_from_str("^a c$")
+            .unwrap()
+            .with_kind(EdgeKind::Indirect)
+            .run_pass(&mut graph).unwrap();
+        assert_eq!(graph.edge_count(), 3);
+
+        RemoveEdgesPass::default()
+            .with_kind(EdgeKind::Indirect)
+            .run_pass(&mut graph).unwrap();
+        assert_eq!(
+            graph.edge_weights().map(|edge| edge.kind).collect::<Vec<_>>(),
+            [EdgeKind::Direct, EdgeKind::Synthetic]
+        );
+    }
+
+    #[test]
+    fn test_regex_failure_is_reported() {
+        let mut graph = CallGraph::new();
+        graph.add_node("x".repeat(40).into());
+
+        let err = RemoveNodePass::new_from_str("(x+x+)+(?=y)").unwrap()
+            .run_pass(&mut graph)
+            .unwrap_err();
+        assert!(matches!(err, PassError::Regex { ref pattern, .. } if pattern == "(x+x+)+(?=y)"));
+        assert_eq!(graph.node_count(), 1);
+    }
+
+    #[test]
+    fn test_regex_edge_gen_order() {
+        let run = || {
+            let mut graph = CallGraph::new();
+            for name in ["main", "a", "b", "c", "d", "e"] {
+                graph.add_node(name.into());
+            }
+            RegexEdgeGenPass::new_from_str("\"^main$\" -> e c a d c b\n\"^main$\" <- d b d")
+                .unwrap()
+                .run_pass(&mut graph)
+                .unwrap();
+            graph.edge_references()
+                .map(|edge| (graph[edge.source()].name().to_string(), graph[edge.target()].name().to_string()))
+                .collect::<Vec<_>>()
+        };
+        let edges = run();
+        assert_eq!(edges.len(), 7);
+        assert_eq!(edges, run());
+        // Listed symbols are resolved in graph order
+        assert_eq!(edges[..5].iter().map(|(_, to)| to.as_str()).collect::<Vec<_>>(), ["a", "b", "c", "d", "e"]);
+    }
+
+    #[test]
+    fn test_string_nodes() {
+        let mut graph = Graph::<String, ()>::new();
+        let main = graph.add_node("main".to_string());
+        let helper = graph.add_node("helper".to_string());
+        let printf = graph.add_node("printf".to_string());
+        graph.add_edge(main, helper, ());
+        graph.add_edge(helper, printf, ());
+
+        RemoveNodePass::new_from_str("^printf$").unwrap().run_pass(&mut graph).unwrap();
+        ReparentGraphPass::new_from_str("^helper$").unwrap().run_pass(&mut graph).unwrap();
+        AnnotateModulesPass::new_from_str("ffffffff81001000 T main\n").run_pass(&mut graph).unwrap();
+        assert_eq!(graph.node_weights().collect::<Vec<_>>(), ["main", "helper"]);
+        assert_eq!(graph.edge_count(), 1);
+
+        RegexEdgeGenPass::new_from_str("\"^help\" <- main").unwrap().run_pass(&mut graph).unwrap();
+        assert_eq!(graph.edge_count(), 2);
+        let mut pass = RegexEdgeGenPass::new();
+        pass.add_rule_from_line("\"^main$\" -> helper").unwrap();
+        pass.run_pass(&mut graph).unwrap();
+        assert_eq!(graph.edge_count(), 3);
+        UniqueEdgesPass::default().run_pass(&mut graph).unwrap();
+        assert_eq!(graph.edge_count(), 1);
+        RemoveEdgesPass::default()
+            .with_kind(EdgeKind::Direct)
+            .run_pass(&mut graph)
+            .unwrap();
+        assert_eq!(graph.edge_count(), 0);
+    }
+}
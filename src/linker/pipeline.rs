@@ -0,0 +1,511 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use log::{error, info, warn};
+use tracing::{field, info_span};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use crate::linker::cancel::CancellationToken;
+use crate::linker::config::parse_config_file;
+use crate::linker::error::PassError;
+use crate::linker::graph_link::link_graphs_with;
+use crate::linker::node::{check_invariants, CallGraph};
+use crate::linker::pass::Pass;
+use crate::linker::profile;
+use crate::linker::report::{graph_size, PassReport};
+
+pub type PassList = Vec<Box<dyn Pass>>;
+
+/// Part of the pipeline a pass belongs to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Stage {
+    BeforeLink,
+    AfterLink,
+}
+
+/// Pass being run by the pipeline
+pub struct PassInfo<'a> {
+    pub name: &'a str,
+    pub stage: Stage,
+    /// Index of the graph the pass runs on
+    pub graph: usize,
+}
+
+/// Callbacks invoked while the pipeline runs, e.g. for progress reporting or snapshots.
+/// With the `parallel` feature callbacks for different graphs come from several threads.
+pub trait PipelineObserver: Send + Sync {
+    fn before_pass(&self, _pass: &PassInfo, _graph: &CallGraph) {}
+
+    fn after_pass(&self, _pass: &PassInfo, _graph: &CallGraph, _report: &PassReport) {}
+
+    fn after_link(&self, _graph: &CallGraph) {}
+}
+
+impl<T: PipelineObserver> PipelineObserver for Arc<T> {
+    fn before_pass(&self, pass: &PassInfo, graph: &CallGraph) {
+        self.as_ref().before_pass(pass, graph);
+    }
+
+    fn after_pass(&self, pass: &PassInfo, graph: &CallGraph, report: &PassReport) {
+        self.as_ref().after_pass(pass, graph, report);
+    }
+
+    fn after_link(&self, graph: &CallGraph) {
+        self.as_ref().after_link(graph);
+    }
+}
+
+/// Passes to run on every graph before linking, optional link step and passes
+/// to run on the linked graph.
+///
+/// ```
+/// use inv_call_extract::linker::node::CallGraph;
+/// use inv_call_extract::linker::pass::{ReverseGraphPass, UniqueEdgesPass};
+/// use inv_call_extract::linker::pipeline::Pipeline;
+///
+/// let pipeline = Pipeline::new()
+///     .add(Box::new(UniqueEdgesPass::default()))
+///     .link()
+///     .add(Box::new(ReverseGraphPass::default()));
+/// let mut graphs = vec![CallGraph::new(), CallGraph::new()];
+/// pipeline.run(&mut graphs).unwrap();
+/// assert_eq!(graphs.len(), 1);
+/// ```
+#[derive(Default)]
+pub struct Pipeline {
+    before_link: PassList,
+    linked: bool,
+    after_link: PassList,
+    observers: Vec<Box<dyn PipelineObserver>>,
+    cancel: CancellationToken,
+    unique_edges: bool,
+    paranoid: bool,
+}
+
+impl Pipeline {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a pass, it runs after the link step if one was already added
+    #[must_use]
+    #[allow(clippy::should_implement_trait)]
+    pub fn add(mut self, pass: Box<dyn Pass>) -> Self {
+        if self.linked {
+            self.after_link.push(pass);
+        } else {
+            self.before_link.push(pass);
+        }
+        self
+    }
+
+    /// Link all graphs into one at this point
+    #[must_use]
+    pub fn link(mut self) -> Self {
+        self.linked = true;
+        self
+    }
+
+    /// Notify `observer` about every pass run
+    #[must_use]
+    pub fn observe(mut self, observer: Box<dyn PipelineObserver>) -> Self {
+        self.observers.push(observer);
+        self
+    }
+
+    /// Stop running passes once `cancel` is triggered
+    #[must_use]
+    pub fn cancel_on(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Link only the first edge between two nodes, for configs starting with `unique_edges`
+    #[must_use]
+    pub fn unique_edges(mut self) -> Self {
+        self.unique_edges = true;
+        self
+    }
+
+    #[must_use]
+    pub fn has_unique_edges(&self) -> bool {
+        self.unique_edges
+    }
+
+    /// Check graphs after every pass and after linking, failing with [`PassError::Invariant`]
+    /// naming the step that broke them. For developing new passes, checks take a pass over the graph
+    #[must_use]
+    pub fn paranoid(mut self) -> Self {
+        self.paranoid = true;
+        self
+    }
+
+    #[must_use]
+    pub fn is_linked(&self) -> bool {
+        self.linked
+    }
+
+    #[must_use]
+    pub fn before_link(&self) -> &[Box<dyn Pass>] {
+        &self.before_link
+    }
+
+    #[must_use]
+    pub fn after_link(&self) -> &[Box<dyn Pass>] {
+        &self.after_link
+    }
+
+    /// Run all passes. If the pipeline links graphs, they are replaced with the linked graph.
+    /// Returns names of passes in run order with their reports summed over all graphs.
+    /// A cancelled run fails with [`PassError::Cancelled`], reports of finished passes
+    /// are logged and passed to observers.
+    pub fn run(&self, graphs: &mut Vec<CallGraph>) -> Result<Vec<(String, PassReport)>, PassError> {
+        let mut reports = self.run_before_link(graphs)?;
+        if self.linked {
+            self.run_link(graphs)?;
+        }
+        reports.extend(self.run_after_link(graphs)?);
+        Ok(reports)
+    }
+
+    /// Run only the passes before the link step
+    pub fn run_before_link(&self, graphs: &mut [CallGraph]) -> Result<Vec<(String, PassReport)>, PassError> {
+        let mut reports = vec![];
+        for pass in &self.before_link {
+            info!("Running pass before link: {}", pass.name());
+            let _phase = profile::phase("pass", &pass.name());
+            reports.push((pass.name(), self.run_pass(pass.as_ref(), Stage::BeforeLink, graphs)?));
+        }
+        Ok(reports)
+    }
+
+    /// Replace graphs with the linked graph, even if the pipeline has no link step
+    pub fn run_link(&self, graphs: &mut Vec<CallGraph>) -> Result<(), PassError> {
+        self.cancel.check()?;
+        let phase = profile::phase("link", "");
+        let span = info_span!("link", graphs = graphs.len(), nodes = field::Empty, edges = field::Empty)
+            .entered();
+        *graphs = vec![link_graphs_with(std::mem::take(graphs), self.unique_edges)?];
+        if self.paranoid {
+            check_invariants(&graphs[0]).map_err(|message| PassError::Invariant { pass: "link".to_string(), message })?;
+        }
+        span.record("nodes", graphs[0].node_count());
+        span.record("edges", graphs[0].edge_count());
+        drop(span);
+        drop(phase);
+        info!("Linked graphs");
+        self.observers.iter().for_each(|o| o.after_link(&graphs[0]));
+        Ok(())
+    }
+
+    /// Run only the passes after the link step
+    pub fn run_after_link(&self, graphs: &mut [CallGraph]) -> Result<Vec<(String, PassReport)>, PassError> {
+        let mut reports = vec![];
+        for pass in &self.after_link {
+            info!("Running pass after link: {}", pass.name());
+            let _phase = profile::phase("pass", &pass.name());
+            reports.push((pass.name(), self.run_pass(pass.as_ref(), Stage::AfterLink, graphs)?));
+        }
+        Ok(reports)
+    }
+
+    /// Run `pass` on every graph, on all cores with the `parallel` feature
+    fn run_pass(
+        &self,
+        pass: &dyn Pass,
+        stage: Stage,
+        graphs: &mut [CallGraph]
+    ) -> Result<PassReport, PassError> {
+        let name = pass.name();
+        #[cfg(feature = "parallel")]
+        let reports = graphs.par_iter_mut()
+            .enumerate()
+            .map(|(i, graph)| self.run_on_graph(pass, &PassInfo { name: &name, stage, graph: i }, graph))
+            .collect::<Result<Vec<_>, _>>()?;
+        #[cfg(not(feature = "parallel"))]
+        let reports = graphs.iter_mut()
+            .enumerate()
+            .map(|(i, graph)| self.run_on_graph(pass, &PassInfo { name: &name, stage, graph: i }, graph))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut total = PassReport::default();
+        reports.iter().for_each(|report| total.merge(report));
+        info!("Pass {name} finished: {total}");
+        Ok(total)
+    }
+
+    fn run_on_graph(
+        &self,
+        pass: &dyn Pass,
+        info: &PassInfo,
+        graph: &mut CallGraph
+    ) -> Result<PassReport, PassError> {
+        let name = info.name;
+        let span = info_span!(
+            "pass",
+            pass = %name,
+            stage = ?info.stage,
+            graph = info.graph,
+            nodes = graph.node_count(),
+            edges = graph.edge_count(),
+            nodes_removed = field::Empty,
+            nodes_added = field::Empty,
+            edges_removed = field::Empty,
+            edges_added = field::Empty
+        ).entered();
+        self.observers.iter().for_each(|o| o.before_pass(info, graph));
+        let before = graph_size(graph);
+        let report = match pass.run_pass_cancellable(graph, &self.cancel) {
+            Ok(report) => report,
+            Err(PassError::Cancelled) => {
+                warn!("Pass {name} cancelled");
+                return Err(PassError::Cancelled);
+            }
+            Err(e) => {
+                error!("Pass \"{name}\" failed: {e}");
+                return Err(e);
+            }
+        };
+        if self.paranoid {
+            check_pass(before, graph, &report).map_err(|message| {
+                error!("Pass \"{name}\" broke graph {}: {message}", info.graph);
+                PassError::Invariant { pass: name.to_string(), message }
+            })?;
+            // Filters may remove everything on purpose, e.g. a pattern matching all nodes
+            if before.0 > 0 && graph.node_count() == 0 {
+                warn!("Pass \"{name}\" left graph {} empty", info.graph);
+            }
+        }
+        span.record("nodes_removed", report.nodes_removed);
+        span.record("nodes_added", report.nodes_added);
+        span.record("edges_removed", report.edges_removed);
+        span.record("edges_added", report.edges_added);
+        self.observers.iter().for_each(|o| o.after_pass(info, graph, &report));
+        Ok(report)
+    }
+}
+
+/// Invariants of a graph after a pass, which had `before` nodes and edges:
+/// [`check_invariants`] and a report matching the size change
+fn check_pass(before: (usize, usize), graph: &CallGraph, report: &PassReport) -> Result<(), String> {
+    check_invariants(graph)?;
+    let after = graph_size(graph);
+    if (before.0 + report.nodes_added).checked_sub(report.nodes_removed) != Some(after.0)
+        || (before.1 + report.edges_added).checked_sub(report.edges_removed) != Some(after.1) {
+        return Err(format!(
+            "report {report} doesn't match the change from {} nodes and {} edges to {} and {}",
+            before.0, before.1, after.0, after.1
+        ));
+    }
+    Ok(())
+}
+
+/// Run passes from the config file on every graph.
+/// If the config links graphs, they are replaced with one graph saved to `linked_output`.
+pub fn run_passes(
+    config: &Path,
+    objects: &mut Vec<(PathBuf, CallGraph)>,
+    linked_output: &Path
+) -> io::Result<Vec<(String, PassReport)>> {
+    run_pipeline(&parse_config_file(config)?, objects, linked_output)
+}
+
+/// Same as [`run_passes`] with an already built pipeline.
+/// If a pass fails, `objects` keep the graphs as the passes run so far left them.
+pub fn run_pipeline(
+    pipeline: &Pipeline,
+    objects: &mut Vec<(PathBuf, CallGraph)>,
+    linked_output: &Path
+) -> io::Result<Vec<(String, PassReport)>> {
+    let (mut paths, mut graphs): (Vec<_>, Vec<_>) = objects.drain(..).unzip();
+    let result = pipeline.run(&mut graphs);
+    // A pass failing after the link step leaves the linked graph
+    if pipeline.is_linked() && (result.is_ok() || graphs.len() != paths.len()) {
+        paths = vec![linked_output.to_path_buf()];
+    }
+    *objects = paths.into_iter().zip(graphs).collect();
+    Ok(result?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::linker::edge::EdgeKind;
+    use std::sync::Mutex;
+    use crate::linker::pass::{RemoveNodePass, ReverseGraphPass};
+    use crate::linker::registry::{PassArgs, PassRegistry};
+    use super::*;
+
+    #[test]
+    fn test_pipeline_stages() {
+        let mut first = CallGraph::new();
+        let a = first.add_node("a".into());
+        let b = first.add_node("b".into());
+        first.add_edge(a, b, EdgeKind::Direct.into());
+        let mut second = CallGraph::new();
+        let b = second.add_node("b".into());
+        let c = second.add_node("c".into());
+        second.add_edge(b, c, EdgeKind::Direct.into());
+
+        let pipeline = Pipeline::new()
+            .add(Box::new(RemoveNodePass::new_from_str("^a$").unwrap()))
+            .link()
+            .add(Box::new(ReverseGraphPass::default()));
+        assert_eq!(pipeline.before_link().len(), 1);
+        assert_eq!(pipeline.after_link().len(), 1);
+
+        let mut graphs = vec![first, second];
+        let reports = pipeline.run(&mut graphs).unwrap();
+        assert_eq!(reports[0].0, "node terminator");
+        assert_eq!((reports[0].1.nodes_removed, reports[0].1.edges_removed), (1, 1));
+        assert_eq!(reports[1].1, PassReport::default());
+        assert_eq!(graphs.len(), 1);
+        let linked = &graphs[0];
+        assert_eq!(linked.node_count(), 2);
+        let edge = &linked.raw_edges()[0];
+        assert_eq!(
+            (linked[edge.source()].name.as_ref(), linked[edge.target()].name.as_ref()),
+            ("c", "b")
+        );
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        events: Mutex<Vec<String>>,
+    }
+
+    impl PipelineObserver for Recorder {
+        fn before_pass(&self, pass: &PassInfo, _: &CallGraph) {
+            self.events.lock().unwrap().push(format!("before {} {}", pass.name, pass.graph));
+        }
+
+        fn after_pass(&self, pass: &PassInfo, graph: &CallGraph, _: &PassReport) {
+            self.events.lock().unwrap().push(format!("after {} {}", pass.name, graph.node_count()));
+        }
+
+        fn after_link(&self, graph: &CallGraph) {
+            self.events.lock().unwrap().push(format!("link {}", graph.node_count()));
+        }
+    }
+
+    #[test]
+    fn test_observer() {
+        let recorder = Arc::new(Recorder::default());
+        let pipeline = Pipeline::new()
+            .observe(Box::new(recorder.clone()))
+            .add(Box::new(RemoveNodePass::new_from_str("^a$").unwrap()))
+            .link();
+        let mut graphs = vec![CallGraph::new(), CallGraph::new()];
+        graphs[0].add_node("a".into());
+        graphs[1].add_node("b".into());
+        pipeline.run(&mut graphs).unwrap();
+
+        let mut events = recorder.events.lock().unwrap().clone();
+        assert_eq!(events.pop().unwrap(), "link 1");
+        // graphs may be processed in any order, each one before and then after
+        let before_0 = events.iter().position(|e| e == "before node terminator 0").unwrap();
+        let before_1 = events.iter().position(|e| e == "before node terminator 1").unwrap();
+        assert!(events[before_0 + 1..].contains(&"after node terminator 0".to_string()));
+        assert!(events[before_1 + 1..].contains(&"after node terminator 1".to_string()));
+        assert_eq!(events.len(), 4);
+    }
+
+    #[test]
+    fn test_cancel() {
+        let cancel = CancellationToken::new();
+        let pipeline = Pipeline::new()
+            .cancel_on(cancel.clone())
+            .add(Box::new(RemoveNodePass::new_from_str("^a$").unwrap()));
+        let mut graphs = vec![CallGraph::new()];
+        graphs[0].add_node("a".into());
+        cancel.cancel();
+
+        assert!(matches!(pipeline.run(&mut graphs), Err(PassError::Cancelled)));
+        assert_eq!(graphs[0].node_count(), 1);
+    }
+
+    /// Pass adding a second node with the name of the first one, reporting nothing
+    struct DuplicateFirst;
+
+    impl Pass for DuplicateFirst {
+        fn run_pass(&self, graph: &mut CallGraph) -> Result<PassReport, PassError> {
+            let node = graph[petgraph::graph::NodeIndex::new(0)].clone();
+            graph.add_node(node);
+            Ok(PassReport::default())
+        }
+
+        fn name(&self) -> String {
+            "duplicate first".to_string()
+        }
+    }
+
+    #[test]
+    fn test_paranoid() {
+        let graph = || {
+            let mut graph = CallGraph::new();
+            let a = graph.add_node("a".into());
+            let b = graph.add_node("b".into());
+            graph.add_edge(a, b, EdgeKind::Direct.into());
+            graph
+        };
+        let pipeline = Pipeline::new()
+            .paranoid()
+            .add(Box::new(RemoveNodePass::new_from_str("^a$").unwrap()))
+            .link()
+            .add(Box::new(ReverseGraphPass::default()));
+        pipeline.run(&mut vec![graph(), graph()]).unwrap();
+
+        let pipeline = Pipeline::new().paranoid().add(Box::new(DuplicateFirst));
+        let err = pipeline.run(&mut vec![graph()]).unwrap_err();
+        assert!(matches!(err, PassError::Invariant { ref pass, .. } if pass == "duplicate first"));
+        assert_eq!(err.to_string(), r#"invariant broken by "duplicate first": several nodes are named "a""#);
+
+        // Emptying a graph is only logged
+        let pipeline = Pipeline::new().paranoid().add(Box::new(RemoveNodePass::new_from_str(".").unwrap()));
+        let mut graphs = vec![graph()];
+        pipeline.run(&mut graphs).unwrap();
+        assert_eq!(graphs[0].node_count(), 0);
+        assert!(Pipeline::new().add(Box::new(DuplicateFirst)).run(&mut vec![graph()]).is_ok());
+    }
+
+    #[test]
+    fn test_failing_pass_keeps_objects() {
+        let objects = || ["a.dot", "b.dot"]
+            .map(|path| {
+                let mut graph = CallGraph::new();
+                graph.add_node(path.into());
+                (PathBuf::from(path), graph)
+            })
+            .to_vec();
+        let pipeline = Pipeline::new().paranoid().add(Box::new(DuplicateFirst));
+        let mut inputs = objects();
+        assert!(run_pipeline(&pipeline, &mut inputs, Path::new("out.dot")).is_err());
+        assert_eq!(inputs.iter().map(|(path, _)| path.to_str().unwrap()).collect::<Vec<_>>(), ["a.dot", "b.dot"]);
+
+        let pipeline = Pipeline::new().paranoid().link().add(Box::new(DuplicateFirst));
+        let mut inputs = objects();
+        assert!(run_pipeline(&pipeline, &mut inputs, Path::new("out.dot")).is_err());
+        assert_eq!(inputs.len(), 1);
+        assert_eq!(inputs[0].0, Path::new("out.dot"));
+        assert_eq!(inputs[0].1.node_count(), 3);
+    }
+
+    #[test]
+    fn test_one_sided_cut_deg() {
+        let mut graph = CallGraph::new();
+        let [a, b, c] = ["a", "b", "c"].map(|name| graph.add_node(name.into()));
+        graph.add_edge(a, b, EdgeKind::Direct.into());
+        graph.add_edge(a, c, EdgeKind::Direct.into());
+        // Same as config lines `cut_deg +10` and `cut_deg -1`
+        let registry = PassRegistry::builtin();
+        let pass = |arg| registry.build("cut_deg", &PassArgs { args: &[arg], line_number: 0, match_cache: None });
+        let pipeline = Pipeline::new().add(pass("+10").unwrap()).add(pass("-1").unwrap());
+        let reports = pipeline.run(&mut vec![graph]).unwrap();
+        let names = reports.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, [
+            "degree filtering(incoming < 11, outgoing unlimited)",
+            "degree filtering(incoming unlimited, outgoing < 2)",
+        ]);
+        assert_eq!(reports[1].1.nodes_removed, 1);
+    }
+}
@@ -0,0 +1,80 @@
+use std::io;
+use std::path::Path;
+use libloading::{Library, Symbol};
+use log::{error, info};
+use crate::linker::registry::PassRegistry;
+
+/// Version of the plugin interface, bumped whenever `Pass`, `PassReport`, `PassRegistry` or `PassArgs` change.
+/// Plugins are called through the Rust ABI, so they must also be built with the same toolchain.
+pub const PLUGIN_ABI_VERSION: u32 = 5;
+
+const ABI_VERSION_SYMBOL: &[u8] = b"dot_linker_plugin_abi_version";
+const REGISTER_SYMBOL: &[u8] = b"dot_linker_plugin_register";
+
+/// Export plugin entry points from a `cdylib` crate.
+/// `$register` is a `fn(&mut PassRegistry)` adding the plugin passes.
+///
+/// ```ignore
+/// fn register(registry: &mut PassRegistry) {
+///     registry.register("my_pass", |args| Ok(Box::new(MyPass::new(args))));
+/// }
+/// inv_call_extract::declare_plugin!(register);
+/// ```
+#[macro_export]
+macro_rules! declare_plugin {
+    ($register:path) => {
+        #[no_mangle]
+        pub extern "C" fn dot_linker_plugin_abi_version() -> u32 {
+            $crate::linker::plugin::PLUGIN_ABI_VERSION
+        }
+
+        #[no_mangle]
+        pub fn dot_linker_plugin_register(
+            registry: &mut $crate::linker::registry::PassRegistry
+        ) {
+            $register(registry);
+        }
+    };
+}
+
+/// Load a plugin built with [`declare_plugin`] and add its passes to `registry`.
+/// The library is never unloaded: registered constructors point into it.
+pub fn load_plugin(path: &Path, registry: &mut PassRegistry) -> io::Result<()> {
+    // SAFETY: plugins are trusted code, their ABI version is checked before registration
+    unsafe {
+        let library = Library::new(path).map_err(|e| {
+            error!("Failed to load plugin {}: {e}", path.display());
+            io::Error::other(e)
+        })?;
+        let abi_version: Symbol<extern "C" fn() -> u32> = library
+            .get(ABI_VERSION_SYMBOL)
+            .map_err(io::Error::other)?;
+        let version = abi_version();
+        if version != PLUGIN_ABI_VERSION {
+            error!(
+                "Plugin {} is built for interface version {version}, expected {PLUGIN_ABI_VERSION}",
+                path.display()
+            );
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+        let register: Symbol<fn(&mut PassRegistry)> = library
+            .get(REGISTER_SYMBOL)
+            .map_err(io::Error::other)?;
+        register(registry);
+        std::mem::forget(library);
+    }
+    info!("Loaded plugin {}", path.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_plugin() {
+        let mut registry = PassRegistry::new();
+        assert!(load_plugin(Path::new("missing/libno_such_plugin.so"), &mut registry).is_err());
+        assert_eq!(registry.keywords().count(), 0);
+    }
+}
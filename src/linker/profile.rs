@@ -0,0 +1,241 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::io;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Counters of [`TrackingAllocator`], only updated while profiling is enabled
+static ALLOCATED: AtomicU64 = AtomicU64::new(0);
+static FREED: AtomicU64 = AtomicU64::new(0);
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+/// Bytes allocated minus bytes freed since profiling was enabled, can go below zero
+/// when memory allocated before is freed
+static CURRENT: AtomicI64 = AtomicI64::new(0);
+static PEAK: AtomicI64 = AtomicI64::new(0);
+
+static PHASES: Mutex<Vec<PhaseStats>> = Mutex::new(vec![]);
+
+/// System allocator counting allocations while profiling is enabled.
+/// Programs install it with `#[global_allocator]`, otherwise phases report no allocations.
+/// The command line tool does so with the `alloc-profile` feature.
+pub struct TrackingAllocator;
+
+#[allow(clippy::cast_possible_wrap)]
+fn record_alloc(size: usize) {
+    ALLOCATED.fetch_add(size as u64, Ordering::Relaxed);
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    let current = CURRENT.fetch_add(size as i64, Ordering::Relaxed) + size as i64;
+    PEAK.fetch_max(current, Ordering::Relaxed);
+}
+
+#[allow(clippy::cast_possible_wrap)]
+fn record_free(size: usize) {
+    FREED.fetch_add(size as u64, Ordering::Relaxed);
+    CURRENT.fetch_sub(size as i64, Ordering::Relaxed);
+}
+
+unsafe impl GlobalAlloc for TrackingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() && is_enabled() {
+            record_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() && is_enabled() {
+            record_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        if is_enabled() {
+            record_free(layout.size());
+        }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() && is_enabled() {
+            record_free(layout.size());
+            record_alloc(new_size);
+        }
+        new_ptr
+    }
+}
+
+/// Wall time and allocations of one phase
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PhaseStats {
+    /// `parse`, `config`, `pass`, `link` or `write`
+    pub phase: &'static str,
+    /// Pass name for `pass` phases, empty otherwise
+    pub name: String,
+    pub wall: Duration,
+    /// Bytes allocated during the phase, reallocations count as a new allocation
+    pub allocated: u64,
+    pub freed: u64,
+    pub allocations: u64,
+    /// Highest number of bytes in use during the phase above the usage at its start
+    pub peak: u64,
+}
+
+/// Phase being measured, recorded when dropped
+pub struct Phase {
+    stats: Option<PhaseStats>,
+    start: Instant,
+    allocated: u64,
+    freed: u64,
+    allocations: u64,
+    current: i64,
+}
+
+impl Drop for Phase {
+    fn drop(&mut self) {
+        let Some(mut stats) = self.stats.take() else {
+            return;
+        };
+        stats.wall = self.start.elapsed();
+        stats.allocated = ALLOCATED.load(Ordering::Relaxed) - self.allocated;
+        stats.freed = FREED.load(Ordering::Relaxed) - self.freed;
+        stats.allocations = ALLOCATIONS.load(Ordering::Relaxed) - self.allocations;
+        stats.peak = (PEAK.load(Ordering::Relaxed) - self.current).try_into().unwrap_or(0);
+        PHASES.lock().unwrap_or_else(PoisonError::into_inner).push(stats);
+    }
+}
+
+/// Record phases from now on
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Stop recording phases and drop the ones not taken yet
+pub fn disable() {
+    ENABLED.store(false, Ordering::Relaxed);
+    take_phases();
+}
+
+/// Profiling enabled for as long as it lives, disabled again when dropped
+pub struct Session(());
+
+impl Session {
+    #[must_use]
+    pub fn start() -> Self {
+        enable();
+        Self(())
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        disable();
+    }
+}
+
+#[must_use]
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Start measuring a phase, nothing is recorded while profiling is disabled.
+/// Phases must not overlap, allocation counters are shared by all threads.
+#[must_use]
+pub fn phase(phase: &'static str, name: &str) -> Phase {
+    let current = CURRENT.load(Ordering::Relaxed);
+    if is_enabled() {
+        PEAK.store(current, Ordering::Relaxed);
+    }
+    Phase {
+        stats: is_enabled().then(|| PhaseStats {
+            phase,
+            name: name.to_string(),
+            wall: Duration::ZERO,
+            allocated: 0,
+            freed: 0,
+            allocations: 0,
+            peak: 0,
+        }),
+        start: Instant::now(),
+        allocated: ALLOCATED.load(Ordering::Relaxed),
+        freed: FREED.load(Ordering::Relaxed),
+        allocations: ALLOCATIONS.load(Ordering::Relaxed),
+        current,
+    }
+}
+
+/// Remove and return all recorded phases in the order they finished
+pub fn take_phases() -> Vec<PhaseStats> {
+    std::mem::take(&mut *PHASES.lock().unwrap_or_else(PoisonError::into_inner))
+}
+
+pub(crate) fn json_string(s: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if u32::from(c) < 0x20 => escaped.push_str(&format!("\\u{:04x}", u32::from(c))),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// Write phases as a JSON array of objects, times are in microseconds
+pub fn write_json(out: &mut impl Write, phases: &[PhaseStats]) -> io::Result<()> {
+    writeln!(out, "[")?;
+    for (i, stats) in phases.iter().enumerate() {
+        writeln!(
+            out,
+            "  {{\"phase\": {}, \"name\": {}, \"wall_us\": {}, \"allocated\": {}, \"freed\": {}, \
+                \"allocations\": {}, \"peak\": {}}}{}",
+            json_string(stats.phase),
+            json_string(&stats.name),
+            stats.wall.as_micros(),
+            stats.allocated,
+            stats.freed,
+            stats.allocations,
+            stats.peak,
+            if i + 1 < phases.len() { "," } else { "" }
+        )?;
+    }
+    writeln!(out, "]")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phases() {
+        drop(phase("pass", "test_phases disabled"));
+        enable();
+        drop(phase("pass", "test_phases \"quoted\""));
+        let phases = take_phases()
+            .into_iter()
+            .filter(|stats| stats.name.starts_with("test_phases"))
+            .collect::<Vec<_>>();
+        assert_eq!(phases.len(), 1);
+        assert_eq!(phases[0].phase, "pass");
+
+        let mut out = vec![];
+        write_json(&mut out, &phases).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("[\n  {\"phase\": \"pass\", \"name\": \"test_phases \\\"quoted\\\"\", \"wall_us\": "));
+        assert!(out.ends_with("}\n]\n"));
+
+        let session = Session::start();
+        drop(phase("pass", "test_phases session"));
+        drop(session);
+        assert!(!is_enabled());
+        assert!(!take_phases().iter().any(|stats| stats.name == "test_phases session"));
+    }
+}
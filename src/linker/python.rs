@@ -0,0 +1,249 @@
+//! Python module, built with `maturin build --features python`
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use petgraph::Direction;
+use pyo3::prelude::*;
+use crate::linker::cancel::CancellationToken;
+use crate::linker::config::{config_lines, ConfigLine};
+use crate::linker::conversion::{named_edges, save_dot};
+use crate::linker::graph_link::link_graphs_with;
+use crate::linker::import::{read_graphs, InputFormat, MultiGraph};
+use crate::linker::error::PassError;
+use crate::linker::intern;
+use crate::linker::node::CallGraph;
+use crate::linker::pass::Pass;
+use crate::linker::pipeline::Pipeline;
+use crate::linker::registry::{PassArgs, PassRegistry};
+use crate::linker::report::PassReport;
+
+/// Edge in the form accepted by `networkx.MultiDiGraph.add_edges_from`
+type EdgeTuple = (String, String, BTreeMap<&'static str, String>);
+
+/// Report as a dict of counters, pass specific counters included
+type ReportDict = BTreeMap<String, usize>;
+
+/// Resulting graphs and `(pass name, report)` pairs of a pipeline run
+type RunResult = (Vec<PyGraph>, Vec<(String, ReportDict)>);
+
+fn report_dict(report: PassReport) -> ReportDict {
+    let mut dict = report.custom;
+    dict.insert("nodes_removed".to_string(), report.nodes_removed);
+    dict.insert("nodes_added".to_string(), report.nodes_added);
+    dict.insert("edges_removed".to_string(), report.edges_removed);
+    dict.insert("edges_added".to_string(), report.edges_added);
+    dict
+}
+
+#[pyclass(name = "Graph", from_py_object)]
+#[derive(Clone, Default)]
+pub struct PyGraph(CallGraph);
+
+/// Names of a graph collected by Python are released unless other graphs use them
+impl Drop for PyGraph {
+    fn drop(&mut self) {
+        if self.0.node_count() > 0 {
+            self.0 = CallGraph::new();
+            intern::release_unused();
+        }
+    }
+}
+
+#[pymethods]
+impl PyGraph {
+    #[new]
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn node_count(&self) -> usize {
+        self.0.node_count()
+    }
+
+    fn edge_count(&self) -> usize {
+        self.0.edge_count()
+    }
+
+    /// `(name, attrs)` pairs, accepted by `networkx.DiGraph.add_nodes_from`
+    fn nodes(&self) -> Vec<(String, BTreeMap<String, String>)> {
+        self.0.node_weights()
+            .map(|node| (node.name.to_string(), node.attrs.clone()))
+            .collect()
+    }
+
+    /// `(from, to, {"kind": kind, "count": count})` triples
+    fn edges(&self) -> Vec<EdgeTuple> {
+        named_edges(&self.0)
+            .map(|(from, to, edge)| (
+                from.to_string(),
+                to.to_string(),
+                BTreeMap::from([("kind", edge.kind.to_string()), ("count", edge.count.to_string())])
+            ))
+            .collect()
+    }
+
+    /// Names of functions calling `name`
+    fn callers(&self, name: &str) -> Vec<String> {
+        self.neighbors(name, Direction::Incoming)
+    }
+
+    /// Names of functions called by `name`
+    fn callees(&self, name: &str) -> Vec<String> {
+        self.neighbors(name, Direction::Outgoing)
+    }
+
+    fn save_dot(&self, path: PathBuf) -> PyResult<()> {
+        Ok(save_dot(&path, &self.0)?)
+    }
+}
+
+impl PyGraph {
+    fn neighbors(&self, name: &str, direction: Direction) -> Vec<String> {
+        let mut names = self.0.node_indices()
+            .filter(|&idx| &*self.0[idx].name == name)
+            .flat_map(|idx| self.0.neighbors_directed(idx, direction))
+            .map(|idx| self.0[idx].name.to_string())
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+}
+
+/// Pass built once and shared by the Python objects holding it
+struct SharedPass(Arc<dyn Pass>);
+
+impl Pass for SharedPass {
+    fn run_pass(&self, graph: &mut CallGraph) -> Result<PassReport, PassError> {
+        self.0.run_pass(graph)
+    }
+
+    fn run_pass_cancellable(&self, graph: &mut CallGraph, cancel: &CancellationToken) -> Result<PassReport, PassError> {
+        self.0.run_pass_cancellable(graph, cancel)
+    }
+
+    fn name(&self) -> String {
+        self.0.name()
+    }
+}
+
+/// Pass created from a config keyword and its arguments, e.g. `Pass("remove_nodes", "file")`
+#[pyclass(name = "Pass", from_py_object)]
+#[derive(Clone)]
+pub struct PyPass {
+    keyword: String,
+    args: Vec<String>,
+    pass: Arc<dyn Pass>,
+}
+
+#[pymethods]
+impl PyPass {
+    #[new]
+    #[pyo3(signature = (keyword, *args))]
+    fn new(keyword: String, args: Vec<String>) -> PyResult<Self> {
+        Ok(Self::build(keyword, args, 0)?)
+    }
+
+    /// Run the pass on `graph` in place, returns the report
+    fn run(&self, graph: &mut PyGraph) -> PyResult<ReportDict> {
+        let report = self.pass.run_pass(&mut graph.0).map_err(std::io::Error::from)?;
+        Ok(report_dict(report))
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Pass({:?}, {:?})", self.keyword, self.args)
+    }
+}
+
+impl PyPass {
+    fn build(keyword: String, args: Vec<String>, line_number: usize) -> std::io::Result<Self> {
+        let arg_refs = args.iter().map(String::as_str).collect::<Vec<_>>();
+        let pass = PassRegistry::builtin()
+            .build(&keyword, &PassArgs { args: &arg_refs, line_number, match_cache: None })?;
+        Ok(Self { keyword, args, pass: pass.into() })
+    }
+}
+
+/// Passes with an optional link step, `None` marks the link
+#[pyclass(name = "Pipeline", skip_from_py_object)]
+#[derive(Clone, Default)]
+pub struct PyPipeline(Vec<Option<PyPass>>);
+
+#[pymethods]
+impl PyPipeline {
+    #[new]
+    fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read passes from a config file
+    #[staticmethod]
+    fn from_config(path: PathBuf) -> PyResult<Self> {
+        let mut pipeline = Self::default();
+        for (line_number, line) in config_lines(&fs::read_to_string(path)?) {
+            pipeline.0.push(match line {
+                ConfigLine::Link => None,
+                ConfigLine::Pass(keyword, args) => Some(PyPass::build(
+                    keyword.to_string(),
+                    args.into_iter().map(str::to_string).collect(),
+                    line_number
+                )?),
+            });
+        }
+        Ok(pipeline)
+    }
+
+    fn add(mut slf: PyRefMut<'_, Self>, pass: PyPass) -> PyRefMut<'_, Self> {
+        slf.0.push(Some(pass));
+        slf
+    }
+
+    fn link(mut slf: PyRefMut<'_, Self>) -> PyRefMut<'_, Self> {
+        slf.0.push(None);
+        slf
+    }
+
+    /// Run on copies of `graphs`
+    fn run(&self, graphs: Vec<PyGraph>) -> PyResult<RunResult> {
+        // Names of graphs dropped by passes are released once the run ends
+        let _scope = intern::Scope::new();
+        let mut pipeline = Pipeline::new();
+        for step in &self.0 {
+            pipeline = match step {
+                Some(pass) => pipeline.add(Box::new(SharedPass(pass.pass.clone()))),
+                None => pipeline.link(),
+            };
+        }
+        let mut graphs = graphs.into_iter().map(|mut graph| std::mem::take(&mut graph.0)).collect();
+        let reports = pipeline.run(&mut graphs).map_err(std::io::Error::from)?;
+        Ok((
+            graphs.into_iter().map(PyGraph).collect(),
+            reports.into_iter().map(|(name, report)| (name, report_dict(report))).collect()
+        ))
+    }
+}
+
+/// Read a dot file, several graphs in one file are linked
+#[pyfunction]
+fn load_dot(path: PathBuf) -> PyResult<PyGraph> {
+    let mut graphs = read_graphs(&path, InputFormat::Dot, MultiGraph::Merge)?;
+    Ok(PyGraph(graphs.pop().unwrap_or_default()))
+}
+
+#[pyfunction]
+fn link(graphs: Vec<PyGraph>) -> PyResult<PyGraph> {
+    let graphs = graphs.into_iter().map(|mut graph| std::mem::take(&mut graph.0)).collect();
+    Ok(PyGraph(link_graphs_with(graphs, false)?))
+}
+
+#[pymodule]
+fn inv_call_extract(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyGraph>()?;
+    module.add_class::<PyPass>()?;
+    module.add_class::<PyPipeline>()?;
+    module.add_function(wrap_pyfunction!(load_dot, module)?)?;
+    module.add_function(wrap_pyfunction!(link, module)?)?;
+    Ok(())
+}
@@ -0,0 +1,381 @@
+use std::collections::BTreeMap;
+use std::{fs, io};
+use std::sync::Arc;
+use log::{error, warn};
+use crate::linker::edge::EdgeKind;
+use crate::linker::error::{RuleError, RuleFileError};
+use crate::linker::match_cache::MatchCache;
+use crate::linker::pass::{
+    AnnotateModulesPass, AnnotateSourcesPass, ComponentsPass, CoverageOverlay, CutDegPass,
+    ExternalNodePass, KeepDefinedPass, KeepInKallsymsPass, OverlayCoveragePass, Pass, RegexEdgeGenPass, RemoveEdgesPass,
+    ReparentGraphPass, ReverseGraphPass, SubgraphExtractionPass, RemoveNodePass,
+    UniqueEdgesPass};
+#[cfg(feature = "scripting")]
+use crate::linker::script::ScriptPass;
+use crate::linker::weighting::{builtin_weighting, WeightsPass, BUILTIN_WEIGHTINGS};
+#[cfg(feature = "wasm")]
+use crate::linker::wasm::WasmPass;
+
+/// Arguments following the pass keyword on a config line
+pub struct PassArgs<'a> {
+    pub args: &'a [&'a str],
+    pub line_number: usize,
+    /// Match results shared by the passes of a run, if enabled
+    pub match_cache: Option<&'a Arc<MatchCache>>,
+}
+
+impl PassArgs<'_> {
+    /// Read the file passed as the first argument
+    pub fn read_file(&self) -> io::Result<String> {
+        fs::read_to_string(self.args.first().ok_or(io::ErrorKind::UnexpectedEof)?)
+    }
+
+    /// Whether `match=full` is passed, regexes then match whole names instead of substrings
+    pub fn full_match(&self) -> io::Result<bool> {
+        let mut full = false;
+        for value in self.args.iter().filter_map(|arg| arg.strip_prefix("match=")) {
+            full = match value {
+                "full" => true,
+                "substring" => false,
+                _ => {
+                    error!("Invalid config on line {}: unknown match mode \"{value}\"", self.line_number);
+                    return Err(io::ErrorKind::InvalidInput.into());
+                }
+            };
+        }
+        Ok(full)
+    }
+
+    /// Pass built from the valid rules of the file passed as the first argument, failing
+    /// with errors of invalid ones naming the file. With `lenient` they are skipped with a warning
+    pub fn check_rules<P>(&self, what: &str, (pass, errors): (P, Vec<RuleError>)) -> io::Result<P> {
+        let file = self.args.first().copied().unwrap_or_default();
+        let lenient = self.args.contains(&"lenient");
+        for e in &errors {
+            if lenient {
+                warn!("Skipping {what} of config line {}: {file} {e}", self.line_number);
+            } else {
+                error!("Invalid config on line {}: {what} in {file} on {e}", self.line_number);
+            }
+        }
+        if errors.is_empty() || lenient {
+            Ok(pass)
+        } else {
+            Err(RuleFileError { path: file.to_string(), errors }.into())
+        }
+    }
+}
+
+pub type PassConstructor = fn(&PassArgs) -> io::Result<Box<dyn Pass>>;
+
+/// Config keywords and constructors of passes they create
+#[derive(Default)]
+pub struct PassRegistry {
+    constructors: BTreeMap<String, PassConstructor>,
+}
+
+impl PassRegistry {
+    /// Registry without any passes
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registry with all passes of this crate
+    #[must_use]
+    pub fn builtin() -> Self {
+        let mut registry = Self::new();
+        registry.register("remove_nodes", remove_nodes);
+        registry.register("remove_edges", remove_edges);
+        registry.register("regex_edge_gen", |args| {
+            let lenient = RegexEdgeGenPass::new_lenient(&args.read_file()?, args.full_match()?);
+            Ok(Box::new(args.check_rules("rule", lenient)?))
+        });
+        registry.register("cut_deg", cut_deg);
+        registry.register("unique_edges", |_| Ok(Box::new(UniqueEdgesPass::default())));
+        registry.register("extract_subgraph", |args| {
+            Ok(Box::new(SubgraphExtractionPass::new_from_str(&args.read_file()?)))
+        });
+        registry.register("reverse", |_| Ok(Box::new(ReverseGraphPass::default())));
+        registry.register("reparent", |args| {
+            let pass = args.check_rules("pattern", ReparentGraphPass::new_lenient(&args.read_file()?))?;
+            Ok(Box::new(match args.match_cache {
+                Some(cache) => pass.with_match_cache(cache.clone()),
+                None => pass,
+            }))
+        });
+        registry.register("annotate_sources", |_| Ok(Box::new(AnnotateSourcesPass::default())));
+        registry.register("keep_defined_in", |args| {
+            Ok(Box::new(KeepDefinedPass::new_from_str(&args.read_file()?)))
+        });
+        registry.register("keep_in_kallsyms", |args| {
+            Ok(Box::new(KeepInKallsymsPass::new_from_str(&args.read_file()?)))
+        });
+        registry.register("external_node", external_node);
+        registry.register("components", components);
+        registry.register("overlay_coverage", overlay_coverage);
+        registry.register("weights", weights);
+        registry.register("annotate_modules", |args| {
+            Ok(Box::new(AnnotateModulesPass::new_from_str(&args.read_file()?)))
+        });
+        #[cfg(feature = "wasm")]
+        registry.register("wasm_pass", wasm_pass);
+        #[cfg(feature = "scripting")]
+        registry.register("script_pass", script_pass);
+        registry
+    }
+
+    /// Add a pass keyword, replacing the previous constructor with the same keyword
+    pub fn register(&mut self, keyword: &str, constructor: PassConstructor) {
+        self.constructors.insert(keyword.to_string(), constructor);
+    }
+
+    #[must_use]
+    pub fn get(&self, keyword: &str) -> Option<PassConstructor> {
+        self.constructors.get(keyword).copied()
+    }
+
+    /// All registered keywords in alphabetical order
+    pub fn keywords(&self) -> impl Iterator<Item = &str> {
+        self.constructors.keys().map(String::as_str)
+    }
+
+    /// Create a pass for `keyword`
+    pub fn build(&self, keyword: &str, args: &PassArgs) -> io::Result<Box<dyn Pass>> {
+        let Some(constructor) = self.get(keyword) else {
+            error!("Invalid config on line {}: no \"{keyword}\" pass", args.line_number);
+            return Err(io::ErrorKind::InvalidInput.into());
+        };
+        constructor(args)
+    }
+}
+
+fn remove_nodes(args: &PassArgs) -> io::Result<Box<dyn Pass>> {
+    let data = args.read_file()?;
+    let mut pass = args.check_rules("pattern", RemoveNodePass::new_lenient(&mut data.lines(), args.full_match()?))?;
+    if let Some(cache) = args.match_cache {
+        pass = pass.with_match_cache(cache.clone());
+    }
+    Ok(Box::new(pass))
+}
+
+fn remove_edges(args: &PassArgs) -> io::Result<Box<dyn Pass>> {
+    let mut pass = RemoveEdgesPass::default();
+    let mut kind = None;
+    let full_match = args.full_match()?;
+    for arg in args.args {
+        if arg.starts_with("match=") {
+            continue;
+        }
+        if let Some(value) = arg.strip_prefix("kind=") {
+            kind = Some(value.parse::<EdgeKind>().map_err(|e| {
+                error!("Invalid config on line {}: {e}", args.line_number);
+                io::Error::from(io::ErrorKind::InvalidInput)
+            })?);
+        } else {
+            pass = RemoveEdgesPass::new_from_str_with(&fs::read_to_string(arg)?, full_match).map_err(|e| {
+                error!("Invalid config on line {}: rule in {arg} on {e}", args.line_number);
+                RuleFileError { path: arg.to_string(), errors: vec![e] }
+            })?;
+        }
+    }
+    if args.args.is_empty() {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    if let Some(kind) = kind {
+        pass = pass.with_kind(kind);
+    }
+    Ok(Box::new(pass))
+}
+
+fn external_node(args: &PassArgs) -> io::Result<Box<dyn Pass>> {
+    let policy = args.args.first().map_or(Ok(Default::default()), |arg| arg.parse()).map_err(|e| {
+        error!("Invalid config on line {}: {e}", args.line_number);
+        io::Error::from(io::ErrorKind::InvalidInput)
+    })?;
+    Ok(Box::new(ExternalNodePass::new(policy)))
+}
+
+fn overlay_coverage(args: &PassArgs) -> io::Result<Box<dyn Pass>> {
+    let mut overlay = CoverageOverlay::default();
+    let mut frontier = None;
+    for arg in args.args.iter().skip(1) {
+        if let Some(distance) = arg.strip_prefix("frontier=") {
+            frontier = Some(distance.parse().map_err(|_| {
+                error!("Invalid config on line {}: expected a number of calls, got \"{distance}\"", args.line_number);
+                io::Error::from(io::ErrorKind::InvalidInput)
+            })?);
+        } else {
+            overlay = arg.parse().map_err(|e| {
+                error!("Invalid config on line {}: {e}", args.line_number);
+                io::Error::from(io::ErrorKind::InvalidInput)
+            })?;
+        }
+    }
+    Ok(Box::new(OverlayCoveragePass::new_from_str(&args.read_file()?, overlay, frontier)))
+}
+
+fn weights(args: &PassArgs) -> io::Result<Box<dyn Pass>> {
+    let strategy = args.args.first().copied().unwrap_or_default();
+    let Some(weighting) = builtin_weighting(strategy) else {
+        error!(
+            "Invalid config on line {}: unknown weighting \"{strategy}\", expected one of {}",
+            args.line_number,
+            BUILTIN_WEIGHTINGS.join(", ")
+        );
+        return Err(io::ErrorKind::InvalidInput.into());
+    };
+    let targets = match args.args.get(1) {
+        Some(file) => fs::read_to_string(file)?.split_whitespace().map(ToString::to_string).collect(),
+        None if weighting.needs_targets() => {
+            error!(
+                "Invalid config on line {}: {} weighting needs a file with targets",
+                args.line_number,
+                weighting.name()
+            );
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+        None => Default::default(),
+    };
+    Ok(Box::new(WeightsPass::new(weighting, targets)))
+}
+
+fn components(args: &PassArgs) -> io::Result<Box<dyn Pass>> {
+    let Some(top) = args.args.first() else {
+        return Ok(Box::new(ComponentsPass::default()));
+    };
+    let top = top.parse().map_err(|_| {
+        error!("Invalid config on line {}: expected a number of components to log, got \"{top}\"", args.line_number);
+        io::Error::from(io::ErrorKind::InvalidInput)
+    })?;
+    Ok(Box::new(ComponentsPass::new(top)))
+}
+
+fn cut_deg(args: &PassArgs) -> io::Result<Box<dyn Pass>> {
+    // TODO: ensure proper argument parsing
+    let mut incoming: Option<usize> = None;
+    let mut outgoing: Option<usize> = None;
+    let mut distinct = false;
+    for arg in args.args {
+        if *arg == "distinct" {
+            distinct = true;
+            continue;
+        }
+        let sign = arg.chars().next().unwrap();
+        match sign {
+            '+' => incoming = Some(
+                arg[1..]
+                    .parse::<usize>()
+                    .map_err(|_| io::ErrorKind::InvalidInput)?
+            ),
+            '-' => outgoing = Some(
+                arg[1..]
+                    .parse::<usize>()
+                    .map_err(|_| io::ErrorKind::InvalidInput)?
+            ),
+            _ => {
+                error!("Invalid prefix for deg filter on line {}.\
+                 Expected '+' or '-', got {}", args.line_number, sign
+                );
+                return Err(io::ErrorKind::InvalidData.into());
+            }
+        }
+    }
+    let pass = CutDegPass::new(incoming, outgoing);
+    Ok(Box::new(if distinct { pass.with_distinct_neighbors() } else { pass }))
+}
+
+#[cfg(feature = "wasm")]
+fn wasm_pass(args: &PassArgs) -> io::Result<Box<dyn Pass>> {
+    let path = args.args.first().ok_or(io::ErrorKind::UnexpectedEof)?;
+    let mut pass = WasmPass::new(path, &fs::read(path)?).map_err(|e| {
+        error!("Invalid WebAssembly module on line {}: {e}", args.line_number);
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    })?;
+    for arg in &args.args[1..] {
+        if let Some(fuel) = arg.strip_prefix("fuel=").and_then(|f| f.parse().ok()) {
+            pass = pass.with_fuel(fuel);
+        } else if let Some(mib) = arg.strip_prefix("memory=").and_then(|m| m.parse::<usize>().ok()) {
+            pass = pass.with_memory_limit(mib.saturating_mul(1 << 20));
+        } else {
+            error!("Invalid config on line {}: unexpected argument \"{arg}\"", args.line_number);
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+    }
+    Ok(Box::new(pass))
+}
+
+#[cfg(feature = "scripting")]
+fn script_pass(args: &PassArgs) -> io::Result<Box<dyn Pass>> {
+    let path = args.args.first().ok_or(io::ErrorKind::UnexpectedEof)?;
+    let mut pass = ScriptPass::new(path, &fs::read_to_string(path)?).map_err(|e| {
+        error!("Invalid script on line {}: {e}", args.line_number);
+        io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+    })?;
+    for arg in &args.args[1..] {
+        let Some(operations) = arg.strip_prefix("operations=").and_then(|n| n.parse().ok()) else {
+            error!("Invalid config on line {}: unexpected argument \"{arg}\"", args.line_number);
+            return Err(io::ErrorKind::InvalidInput.into());
+        };
+        pass = pass.with_max_operations(operations);
+    }
+    Ok(Box::new(pass))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::linker::node::CallGraph;
+    use super::*;
+
+    #[test]
+    fn test_custom_pass() {
+        let mut registry = PassRegistry::builtin();
+        assert!(registry.keywords().any(|k| k == "remove_nodes"));
+        assert!(registry.get("rename").is_none());
+
+        registry.register("flip", |_| Ok(Box::new(ReverseGraphPass::default())));
+        let args = PassArgs { args: &[], line_number: 0, match_cache: None };
+        let pass = registry.build("flip", &args).unwrap();
+        assert_eq!(pass.name(), "reverse graph");
+        pass.run_pass(&mut CallGraph::new()).unwrap();
+
+        assert_eq!(
+            registry.build("rename", &args).err().map(|e| e.kind()),
+            Some(io::ErrorKind::InvalidInput)
+        );
+
+        assert!(PassArgs { args: &["file", "match=full"], line_number: 1, match_cache: None }.full_match().unwrap());
+        let args = PassArgs { args: &["file", "match=whole"], line_number: 1, match_cache: None };
+        assert_eq!(args.full_match().err().map(|e| e.kind()), Some(io::ErrorKind::InvalidInput));
+    }
+
+    #[test]
+    fn test_invalid_patterns() {
+        let root = crate::linker::test_path("registry");
+        fs::create_dir_all(&root).unwrap();
+        let file = root.join("remove");
+        fs::write(&file, "^a$ (b\n").unwrap();
+        let file = file.to_str().unwrap();
+        let registry = PassRegistry::builtin();
+
+        let args = PassArgs { args: &[file], line_number: 3, match_cache: None };
+        let err = registry.build("remove_nodes", &args).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().starts_with(&format!("{file}: line 1: invalid regex")));
+        let args = PassArgs { args: &[file, "lenient"], line_number: 3, match_cache: None };
+        assert!(registry.build("remove_nodes", &args).is_ok());
+
+        let rules = root.join("edges");
+        fs::write(&rules, "\"^a\" -> b\n\"(c\" <- d\nb -> c\n").unwrap();
+        let rules = rules.to_str().unwrap();
+        let args = PassArgs { args: &[rules], line_number: 4, match_cache: None };
+        let err = registry.build("regex_edge_gen", &args).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().starts_with(&format!("{rules}: line 2: invalid regex")));
+        assert!(err.to_string().ends_with("and 1 more invalid rules"));
+        let args = PassArgs { args: &[rules, "lenient"], line_number: 4, match_cache: None };
+        assert!(registry.build("regex_edge_gen", &args).is_ok());
+        let args = PassArgs { args: &[file], line_number: 5, match_cache: None };
+        assert!(registry.build("reparent", &args).err().unwrap().to_string().starts_with(file));
+    }
+}
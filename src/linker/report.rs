@@ -0,0 +1,85 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+use petgraph::Graph;
+
+/// What a pass changed in a graph
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PassReport {
+    pub nodes_removed: usize,
+    pub nodes_added: usize,
+    pub edges_removed: usize,
+    pub edges_added: usize,
+    /// Pass specific counters, e.g. number of matched nodes
+    pub custom: BTreeMap<String, usize>,
+    /// Keys of `custom` counters merged by taking the larger value, e.g. sizes, instead of adding them up
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub merged_by_max: BTreeSet<String>,
+}
+
+/// Node and edge counts of a graph
+#[must_use]
+pub fn graph_size<N, E>(graph: &Graph<N, E>) -> (usize, usize) {
+    (graph.node_count(), graph.edge_count())
+}
+
+impl PassReport {
+    /// Report for a pass that only added or only removed nodes and edges,
+    /// `before` is the [`graph_size`] before the pass
+    #[must_use]
+    pub fn from_size_change<N, E>(before: (usize, usize), graph: &Graph<N, E>) -> Self {
+        let (nodes, edges) = graph_size(graph);
+        Self {
+            nodes_removed: before.0.saturating_sub(nodes),
+            nodes_added: nodes.saturating_sub(before.0),
+            edges_removed: before.1.saturating_sub(edges),
+            edges_added: edges.saturating_sub(before.1),
+            custom: BTreeMap::new(),
+            merged_by_max: BTreeSet::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn with(mut self, key: &str, value: usize) -> Self {
+        self.custom.insert(key.to_string(), value);
+        self
+    }
+
+    /// Same as [`Self::with`] for a counter merged by the larger value, like the size of the largest part
+    #[must_use]
+    pub fn with_max(mut self, key: &str, value: usize) -> Self {
+        self.merged_by_max.insert(key.to_string());
+        self.with(key, value)
+    }
+
+    /// Add up counters of both reports, counters in [`Self::merged_by_max`] of either one take the larger value
+    pub fn merge(&mut self, other: &PassReport) {
+        self.nodes_removed += other.nodes_removed;
+        self.nodes_added += other.nodes_added;
+        self.edges_removed += other.edges_removed;
+        self.edges_added += other.edges_added;
+        self.merged_by_max.extend(other.merged_by_max.iter().cloned());
+        for (key, value) in &other.custom {
+            let counter = self.custom.entry(key.clone()).or_default();
+            if self.merged_by_max.contains(key) {
+                *counter = (*counter).max(*value);
+            } else {
+                *counter += value;
+            }
+        }
+    }
+}
+
+impl Display for PassReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "nodes -{} +{}, edges -{} +{}",
+            self.nodes_removed, self.nodes_added, self.edges_removed, self.edges_added
+        )?;
+        for (key, value) in &self.custom {
+            write!(f, ", {key} {value}")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,456 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use fancy_regex::Regex;
+use log::warn;
+use crate::linker::analysis::degree_histogram;
+use crate::linker::cancel::CancellationToken;
+use crate::linker::config::parse_config_file_cached;
+use crate::linker::conversion::{save_dot, DuplicateNodes};
+use crate::linker::import::{
+    expand_inputs, output_path, read_inputs_skipping_bad, read_inputs_with, InputFormat, MultiGraph
+};
+use crate::linker::import::cache::ParseCache;
+use crate::linker::incremental::{config_fingerprint, run_incremental};
+use crate::linker::intern;
+use crate::linker::match_cache::MatchCache;
+use crate::linker::node::{node_limit_error, CallGraph};
+use crate::linker::pipeline::run_pipeline;
+use crate::linker::profile;
+use crate::linker::profile::{json_string, PhaseStats};
+use crate::linker::registry::PassRegistry;
+use crate::linker::report::PassReport;
+
+/// Everything the command line configures
+pub struct RunOptions {
+    /// Input files, directories are searched for dot files recursively
+    pub inputs: Vec<PathBuf>,
+    /// Skip discovered files and directories with paths matching this regex
+    pub exclude: Option<Regex>,
+    pub config: PathBuf,
+    /// Pass keywords available in the config
+    pub registry: PassRegistry,
+    /// Output of the linked graph
+    pub linked_output: PathBuf,
+    /// Format of all inputs, detected for every file if not provided
+    pub format: Option<InputFormat>,
+    pub multi_graph: MultiGraph,
+    pub cancel: CancellationToken,
+    /// Share regex match results between the passes of the run in a [`MatchCache`]
+    pub regex_cache: bool,
+    /// Directory of the [`ParseCache`], inputs are always parsed if not provided
+    pub cache_dir: Option<PathBuf>,
+    /// Enable [`profile`] and return the phases in [`RunSummary::phases`]
+    pub profile: bool,
+    /// Link with [`run_incremental`], needs `cache_dir` and a config with a link step
+    pub incremental: bool,
+    /// Skip duplicate edges while reading dot inputs and linking
+    pub unique_edges: bool,
+    /// How dot inputs declaring a node several times with different attributes are handled
+    pub duplicate_nodes: DuplicateNodes,
+    /// Check graphs after every pass with [`Pipeline::paranoid`](crate::linker::pipeline::Pipeline::paranoid)
+    pub paranoid: bool,
+    /// Leave out inputs that fail to read and list them in [`RunSummary::skipped_inputs`]
+    /// instead of failing on the first one
+    pub skip_bad_inputs: bool,
+    /// Leave out input files larger than this many bytes, as stored on disk
+    pub max_file_size: Option<u64>,
+    /// Leave out inputs with more nodes in all their graphs, reading one stops as soon as it gets more.
+    /// Not supported by incremental runs
+    pub max_nodes_per_file: Option<usize>,
+    /// Write [`degree_histogram`] of the linked graph to this file, as CSV for `.csv` files and as text otherwise
+    pub degree_hist: Option<PathBuf>,
+}
+
+impl RunOptions {
+    /// Options with builtin passes and the command line defaults
+    #[must_use]
+    pub fn new(inputs: Vec<PathBuf>, config: PathBuf) -> Self {
+        Self {
+            inputs,
+            exclude: None,
+            config,
+            registry: PassRegistry::builtin(),
+            linked_output: PathBuf::from("out.dot"),
+            format: None,
+            multi_graph: MultiGraph::Merge,
+            cancel: CancellationToken::new(),
+            regex_cache: false,
+            cache_dir: None,
+            profile: false,
+            incremental: false,
+            unique_edges: false,
+            duplicate_nodes: DuplicateNodes::Merge,
+            paranoid: false,
+            skip_bad_inputs: false,
+            max_file_size: None,
+            max_nodes_per_file: None,
+            degree_hist: None,
+        }
+    }
+}
+
+/// Outcome of [`run`]
+#[derive(Debug, Default)]
+pub struct RunSummary {
+    /// Number of graphs read from the inputs, incremental runs only read changed ones
+    pub graphs_read: usize,
+    /// Names of passes in run order with their reports
+    pub reports: Vec<(String, PassReport)>,
+    /// Written output files
+    pub outputs: Vec<PathBuf>,
+    /// Outputs that could not be written, they don't fail the run
+    pub failed_outputs: Vec<(PathBuf, io::Error)>,
+    /// Inputs left out with [`RunOptions::skip_bad_inputs`], in input order
+    pub skipped_inputs: Vec<(PathBuf, io::Error)>,
+    /// Inputs over [`RunOptions::max_file_size`] or [`RunOptions::max_nodes_per_file`], in input order.
+    /// They don't fail the run
+    pub oversized_inputs: Vec<(PathBuf, io::Error)>,
+    /// Profiled phases in run order, empty unless [`RunOptions::profile`] is set
+    pub phases: Vec<PhaseStats>,
+}
+
+/// Move files larger than `max` bytes out of `files`, with the reason
+fn take_large_files(files: &mut Vec<PathBuf>, max: u64) -> Vec<(PathBuf, io::Error)> {
+    let mut large = vec![];
+    files.retain(|path| match fs::metadata(path) {
+        Ok(metadata) if metadata.len() > max => {
+            let reason = format!("{} bytes, more than the limit of {max}", metadata.len());
+            large.push((path.clone(), io::Error::new(io::ErrorKind::FileTooLarge, reason)));
+            false
+        }
+        // Reading reports missing files
+        _ => true,
+    });
+    large
+}
+
+/// Write `summary` as a JSON object: graphs read, pass reports, written and failed outputs,
+/// skipped and oversized inputs
+pub fn write_summary(out: &mut impl Write, summary: &RunSummary) -> io::Result<()> {
+    let path = |path: &Path| json_string(&path.to_string_lossy());
+    let failures = |list: &[(PathBuf, io::Error)]| list.iter()
+        .map(|(p, err)| format!("{{\"path\": {}, \"error\": {}}}", path(p), json_string(&err.to_string())))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let passes = summary.reports.iter()
+        .map(|(name, report)| {
+            let custom = report.custom.iter()
+                .map(|(key, value)| format!("{}: {value}", json_string(key)))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!(
+                "{{\"name\": {}, \"nodes_removed\": {}, \"nodes_added\": {}, \"edges_removed\": {}, \
+                    \"edges_added\": {}, \"custom\": {{{custom}}}}}",
+                json_string(name),
+                report.nodes_removed,
+                report.nodes_added,
+                report.edges_removed,
+                report.edges_added
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    let outputs = summary.outputs.iter().map(|p| path(p)).collect::<Vec<_>>().join(", ");
+    writeln!(out, "{{")?;
+    writeln!(out, "  \"graphs_read\": {},", summary.graphs_read)?;
+    writeln!(out, "  \"passes\": [{passes}],")?;
+    writeln!(out, "  \"outputs\": [{outputs}],")?;
+    writeln!(out, "  \"failed_outputs\": [{}],", failures(&summary.failed_outputs))?;
+    writeln!(out, "  \"skipped_inputs\": [{}],", failures(&summary.skipped_inputs))?;
+    writeln!(out, "  \"oversized_inputs\": [{}]", failures(&summary.oversized_inputs))?;
+    writeln!(out, "}}")
+}
+
+/// Write the degree histogram of `linked` to `path`, warning instead without a link step
+fn write_degree_hist(path: &Path, linked: Option<&CallGraph>) -> io::Result<()> {
+    let Some(graph) = linked else {
+        warn!("Degree histograms need a link step in the config, not writing {}", path.display());
+        return Ok(());
+    };
+    let histogram = degree_histogram(graph);
+    let mut out = io::BufWriter::new(fs::File::create(path)?);
+    if path.extension().is_some_and(|ext| ext == "csv") {
+        histogram.write_csv(&mut out)?;
+    } else {
+        write!(out, "{histogram}")?;
+    }
+    out.flush()
+}
+
+/// Lexically normalized absolute path, so `./a.dot` and `a.dot` compare equal
+fn normalize(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()).components().collect()
+}
+
+/// Fail if an output would overwrite an input or another output.
+/// `outputs` pairs every output with a description of the graph written to it.
+fn check_outputs(outputs: &[(PathBuf, String)], inputs: &[PathBuf]) -> io::Result<()> {
+    let inputs = inputs.iter().map(|input| normalize(input)).collect::<HashSet<_>>();
+    let mut written: HashMap<PathBuf, &str> = HashMap::new();
+    for (output, graph) in outputs {
+        let normalized = normalize(output);
+        let message = if inputs.contains(&normalized) {
+            format!("Output {} of {graph} would overwrite an input", output.display())
+        } else if let Some(other) = written.insert(normalized, graph) {
+            format!("Both {other} and {graph} would be written to {}", output.display())
+        } else {
+            continue;
+        };
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, message));
+    }
+    Ok(())
+}
+
+/// Read inputs, run passes from the config and write every resulting graph.
+/// Fails before reading inputs if outputs would overwrite inputs or each other,
+/// with split dot files after reading them.
+pub fn run(options: &RunOptions) -> io::Result<RunSummary> {
+    // Releases names of the run's graphs after everything else is dropped
+    let _names = intern::Scope::new();
+    // Disables profiling again however the run ends
+    let _profiling = options.profile.then(profile::Session::start);
+    let phase = profile::phase("config", "");
+    let match_cache = options.regex_cache.then(|| Arc::new(MatchCache::new()));
+    let mut pipeline = parse_config_file_cached(&options.config, &options.registry, match_cache.as_ref())?
+        .cancel_on(options.cancel.clone());
+    if options.unique_edges {
+        pipeline = pipeline.unique_edges();
+    }
+    if options.paranoid {
+        pipeline = pipeline.paranoid();
+    }
+    drop(phase);
+
+    let mut files = expand_inputs(options.inputs.iter().cloned(), options.exclude.as_ref())?;
+    let mut summary = RunSummary::default();
+    if let Some(max) = options.max_file_size {
+        summary.oversized_inputs = take_large_files(&mut files, max);
+    }
+    if pipeline.is_linked() {
+        check_outputs(&[(options.linked_output.clone(), "the linked graph".to_string())], &files)?;
+    } else if options.multi_graph == MultiGraph::Merge {
+        let outputs = files.iter()
+            .map(|input| (output_path(input, None), format!("input {}", input.display())))
+            .collect::<Vec<_>>();
+        check_outputs(&outputs, &files)?;
+    }
+    let cache = options.cache_dir.as_ref().map(ParseCache::new).transpose()?;
+    if options.incremental && !pipeline.is_linked() {
+        warn!("Incremental runs need a link step in the config, reading all inputs");
+    }
+    let graphs = match &cache {
+        Some(cache) if options.incremental && pipeline.is_linked() => {
+            if options.max_nodes_per_file.is_some() {
+                let message = "Incremental runs don't support a node limit per file";
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+            }
+            let fingerprint = config_fingerprint(&options.config)?;
+            let run = run_incremental(
+                &pipeline,
+                cache,
+                &files,
+                options.format,
+                options.multi_graph,
+                options.duplicate_nodes,
+                fingerprint,
+                options.skip_bad_inputs
+            )?;
+            summary.graphs_read = run.graphs_read;
+            summary.reports = run.reports;
+            summary.skipped_inputs = run.skipped_inputs;
+            vec![(options.linked_output.clone(), run.graph)]
+        }
+        _ => {
+            if options.incremental {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, "Incremental runs need a cache directory"));
+            }
+            let phase = profile::phase("parse", "");
+            // Inputs over the node limit stop being read early and are reported, not failing the run
+            let (mut graphs, failed) = if options.skip_bad_inputs || options.max_nodes_per_file.is_some() {
+                read_inputs_skipping_bad(
+                    &files,
+                    options.format,
+                    options.multi_graph,
+                    options.unique_edges,
+                    options.duplicate_nodes,
+                    options.max_nodes_per_file,
+                    cache.as_ref()
+                )
+            } else {
+                let graphs = read_inputs_with(
+                    &files,
+                    options.format,
+                    options.multi_graph,
+                    options.unique_edges,
+                    options.duplicate_nodes,
+                    None,
+                    cache.as_ref()
+                )?;
+                (graphs, vec![])
+            };
+            drop(phase);
+            for (path, err) in failed {
+                if err.kind() == io::ErrorKind::FileTooLarge {
+                    // Readers name the file in the error, the summary lists it already
+                    summary.oversized_inputs.push((path, options.max_nodes_per_file.map_or(err, node_limit_error)));
+                } else if options.skip_bad_inputs {
+                    summary.skipped_inputs.push((path, err));
+                } else {
+                    return Err(err);
+                }
+            }
+            if !pipeline.is_linked() && options.multi_graph == MultiGraph::Split {
+                let outputs = graphs.iter()
+                    .map(|(output, graph)| {
+                        let source = graph.node_weights().find_map(|node| node.sources.first());
+                        let graph = source.map_or("an empty graph".to_string(), |source| {
+                            format!("a graph of {}", source.display())
+                        });
+                        (output.clone(), graph)
+                    })
+                    .collect::<Vec<_>>();
+                check_outputs(&outputs, &files)?;
+            }
+            summary.graphs_read = graphs.len();
+            summary.reports = run_pipeline(&pipeline, &mut graphs, &options.linked_output)?;
+            graphs
+        }
+    };
+
+    for (path, err) in &summary.skipped_inputs {
+        warn!("Skipped input {}: {err}", path.display());
+    }
+    for (path, err) in &summary.oversized_inputs {
+        warn!("Skipped oversized input {}: {err}", path.display());
+    }
+
+    let phase = profile::phase("write", "");
+    if let Some(path) = &options.degree_hist {
+        let linked = graphs.first().filter(|_| pipeline.is_linked()).map(|(_, graph)| graph);
+        write_degree_hist(path, linked)?;
+    }
+    for (save_to, graph) in graphs {
+        match save_dot(&save_to, &graph) {
+            Ok(()) => summary.outputs.push(save_to),
+            Err(err) => {
+                warn!("Failed to write .dot file: {err}");
+                summary.failed_outputs.push((save_to, err));
+            }
+        }
+    }
+    drop(phase);
+    if options.profile {
+        summary.phases = profile::take_phases();
+    }
+    Ok(summary)
+}
+
+#[cfg(all(test, feature = "dot"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run() {
+        let root = crate::linker::test_path("runner");
+        fs::create_dir_all(root.join("inputs")).unwrap();
+        fs::write(root.join("inputs/a.dot"), "digraph { a -> b; x -> y }").unwrap();
+        fs::write(root.join("inputs/b.dot"), "digraph { b -> c }").unwrap();
+        fs::write(root.join("remove"), "^x$\n").unwrap();
+        fs::write(root.join("config"), format!("remove_nodes {}\nlink\n", root.join("remove").display())).unwrap();
+
+        let mut options = RunOptions::new(vec![root.join("inputs")], root.join("config"));
+        options.linked_output = root.join("linked.dot");
+        let summary = run(&options).unwrap();
+        assert_eq!(summary.graphs_read, 2);
+        assert_eq!(summary.reports.len(), 1);
+        assert_eq!(summary.reports[0].1.nodes_removed, 1);
+        assert_eq!(summary.outputs, [root.join("linked.dot")]);
+        assert!(summary.failed_outputs.is_empty());
+        let linked = fs::read_to_string(root.join("linked.dot")).unwrap();
+        assert!(linked.contains(r#""b" -> "c""#));
+
+        assert!(!linked.contains(r#""x""#));
+
+        options.degree_hist = Some(root.join("degrees.csv"));
+        run(&options).unwrap();
+        let csv = fs::read_to_string(root.join("degrees.csv")).unwrap();
+        assert_eq!(csv, "degree,in,out\n0,2,2\n1,2,2\n");
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_output_collisions() {
+        let root = crate::linker::test_path("collisions");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("inputs")).unwrap();
+        fs::write(root.join("inputs/foo.dot"), "digraph { a -> b }").unwrap();
+        fs::write(root.join("inputs/foo.folded"), "a;b 1\n").unwrap();
+        fs::write(root.join("inputs/bar.dot"), "digraph { a } digraph { b }").unwrap();
+        fs::write(root.join("inputs/bar.0.dot"), "digraph { c }").unwrap();
+        fs::write(root.join("config"), "reverse\n").unwrap();
+
+        let inputs = ["foo.dot", "foo.folded"].map(|file| root.join("inputs").join(file)).to_vec();
+        let options = RunOptions::new(inputs.clone(), root.join("config"));
+        let err = run(&options).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert!(err.to_string().contains("foo.folded would be written to"));
+        assert!(!root.join("inputs/foo.out.dot").exists());
+
+        let split_inputs = ["bar.dot", "bar.0.dot"].map(|file| root.join("inputs").join(file)).to_vec();
+        let mut options = RunOptions::new(split_inputs, root.join("config"));
+        options.multi_graph = MultiGraph::Split;
+        assert!(run(&options).unwrap_err().to_string().contains("a graph of"));
+        assert_eq!(fs::read_dir(root.join("inputs")).unwrap().count(), 4);
+
+        fs::write(root.join("config"), "link\n").unwrap();
+        let mut options = RunOptions::new(inputs, root.join("config"));
+        options.linked_output = root.join("inputs/./foo.dot");
+        assert!(run(&options).unwrap_err().to_string().contains("would overwrite an input"));
+        options.linked_output = root.join("linked.dot");
+        run(&options).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_oversized_inputs() {
+        let root = crate::linker::test_path("oversized");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("inputs")).unwrap();
+        fs::write(root.join("inputs/a.dot"), "digraph { a -> b }").unwrap();
+        fs::write(root.join("inputs/b.dot"), "digraph { b -> c; c -> d }").unwrap();
+        fs::write(root.join("inputs/c.dot"), format!("digraph {{ c -> d }}{}", " ".repeat(100))).unwrap();
+        fs::write(root.join("inputs/d.dot"), "digraph { x } digraph { y -> z }").unwrap();
+        fs::write(root.join("config"), "link\n").unwrap();
+
+        let mut options = RunOptions::new(vec![root.join("inputs")], root.join("config"));
+        options.linked_output = root.join("linked.dot");
+        options.max_file_size = Some(100);
+        options.max_nodes_per_file = Some(2);
+        let summary = run(&options).unwrap();
+        assert_eq!(summary.graphs_read, 1);
+        let oversized = summary.oversized_inputs.iter()
+            .map(|(path, err)| (path.file_name().unwrap().to_str().unwrap(), err.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(oversized, [
+            ("c.dot", "118 bytes, more than the limit of 100".to_string()),
+            ("b.dot", "more than the limit of 2 nodes".to_string()),
+            ("d.dot", "more than the limit of 2 nodes".to_string()),
+        ]);
+
+        let mut out = vec![];
+        write_summary(&mut out, &summary).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains(r#""graphs_read": 1,"#));
+        assert!(out.contains(r#"more than the limit of 2 nodes"}]"#));
+        assert!(out.contains(r#""skipped_inputs": [],"#));
+
+        options.incremental = true;
+        options.cache_dir = Some(root.join("cache"));
+        assert_eq!(run(&options).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
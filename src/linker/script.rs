@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use fancy_regex::Regex;
+use log::info;
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Map, Scope, AST};
+use crate::linker::cancel::CancellationToken;
+use crate::linker::edge::EdgeKind;
+use crate::linker::edit::{apply_edits, GraphEdit};
+use crate::linker::error::PassError;
+use crate::linker::node::CallGraph;
+use crate::linker::pass::Pass;
+use crate::linker::report::PassReport;
+
+/// Operations a script may run on a single graph before it is stopped
+pub const DEFAULT_MAX_OPERATIONS: u64 = 1_000_000_000;
+
+/// Pass running a Rhai script.
+///
+/// The script sees `nodes`, an array of node names, and `edges`, an array of `#{from, to, kind, count}` maps.
+/// Both are constants, so counts can't be changed, and added edges count one call.
+/// It may call `matches(name, regex)`, `remove_node(name)`, `remove_edge(from, to)`,
+/// `add_edge(from, to)` and `add_edge(from, to, kind)`.
+/// Edits are applied after the script finishes, nodes are removed last.
+/// Scripts are stopped after [`DEFAULT_MAX_OPERATIONS`] or once the run is cancelled.
+pub struct ScriptPass {
+    name: String,
+    ast: AST,
+    max_operations: u64,
+}
+
+type Edits = Arc<Mutex<Vec<GraphEdit>>>;
+
+impl ScriptPass {
+    pub fn new(name: &str, script: &str) -> Result<Self, Box<EvalAltResult>> {
+        let ast = Engine::new().compile(script)?;
+        Ok(Self { name: name.to_string(), ast, max_operations: DEFAULT_MAX_OPERATIONS })
+    }
+
+    #[must_use]
+    pub fn with_max_operations(mut self, operations: u64) -> Self {
+        self.max_operations = operations;
+        self
+    }
+
+    fn engine(&self, edits: &Edits, cancel: &CancellationToken) -> Engine {
+        let mut engine = Engine::new();
+        engine.set_max_operations(self.max_operations);
+        let cancel = cancel.clone();
+        engine.on_progress(move |_| cancel.is_cancelled().then_some(Dynamic::UNIT));
+        let regexes: Arc<Mutex<HashMap<String, Regex>>> = Arc::default();
+        engine.register_fn("matches", move |name: &str, pattern: &str| {
+            let mut regexes = regexes.lock().unwrap();
+            if !regexes.contains_key(pattern) {
+                let re = Regex::new(pattern).map_err(|e| e.to_string())?;
+                regexes.insert(pattern.to_string(), re);
+            }
+            regexes[pattern]
+                .is_match(name)
+                .map_err(|e| Box::<EvalAltResult>::from(e.to_string()))
+        });
+        let list = edits.clone();
+        engine.register_fn("remove_node", move |name: &str| {
+            list.lock().unwrap().push(GraphEdit::RemoveNode(name.to_string()));
+        });
+        let list = edits.clone();
+        engine.register_fn("remove_edge", move |from: &str, to: &str| {
+            list.lock().unwrap().push(GraphEdit::RemoveEdge(from.to_string(), to.to_string()));
+        });
+        let list = edits.clone();
+        engine.register_fn("add_edge", move |from: &str, to: &str| {
+            list.lock().unwrap().push(
+                GraphEdit::AddEdge(from.to_string(), to.to_string(), EdgeKind::Synthetic)
+            );
+        });
+        let list = edits.clone();
+        engine.register_fn("add_edge", move |from: &str, to: &str, kind: &str| {
+            let kind = kind.parse::<EdgeKind>().map_err(|e| e.to_string())?;
+            list.lock().unwrap().push(GraphEdit::AddEdge(from.to_string(), to.to_string(), kind));
+            Ok::<_, Box<EvalAltResult>>(())
+        });
+        engine
+    }
+
+    fn scope(graph: &CallGraph) -> Scope<'static> {
+        let nodes: Array = graph.node_weights()
+            .map(|node| Dynamic::from(node.name.to_string()))
+            .collect();
+        let edges: Array = graph.raw_edges()
+            .iter()
+            .map(|edge| {
+                let mut map = Map::new();
+                map.insert("from".into(), graph[edge.source()].name.to_string().into());
+                map.insert("to".into(), graph[edge.target()].name.to_string().into());
+                map.insert("kind".into(), edge.weight.kind.to_string().into());
+                map.insert("count".into(), i64::try_from(edge.weight.count).unwrap_or(i64::MAX).into());
+                Dynamic::from_map(map)
+            })
+            .collect();
+        let mut scope = Scope::new();
+        scope.push_constant("nodes", nodes);
+        scope.push_constant("edges", edges);
+        scope
+    }
+}
+
+impl Pass for ScriptPass {
+    fn run_pass(&self, graph: &mut CallGraph) -> Result<PassReport, PassError> {
+        self.run_pass_cancellable(graph, &CancellationToken::new())
+    }
+
+    fn run_pass_cancellable(&self, graph: &mut CallGraph, cancel: &CancellationToken) -> Result<PassReport, PassError> {
+        let edits = Edits::default();
+        self.engine(&edits, cancel)
+            .run_ast_with_scope(&mut Self::scope(graph), &self.ast)
+            .map_err(|e| match *e {
+                EvalAltResult::ErrorTerminated(..) => PassError::Cancelled,
+                _ => PassError::Other(e),
+            })?;
+        let edits = std::mem::take(&mut *edits.lock().unwrap());
+        info!("Script {} requested {} edits", self.name, edits.len());
+        Ok(apply_edits(graph, edits))
+    }
+
+    fn name(&self) -> String {
+        format!("script pass {}", self.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script_edits() {
+        let mut graph = CallGraph::new();
+        let main = graph.add_node("main".into());
+        let lock = graph.add_node("spin_lock".into());
+        let unlock = graph.add_node("spin_unlock".into());
+        graph.add_edge(main, lock, EdgeKind::Direct.into());
+        graph.add_edge(main, unlock, EdgeKind::Indirect.into());
+
+        let pass = ScriptPass::new("test", r#"
+            for edge in edges {
+                if edge.count != 1 {
+                    throw "unexpected count";
+                }
+                if edge.kind == "indirect" {
+                    remove_edge(edge.from, edge.to);
+                }
+            }
+            for name in nodes {
+                if matches(name, "^spin_(?!lock)") {
+                    remove_node(name);
+                }
+            }
+            add_edge("main", "schedule", "direct");
+        "#).unwrap();
+        let report = pass.run_pass(&mut graph).unwrap();
+        assert_eq!(
+            (report.nodes_removed, report.nodes_added, report.edges_removed, report.edges_added),
+            (1, 1, 1, 1)
+        );
+
+        let mut names = graph.node_weights().map(|n| n.name.as_ref()).collect::<Vec<_>>();
+        names.sort_unstable();
+        assert_eq!(names, ["main", "schedule", "spin_lock"]);
+        assert_eq!(graph.edge_weights().map(|edge| edge.kind).collect::<Vec<_>>(), [EdgeKind::Direct; 2]);
+    }
+
+    #[test]
+    fn test_script_limits() {
+        let pass = ScriptPass::new("spin", "loop {}").unwrap().with_max_operations(1000);
+        let mut graph = CallGraph::new();
+        assert!(matches!(pass.run_pass(&mut graph), Err(PassError::Other(_))));
+
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let pass = ScriptPass::new("spin", "loop {}").unwrap();
+        assert!(matches!(pass.run_pass_cancellable(&mut graph, &cancel), Err(PassError::Cancelled)));
+    }
+
+    #[test]
+    fn test_script_error() {
+        let pass = ScriptPass::new("test", r#"add_edge("a", "b", "weird");"#).unwrap();
+        let mut graph = CallGraph::new();
+        assert!(matches!(pass.run_pass(&mut graph), Err(PassError::Other(_))));
+        assert_eq!(graph.node_count(), 0);
+
+        let a = graph.add_node("a".into());
+        graph.add_edge(a, a, EdgeKind::Direct.into());
+        let pass = ScriptPass::new("test", "edges[0].count = 5;").unwrap();
+        assert!(matches!(pass.run_pass(&mut graph), Err(PassError::Other(_))));
+        assert_eq!(graph.edge_weights().map(|edge| edge.count).collect::<Vec<_>>(), [1]);
+    }
+}
@@ -0,0 +1,351 @@
+use std::borrow::Cow;
+use std::error::Error;
+use log::info;
+use wasmi::{
+    Config, Engine, Linker, Module, Store, StoreLimits, StoreLimitsBuilder, TypedFunc, TypedResumableCall, WasmParams,
+    WasmResults
+};
+use crate::linker::cancel::CancellationToken;
+use crate::linker::edge::EdgeKind;
+use crate::linker::edit::{apply_edits, GraphEdit};
+use crate::linker::error::PassError;
+use crate::linker::node::CallGraph;
+use crate::linker::pass::Pass;
+use crate::linker::report::PassReport;
+
+/// Instructions a module may execute on a single graph before it is stopped
+pub const DEFAULT_FUEL: u64 = 10_000_000_000;
+
+/// Bytes of linear memory a module may grow to
+pub const DEFAULT_MEMORY_LIMIT: usize = 1 << 30;
+
+/// Instructions a module runs between checks for cancellation
+const FUEL_SLICE: u64 = 10_000_000;
+
+/// Pass running a WebAssembly module in a sandbox.
+///
+/// The module exports `memory`, `alloc(len: i32) -> i32` and `run(ptr: i32, len: i32) -> i64`.
+/// `run` receives the graph as tab separated lines `node NAME` and `edge FROM TO KIND`
+/// and returns edits packed as `ptr << 32 | len`: lines `remove_node NAME`,
+/// `remove_edge FROM TO` and `add_edge FROM TO [KIND]`.
+/// Backslashes, tabs and line breaks of names are escaped as `\\`, `\t`, `\n` and `\r` both ways.
+/// Nodes are removed after all other edits are applied.
+pub struct WasmPass {
+    name: String,
+    engine: Engine,
+    module: Module,
+    fuel: u64,
+    memory_limit: usize,
+}
+
+fn wasm_error(e: impl Into<Box<dyn Error + Send + Sync>>) -> PassError {
+    PassError::Other(e.into())
+}
+
+/// Name as a single field of a tab separated line
+fn escape_field(name: &str) -> Cow<'_, str> {
+    if !name.contains(['\\', '\t', '\n', '\r']) {
+        return Cow::Borrowed(name);
+    }
+    let mut result = String::with_capacity(name.len() + 2);
+    for c in name.chars() {
+        match c {
+            '\\' => result.push_str("\\\\"),
+            '\t' => result.push_str("\\t"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            c => result.push(c),
+        }
+    }
+    Cow::Owned(result)
+}
+
+/// Name of a field escaped by [`escape_field`]
+fn unescape_field(field: &str) -> Result<String, PassError> {
+    let mut result = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('\\') => result.push('\\'),
+            Some('t') => result.push('\t'),
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            _ => return Err(wasm_error(format!("invalid escape in \"{field}\""))),
+        }
+    }
+    Ok(result)
+}
+
+impl WasmPass {
+    pub fn new(name: &str, wasm: &[u8]) -> Result<Self, wasmi::Error> {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let module = Module::new(&engine, wasm)?;
+        Ok(Self { name: name.to_string(), engine, module, fuel: DEFAULT_FUEL, memory_limit: DEFAULT_MEMORY_LIMIT })
+    }
+
+    #[must_use]
+    pub fn with_fuel(mut self, fuel: u64) -> Self {
+        self.fuel = fuel;
+        self
+    }
+
+    /// Fail memory allocation and growth of the module beyond `bytes`
+    #[must_use]
+    pub fn with_memory_limit(mut self, bytes: usize) -> Self {
+        self.memory_limit = bytes;
+        self
+    }
+
+    fn serialize(graph: &CallGraph) -> String {
+        let mut result = String::new();
+        for node in graph.node_weights() {
+            result += &format!("node\t{}\n", escape_field(&node.name));
+        }
+        for edge in graph.raw_edges() {
+            result += &format!(
+                "edge\t{}\t{}\t{}\n",
+                escape_field(&graph[edge.source()].name),
+                escape_field(&graph[edge.target()].name),
+                edge.weight.kind
+            );
+        }
+        result
+    }
+
+    /// Call `func` with the fuel of `store` and then `reserve` in slices, checking `cancel` between them
+    fn call_sliced<Params: WasmParams, Results: WasmResults>(
+        &self,
+        store: &mut Store<StoreLimits>,
+        func: &TypedFunc<Params, Results>,
+        params: Params,
+        reserve: &mut u64,
+        cancel: &CancellationToken
+    ) -> Result<Results, PassError> {
+        let mut call = func.call_resumable(&mut *store, params).map_err(wasm_error)?;
+        loop {
+            let out_of_fuel = match call {
+                TypedResumableCall::Finished(results) => return Ok(results),
+                TypedResumableCall::HostTrap(_) => return Err(wasm_error("module trapped in a host call")),
+                TypedResumableCall::OutOfFuel(out_of_fuel) => out_of_fuel,
+            };
+            cancel.check()?;
+            let fuel = store.get_fuel().map_err(wasm_error)?;
+            let required = out_of_fuel.required_fuel();
+            let refill = FUEL_SLICE.max(required).saturating_sub(fuel).min(*reserve);
+            if fuel + refill < required {
+                return Err(wasm_error(format!("module {} ran out of {} fuel", self.name, self.fuel)));
+            }
+            *reserve -= refill;
+            store.set_fuel(fuel + refill).map_err(wasm_error)?;
+            call = out_of_fuel.resume(&mut *store).map_err(wasm_error)?;
+        }
+    }
+
+    fn call_module(&self, input: &str, cancel: &CancellationToken) -> Result<String, PassError> {
+        let limits = StoreLimitsBuilder::new().memory_size(self.memory_limit).build();
+        let mut store = Store::new(&self.engine, limits);
+        store.limiter(|limits: &mut StoreLimits| limits);
+        store.set_fuel(self.fuel).map_err(wasm_error)?;
+        let instance = Linker::new(&self.engine)
+            .instantiate_and_start(&mut store, &self.module)
+            .map_err(wasm_error)?;
+        // Fuel left by the start function is handed out in slices
+        let mut reserve = store.get_fuel().map_err(wasm_error)?;
+        let slice = reserve.min(FUEL_SLICE);
+        reserve -= slice;
+        store.set_fuel(slice).map_err(wasm_error)?;
+        let memory = instance
+            .get_memory(&store, "memory")
+            .ok_or_else(|| wasm_error("module does not export \"memory\""))?;
+        let alloc = instance
+            .get_typed_func::<i32, i32>(&store, "alloc")
+            .map_err(wasm_error)?;
+        let run = instance
+            .get_typed_func::<(i32, i32), i64>(&store, "run")
+            .map_err(wasm_error)?;
+
+        let len = i32::try_from(input.len()).map_err(wasm_error)?;
+        let ptr = self.call_sliced(&mut store, &alloc, len, &mut reserve, cancel)?;
+        memory
+            .write(&mut store, usize::try_from(ptr).map_err(wasm_error)?, input.as_bytes())
+            .map_err(wasm_error)?;
+        #[allow(clippy::cast_sign_loss)]
+        let packed = self.call_sliced(&mut store, &run, (ptr, len), &mut reserve, cancel)? as u64;
+        let out_ptr = usize::try_from(packed >> 32).map_err(wasm_error)?;
+        let out_len = usize::try_from(packed & 0xffff_ffff).map_err(wasm_error)?;
+        if out_ptr.checked_add(out_len).is_none_or(|end| end > memory.data_size(&store)) {
+            return Err(wasm_error(format!("output at {out_ptr} of {out_len} bytes is out of memory bounds")));
+        }
+        let mut output = vec![0; out_len];
+        memory.read(&store, out_ptr, &mut output).map_err(wasm_error)?;
+        String::from_utf8(output).map_err(wasm_error)
+    }
+
+    fn parse_edits(edits: &str) -> Result<Vec<GraphEdit>, PassError> {
+        edits.lines()
+            .filter(|l| !l.is_empty())
+            .map(|line| match line.split('\t').collect::<Vec<_>>()[..] {
+                ["remove_node", name] => Ok(GraphEdit::RemoveNode(unescape_field(name)?)),
+                ["remove_edge", from, to] => {
+                    Ok(GraphEdit::RemoveEdge(unescape_field(from)?, unescape_field(to)?))
+                }
+                ["add_edge", from, to] => {
+                    Ok(GraphEdit::AddEdge(unescape_field(from)?, unescape_field(to)?, EdgeKind::Synthetic))
+                }
+                ["add_edge", from, to, kind] => Ok(GraphEdit::AddEdge(
+                    unescape_field(from)?,
+                    unescape_field(to)?,
+                    kind.parse().map_err(wasm_error)?
+                )),
+                _ => Err(wasm_error(format!("invalid edit \"{line}\""))),
+            })
+            .collect()
+    }
+}
+
+impl Pass for WasmPass {
+    fn run_pass(&self, graph: &mut CallGraph) -> Result<PassReport, PassError> {
+        self.run_pass_cancellable(graph, &CancellationToken::new())
+    }
+
+    /// The module is stopped between fuel slices once `cancel` is triggered
+    fn run_pass_cancellable(&self, graph: &mut CallGraph, cancel: &CancellationToken) -> Result<PassReport, PassError> {
+        let edits = self.call_module(&Self::serialize(graph), cancel)?;
+        info!("Module {} returned {} edits", self.name, edits.lines().count());
+        Ok(apply_edits(graph, Self::parse_edits(&edits)?))
+    }
+
+    fn name(&self) -> String {
+        format!("wasm pass {}", self.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph() -> CallGraph {
+        let mut graph = CallGraph::new();
+        let a = graph.add_node("a".into());
+        let b = graph.add_node("b".into());
+        graph.add_edge(a, b, EdgeKind::Direct.into());
+        graph
+    }
+
+    #[test]
+    fn test_wasm_edits() {
+        let wasm = wat::parse_str(r#"
+            (module
+                (memory (export "memory") 1)
+                (data (i32.const 0) "remove_node\tb\nadd_edge\ta\tc\n")
+                (func (export "alloc") (param i32) (result i32) i32.const 1024)
+                (func (export "run") (param i32 i32) (result i64) i64.const 27))
+        "#).unwrap();
+        let mut graph = graph();
+        WasmPass::new("edits", &wasm).unwrap().run_pass(&mut graph).unwrap();
+
+        assert_eq!(
+            graph.node_weights().map(|n| n.name.as_ref()).collect::<Vec<_>>(),
+            ["a", "c"]
+        );
+        assert_eq!(graph.edge_weights().map(|edge| edge.kind).collect::<Vec<_>>(), [EdgeKind::Synthetic]);
+    }
+
+    #[test]
+    fn test_wasm_memory_bounds() {
+        let wasm = wat::parse_str(r#"
+            (module
+                (memory (export "memory") 1)
+                (func (export "alloc") (param i32) (result i32) i32.const 0)
+                (func (export "run") (param i32 i32) (result i64) i64.const 0x7fffffff))
+        "#).unwrap();
+        let mut graph = graph();
+        let err = WasmPass::new("huge", &wasm).unwrap().run_pass(&mut graph).unwrap_err();
+        assert!(err.to_string().contains("out of memory bounds"), "{err}");
+
+        let wasm = wat::parse_str(r#"
+            (module
+                (memory (export "memory") 1)
+                (func (export "alloc") (param i32) (result i32) i32.const 0)
+                (func (export "run") (param i32 i32) (result i64)
+                    (if (i32.eq (memory.grow (i32.const 16)) (i32.const -1)) (then unreachable))
+                    i64.const 0))
+        "#).unwrap();
+        let pass = WasmPass::new("grow", &wasm).unwrap().with_memory_limit(4 << 16);
+        assert!(matches!(pass.run_pass(&mut graph), Err(PassError::Other(_))));
+        assert!(WasmPass::new("grow", &wasm).unwrap().run_pass(&mut graph).is_ok());
+    }
+
+    #[test]
+    fn test_wasm_out_of_fuel() {
+        let wasm = wat::parse_str(r#"
+            (module
+                (memory (export "memory") 1)
+                (func (export "alloc") (param i32) (result i32) i32.const 0)
+                (func (export "run") (param i32 i32) (result i64)
+                    (loop $spin (br $spin))
+                    i64.const 0))
+        "#).unwrap();
+        let pass = WasmPass::new("spin", &wasm).unwrap().with_fuel(1000);
+        let mut graph = graph();
+        assert!(matches!(pass.run_pass(&mut graph), Err(PassError::Other(_))));
+        assert_eq!(graph.edge_count(), 1);
+    }
+
+    #[test]
+    fn test_wasm_cancel() {
+        let wasm = wat::parse_str(r#"
+            (module
+                (memory (export "memory") 1)
+                (func (export "alloc") (param i32) (result i32) i32.const 0)
+                (func (export "run") (param i32 i32) (result i64)
+                    (loop $spin (br $spin))
+                    i64.const 0))
+        "#).unwrap();
+        let pass = WasmPass::new("spin", &wasm).unwrap();
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let mut graph = graph();
+        assert!(matches!(pass.run_pass_cancellable(&mut graph, &cancel), Err(PassError::Cancelled)));
+        assert_eq!(graph.edge_count(), 1);
+    }
+
+    #[test]
+    fn test_wasm_escaped_names() {
+        let mut graph = graph();
+        let tab = graph.add_node("a\tb".into());
+        graph.add_node("c\\n\nd".into());
+        graph.add_edge(tab, tab, EdgeKind::Direct.into());
+        assert_eq!(
+            WasmPass::serialize(&graph),
+            "node\ta\nnode\tb\nnode\ta\\tb\nnode\tc\\\\n\\nd\n\
+             edge\ta\tb\tdirect\nedge\ta\\tb\ta\\tb\tdirect\n"
+        );
+
+        let wasm = wat::parse_str(r#"
+            (module
+                (memory (export "memory") 1)
+                (data (i32.const 0) "remove_node\ta\\tb\nremove_node\tc\\\\n\\nd\n")
+                (func (export "alloc") (param i32) (result i32) i32.const 1024)
+                (func (export "run") (param i32 i32) (result i64) i64.const 37))
+        "#).unwrap();
+        WasmPass::new("escaped", &wasm).unwrap().run_pass(&mut graph).unwrap();
+        assert_eq!(graph.node_weights().map(|n| n.name.as_ref()).collect::<Vec<_>>(), ["a", "b"]);
+
+        let wasm = wat::parse_str(r#"
+            (module
+                (memory (export "memory") 1)
+                (data (i32.const 0) "remove_node\ta\\x\n")
+                (func (export "alloc") (param i32) (result i32) i32.const 1024)
+                (func (export "run") (param i32 i32) (result i64) i64.const 17))
+        "#).unwrap();
+        let err = WasmPass::new("invalid", &wasm).unwrap().run_pass(&mut graph).unwrap_err();
+        assert!(err.to_string().contains("invalid escape"), "{err}");
+    }
+}
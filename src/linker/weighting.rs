@@ -0,0 +1,325 @@
+use std::collections::HashSet;
+use petgraph::algo::tarjan_scc;
+use petgraph::graph::NodeIndex;
+use crate::linker::csr::CsrGraph;
+use crate::linker::error::PassError;
+use crate::linker::node::{CallGraph, NodeLabel};
+use crate::linker::pass::Pass;
+use crate::linker::report::PassReport;
+
+/// Importance of every function of a graph. Implement it to plug a custom prioritization model
+/// into [`WeightsPass`], builtin strategies are listed by [`builtin_weighting`]
+pub trait Weighting: Send + Sync {
+    /// Weight of every node, indexed by node index. `targets` are the functions of interest,
+    /// strategies not using them ignore them. [`WeightsPass`] fails if there isn't a weight for every node
+    fn weights(&self, graph: &CallGraph, targets: &[NodeIndex]) -> Vec<f64>;
+
+    fn name(&self) -> String;
+
+    /// Whether [`Weighting::weights`] needs targets
+    fn needs_targets(&self) -> bool {
+        false
+    }
+}
+
+/// Number of targets reachable from the node, the node itself included.
+/// Targets are propagated as bitsets of 64 targets over strongly connected components,
+/// from callees to callers
+#[derive(Default)]
+pub struct ReachableTargets {}
+
+impl Weighting for ReachableTargets {
+    fn weights(&self, graph: &CallGraph, targets: &[NodeIndex]) -> Vec<f64> {
+        // Components come callees first, so edges between components lead to earlier ones
+        let sccs = tarjan_scc(graph);
+        let mut component = vec![0; graph.node_count()];
+        for (i, scc) in sccs.iter().enumerate() {
+            for node in scc {
+                component[node.index()] = i;
+            }
+        }
+        let mut calls = graph.raw_edges()
+            .iter()
+            .map(|edge| (component[edge.source().index()], component[edge.target().index()]))
+            .filter(|(caller, callee)| caller != callee)
+            .collect::<Vec<_>>();
+        calls.sort_unstable();
+        calls.dedup();
+
+        let mut targets = targets.to_vec();
+        targets.sort_unstable();
+        targets.dedup();
+        let mut counts = vec![0u32; sccs.len()];
+        for chunk in targets.chunks(64) {
+            let mut reached = vec![0u64; sccs.len()];
+            for (bit, target) in chunk.iter().enumerate() {
+                reached[component[target.index()]] |= 1 << bit;
+            }
+            for &(caller, callee) in &calls {
+                reached[caller] |= reached[callee];
+            }
+            for (count, reached) in counts.iter_mut().zip(reached) {
+                *count += reached.count_ones();
+            }
+        }
+        component.into_iter().map(|component| f64::from(counts[component])).collect()
+    }
+
+    fn name(&self) -> String {
+        "reachable targets".to_string()
+    }
+
+    fn needs_targets(&self) -> bool {
+        true
+    }
+}
+
+/// `1 / (1 + d)` for the number of calls `d` to the nearest target, 0 for nodes reaching none
+#[derive(Default)]
+pub struct InverseDistance {}
+
+impl Weighting for InverseDistance {
+    #[allow(clippy::cast_precision_loss)]
+    fn weights(&self, graph: &CallGraph, targets: &[NodeIndex]) -> Vec<f64> {
+        let callers = CsrGraph::from_graph(graph).reversed();
+        callers.distances(targets.iter().copied())
+            .into_iter()
+            .map(|distance| distance.map_or(0.0, |distance| 1.0 / (1 + distance) as f64))
+            .collect()
+    }
+
+    fn name(&self) -> String {
+        "inverse distance".to_string()
+    }
+
+    fn needs_targets(&self) -> bool {
+        true
+    }
+}
+
+/// Number of incoming and outgoing edges, parallel edges included
+#[derive(Default)]
+pub struct Degree {}
+
+impl Weighting for Degree {
+    #[allow(clippy::cast_precision_loss)]
+    fn weights(&self, graph: &CallGraph, _targets: &[NodeIndex]) -> Vec<f64> {
+        let mut degrees = vec![0usize; graph.node_count()];
+        for edge in graph.raw_edges() {
+            degrees[edge.source().index()] += 1;
+            degrees[edge.target().index()] += 1;
+        }
+        degrees.into_iter().map(|degree| degree as f64).collect()
+    }
+
+    fn name(&self) -> String {
+        "degree".to_string()
+    }
+}
+
+/// PageRank with rank flowing from callers to callees, so functions called from many
+/// important places rank high. Ranks of all nodes sum up to 1
+pub struct PageRank {
+    pub damping: f64,
+    pub iterations: usize,
+}
+
+impl Default for PageRank {
+    fn default() -> Self {
+        Self { damping: 0.85, iterations: 50 }
+    }
+}
+
+impl Weighting for PageRank {
+    #[allow(clippy::cast_precision_loss)]
+    fn weights(&self, graph: &CallGraph, _targets: &[NodeIndex]) -> Vec<f64> {
+        let count = graph.node_count();
+        if count == 0 {
+            return vec![];
+        }
+        let csr = CsrGraph::from_graph(graph);
+        let out_degrees = graph.node_indices().map(|idx| csr.neighbors(idx).count()).collect::<Vec<_>>();
+        let mut ranks = vec![1.0 / count as f64; count];
+        for _ in 0..self.iterations {
+            // Nodes without callees spread their rank over all nodes
+            let dangling = ranks.iter()
+                .zip(&out_degrees)
+                .filter(|&(_, &degree)| degree == 0)
+                .map(|(rank, _)| rank)
+                .sum::<f64>();
+            let base = (1.0 - self.damping + self.damping * dangling) / count as f64;
+            let mut next = vec![base; count];
+            for idx in graph.node_indices() {
+                let degree = out_degrees[idx.index()];
+                if degree == 0 {
+                    continue;
+                }
+                let share = self.damping * ranks[idx.index()] / degree as f64;
+                for callee in csr.neighbors(idx) {
+                    next[callee.index()] += share;
+                }
+            }
+            ranks = next;
+        }
+        ranks
+    }
+
+    fn name(&self) -> String {
+        format!("pagerank(damping {}, {} iterations)", self.damping, self.iterations)
+    }
+}
+
+/// Names of strategies available from [`builtin_weighting`]
+pub const BUILTIN_WEIGHTINGS: [&str; 4] = ["reachable_targets", "inverse_distance", "degree", "pagerank"];
+
+/// Builtin strategy by its config name, see [`BUILTIN_WEIGHTINGS`]
+#[must_use]
+pub fn builtin_weighting(name: &str) -> Option<Box<dyn Weighting>> {
+    match name {
+        "reachable_targets" => Some(Box::new(ReachableTargets::default())),
+        "inverse_distance" => Some(Box::new(InverseDistance::default())),
+        "degree" => Some(Box::new(Degree::default())),
+        "pagerank" => Some(Box::new(PageRank::default())),
+        _ => None,
+    }
+}
+
+/// Nodes of `graph` with names in `names`
+#[must_use]
+pub fn find_targets(graph: &CallGraph, names: &HashSet<String>) -> Vec<NodeIndex> {
+    graph.node_indices().filter(|&idx| names.contains(graph[idx].name())).collect()
+}
+
+/// Store the weight of every node from a [`Weighting`] in its `weight` attribute
+pub struct WeightsPass {
+    weighting: Box<dyn Weighting>,
+    targets: HashSet<String>,
+}
+
+impl WeightsPass {
+    #[must_use]
+    pub fn new(weighting: Box<dyn Weighting>, targets: HashSet<String>) -> Self {
+        Self { weighting, targets }
+    }
+}
+
+impl Pass for WeightsPass {
+    fn run_pass(&self, graph: &mut CallGraph) -> Result<PassReport, PassError> {
+        let targets = find_targets(graph, &self.targets);
+        let weights = self.weighting.weights(graph, &targets);
+        if weights.len() != graph.node_count() {
+            return Err(PassError::Other(format!(
+                "weighting {} returned {} weights for {} nodes",
+                self.weighting.name(),
+                weights.len(),
+                graph.node_count()
+            ).into()));
+        }
+        let mut weighted = 0;
+        for (node, weight) in graph.node_weights_mut().zip(weights) {
+            if weight != 0.0 {
+                weighted += 1;
+            }
+            node.set_attr("weight", weight.to_string());
+        }
+        Ok(PassReport::default().with("weighted", weighted).with("targets", targets.len()))
+    }
+
+    fn name(&self) -> String {
+        format!("weights({})", self.weighting.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::linker::edge::EdgeKind;
+    use crate::linker::node::Node;
+
+    fn graph() -> (CallGraph, [NodeIndex; 4]) {
+        let mut graph = CallGraph::new();
+        let nodes = ["main", "a", "b", "c"].map(|name| graph.add_node(Node::from(name)));
+        let [main, a, b, c] = nodes;
+        for (from, to) in [(main, a), (main, b), (a, c), (b, c)] {
+            graph.add_edge(from, to, EdgeKind::Direct.into());
+        }
+        (graph, nodes)
+    }
+
+    #[test]
+    fn test_weightings() {
+        let (graph, [main, a, b, c]) = graph();
+        assert_eq!(ReachableTargets::default().weights(&graph, &[a, c]), [2.0, 2.0, 1.0, 1.0]);
+        assert_eq!(InverseDistance::default().weights(&graph, &[a]), [0.5, 1.0, 0.0, 0.0]);
+        assert_eq!(Degree::default().weights(&graph, &[]), [2.0, 2.0, 2.0, 2.0]);
+
+        let ranks = PageRank::default().weights(&graph, &[]);
+        assert!((ranks.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        assert!(ranks[c.index()] > ranks[a.index()] && ranks[a.index()] > ranks[main.index()]);
+        assert!((ranks[a.index()] - ranks[b.index()]).abs() < 1e-12);
+        assert!(builtin_weighting("random").is_none());
+    }
+
+    #[test]
+    fn test_many_targets() {
+        // A cycle calling a chain, with more targets than fit in one bitset
+        let mut graph = CallGraph::new();
+        let nodes = (0..200).map(|i| graph.add_node(Node::from(format!("f{i}").as_str()))).collect::<Vec<_>>();
+        for i in 0..199 {
+            graph.add_edge(nodes[i], nodes[i + 1], EdgeKind::Direct.into());
+        }
+        graph.add_edge(nodes[49], nodes[0], EdgeKind::Direct.into());
+        let targets = nodes.iter().copied().step_by(2).chain([nodes[0]]).collect::<Vec<_>>();
+        let weights = ReachableTargets::default().weights(&graph, &targets);
+        let callers = CsrGraph::from_graph(&graph).reversed();
+        for (i, &weight) in weights.iter().enumerate() {
+            let expected = targets.iter()
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .filter(|&&target| callers.reachable([target])[i])
+                .count();
+            assert_eq!(weight, expected as f64);
+        }
+        assert_eq!((weights[0], weights[49], weights[50], weights[199]), (100.0, 100.0, 75.0, 0.0));
+
+        let distances = InverseDistance::default().weights(&graph, &[nodes[10], nodes[60]]);
+        assert_eq!((distances[10], distances[9], distances[55], distances[40]), (1.0, 0.5, 1.0 / 6.0, 1.0 / 21.0));
+    }
+
+    #[test]
+    fn test_weights_pass() {
+        struct Constant;
+        impl Weighting for Constant {
+            fn weights(&self, graph: &CallGraph, _targets: &[NodeIndex]) -> Vec<f64> {
+                vec![1.5; graph.node_count()]
+            }
+
+            fn name(&self) -> String {
+                "constant".to_string()
+            }
+        }
+
+        let (mut graph, [main, ..]) = graph();
+        let report = WeightsPass::new(Box::new(Constant), HashSet::new()).run_pass(&mut graph).unwrap();
+        assert_eq!(report.custom["weighted"], 4);
+        assert_eq!(graph[main].attrs["weight"], "1.5");
+
+        let pass = WeightsPass::new(builtin_weighting("reachable_targets").unwrap(), HashSet::from(["c".to_string()]));
+        pass.run_pass(&mut graph).unwrap();
+        assert_eq!(graph[main].attrs["weight"], "1");
+
+        struct Short;
+        impl Weighting for Short {
+            fn weights(&self, _graph: &CallGraph, _targets: &[NodeIndex]) -> Vec<f64> {
+                vec![2.0]
+            }
+
+            fn name(&self) -> String {
+                "short".to_string()
+            }
+        }
+        let err = WeightsPass::new(Box::new(Short), HashSet::new()).run_pass(&mut graph).unwrap_err();
+        assert_eq!(err.to_string(), "weighting short returned 1 weights for 4 nodes");
+        assert_eq!(graph[main].attrs["weight"], "1");
+    }
+}
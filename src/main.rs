@@ -1,64 +1,502 @@
-use clap::Parser;
-use graphviz_rust::parse;
-use petgraph::dot::{Config, Dot};
-use std::fs::{read_to_string, File};
-use std::path::PathBuf;
-use std::{fs, io};
-use std::io::{BufRead, BufReader};
-use log::{debug, info, warn};
-use petgraph::Graph;
-use inv_call_extract::linker::config::parse_config_file;
-use crate::linker::conversion::graphviz_to_graph;
-use crate::linker::graph_link::link_all_graphs;
-
-pub mod linker;
+use clap::{Parser, Subcommand};
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::io;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::sync::Mutex;
+use fancy_regex::Regex;
+use log::warn;
+use petgraph::Direction;
+use petgraph::graph::NodeIndex;
+use tracing_subscriber::fmt::format::FmtSpan;
+use inv_call_extract::linker::analysis::{
+    call_depths, common_ancestors, components, cycles, hot_paths, must_pass_through, paths_subgraph, similarity,
+    syscall_surface, write_reachability_csv
+};
+#[cfg(feature = "dot")]
+use inv_call_extract::linker::conversion::round_trip;
+use inv_call_extract::linker::conversion::{save_dot, DuplicateNodes};
+use inv_call_extract::linker::import::read_graphs;
+use inv_call_extract::linker::import::{InputFormat, MultiGraph};
+use inv_call_extract::linker::node::CallGraph;
+#[cfg(feature = "plugins")]
+use inv_call_extract::linker::plugin::load_plugin;
+use inv_call_extract::linker::profile::write_json;
+#[cfg(feature = "alloc-profile")]
+use inv_call_extract::linker::profile::TrackingAllocator;
+use inv_call_extract::linker::registry::PassRegistry;
+use inv_call_extract::linker::runner::{run, write_summary, RunOptions};
+use inv_call_extract::linker::weighting::{builtin_weighting, BUILTIN_WEIGHTINGS};
+
+/// Counts allocations while `--profile` is enabled
+#[cfg(feature = "alloc-profile")]
+#[global_allocator]
+static ALLOCATOR: TrackingAllocator = TrackingAllocator;
 
 /// Program that builds inverse call graph with required functions only.
 /// It can be used for creating new .dot graph, listing all ancestors
 /// and weighting every function according to its importance.
 #[derive(Parser)]
-#[command(version, about)]
+#[command(version, about, subcommand_negates_reqs = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// File with list of dot files to process.
-    /// If not provided, paths to dot files are read from stdin
+    /// Listed directories are searched for dot files recursively.
+    /// If neither this nor `--recursive` is provided, paths to dot files are read from stdin
     #[clap(short, long)]
     dots: Option<PathBuf>,
-    
+
+    /// Directory to search for dot files recursively, can be repeated
     #[clap(short, long)]
-    config: PathBuf,
+    recursive: Vec<PathBuf>,
+
+    /// Skip discovered files and directories with paths matching this regex
+    #[clap(short = 'x', long)]
+    exclude: Option<String>,
+    
+    #[clap(short, long, required_unless_present = "list_passes")]
+    config: Option<PathBuf>,
+
+    /// Shared library with additional passes, can be repeated
+    #[cfg(feature = "plugins")]
+    #[clap(long)]
+    plugin: Vec<PathBuf>,
+
+    /// Write JSON lines traces with a span per input file and per pass to this file
+    #[clap(long)]
+    trace: Option<PathBuf>,
+
+    /// Write wall time and allocation statistics of parsing, every pass, linking and writing
+    /// to this file as JSON
+    #[clap(long)]
+    profile: Option<PathBuf>,
+
+    /// Remember regex matches of node names, so passes sharing a regex list test every name once
+    #[clap(long)]
+    regex_cache: bool,
+
+    /// Keep parsed inputs in this directory, unchanged inputs are not parsed again on later runs
+    #[clap(long)]
+    cache_dir: Option<PathBuf>,
+
+    /// Keep linked chunks of inputs in the cache directory and only re-link chunks with changed inputs.
+    /// Needs a link step in the config
+    #[clap(long, requires = "cache_dir")]
+    incremental: bool,
+
+    /// Skip duplicate calls between the same functions while reading dot inputs and linking,
+    /// for configs starting with `unique_edges`
+    #[clap(long)]
+    unique_edges: bool,
+
+    /// What to do with nodes declared several times in a dot input with different attribute values
+    #[clap(long, value_enum, default_value_t = DuplicateNodes::Merge)]
+    duplicate_nodes: DuplicateNodes,
+
+    /// Check graphs after every pass and after linking, failing on the first step leaving
+    /// a broken graph or a report not matching the size change. Emptied graphs are logged
+    #[clap(long)]
+    paranoid: bool,
+
+    /// Leave out inputs that fail to read or parse and process the rest.
+    /// The run still fails at the end, listing the skipped inputs
+    #[clap(long)]
+    skip_bad_inputs: bool,
+
+    /// Leave out input files larger than this many bytes with a warning,
+    /// they don't fail the run
+    #[clap(long)]
+    max_file_size: Option<u64>,
+
+    /// Leave out inputs with more nodes in all their graphs with a warning, reading stops at the limit.
+    /// They don't fail the run. Not supported with --incremental
+    #[clap(long)]
+    max_nodes_per_file: Option<usize>,
+
+    /// Write a JSON summary of the run to file: graphs read, pass reports,
+    /// written and failed outputs, skipped and oversized inputs
+    #[clap(long)]
+    summary: Option<PathBuf>,
+
+    /// Write in and out degree histograms of the linked graph to file,
+    /// as CSV if it ends with `.csv` and as a text table otherwise
+    #[clap(long)]
+    degree_hist: Option<PathBuf>,
+
+    /// Print all config pass keywords and exit
+    #[clap(long)]
+    list_passes: bool,
 
     /// Write extracted call graph in graphviz format to file
     /// Default value is "out.dot"
     #[clap(short, long, default_value = "out.dot")]
     save_extracted: PathBuf,
+
+    /// Format of all input files.
+    /// If not provided, it is guessed from every file extension
+    #[clap(short, long, value_enum)]
+    format: Option<InputFormat>,
+
+    /// What to do with dot files containing several graphs
+    #[clap(short, long, value_enum, default_value_t = MultiGraph::Merge)]
+    multi_graph: MultiGraph,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Read graphs, write them as dot and parse the output again,
+    /// listing nodes and edges lost or changed on the way
+    Verify {
+        #[clap(required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// List recursion cycles of a graph, largest first:
+    /// strongly connected components with several functions and functions calling themselves
+    Cycles {
+        file: PathBuf,
+    },
+    /// List weakly connected components of a graph, largest first,
+    /// with their size and the node with the most edges
+    Components {
+        file: PathBuf,
+    },
+    /// Rank the best paths from entry functions to target functions: heaviest first
+    /// if parallel edges count calls, shortest first otherwise
+    Hotpaths {
+        file: PathBuf,
+        /// File with names of target functions
+        #[clap(short, long)]
+        targets: PathBuf,
+        /// File with names of entry functions, functions without callers by default
+        #[clap(short, long)]
+        entries: Option<PathBuf>,
+        /// Number of paths
+        #[clap(short, default_value_t = 10)]
+        k: usize,
+        /// Write the graph of listed paths only to this dot file
+        #[clap(long)]
+        dot: Option<PathBuf>,
+    },
+    /// List functions from which all target functions are reachable, nearest first:
+    /// shared dispatchers to instrument once
+    CommonCallers {
+        file: PathBuf,
+        /// File with names of target functions
+        #[clap(short, long)]
+        targets: PathBuf,
+        /// Number of functions
+        #[clap(short, default_value_t = 10)]
+        k: usize,
+    },
+    /// Write minimum and maximum call depths of every function reachable from the entries as TSV
+    Depth {
+        file: PathBuf,
+        /// File with names of entry functions, functions without callers by default
+        #[clap(short, long)]
+        entries: Option<PathBuf>,
+        /// Write the TSV to this file instead of stdout
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+        /// Write the graph with `min_depth` and `max_depth` node attributes to this dot file
+        #[clap(long)]
+        dot: Option<PathBuf>,
+    },
+    /// Compare two graphs: Jaccard overlap of node names and of edges between names,
+    /// and the largest regions present in one graph only
+    Similarity {
+        first: PathBuf,
+        second: PathBuf,
+        /// Number of regions
+        #[clap(short, default_value_t = 10)]
+        k: usize,
+    },
+    /// List syscalls of a kernel graph with the number of functions they reach
+    /// and the target functions reachable from them, as TSV
+    Syscalls {
+        file: PathBuf,
+        /// File with names of target functions
+        #[clap(short, long)]
+        targets: Option<PathBuf>,
+    },
+    /// Weight functions by importance and list the heaviest ones
+    Weights {
+        file: PathBuf,
+        /// Weighting strategy: reachable_targets, inverse_distance, degree or pagerank
+        #[clap(short, long)]
+        strategy: String,
+        /// File with names of target functions, needed by reachable_targets and inverse_distance
+        #[clap(short, long)]
+        targets: Option<PathBuf>,
+        /// Number of functions
+        #[clap(short, default_value_t = 10)]
+        k: usize,
+    },
+    /// List functions every path from the entry function to the target function goes through,
+    /// in path order: places for a single hook covering all routes
+    MustPass {
+        file: PathBuf,
+        entry: String,
+        target: String,
+    },
+    /// Export which of the listed functions reach which as a CSV matrix, a row per caller
+    Reachability {
+        file: PathBuf,
+        /// File with names of functions
+        #[clap(short, long)]
+        symbols: PathBuf,
+        /// Write the CSV to this file instead of stdout
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+/// Nodes of `graph` named in `list`, a file with whitespace separated names. Missing names are warned about
+fn find_nodes(graph: &CallGraph, list: &Path) -> io::Result<Vec<NodeIndex>> {
+    let by_name = graph.node_indices().map(|idx| (&*graph[idx].name, idx)).collect::<HashMap<_, _>>();
+    let mut nodes = vec![];
+    for name in std::fs::read_to_string(list)?.split_whitespace() {
+        match by_name.get(name) {
+            Some(&idx) => nodes.push(idx),
+            None => warn!("No function \"{name}\" of {} in the graph", list.display()),
+        }
+    }
+    Ok(nodes)
+}
+
+/// Nodes named in `entries`, functions without callers if not provided
+fn find_entries(graph: &CallGraph, entries: Option<&Path>) -> io::Result<Vec<NodeIndex>> {
+    match entries {
+        Some(entries) => find_nodes(graph, entries),
+        None => Ok(graph.externals(Direction::Incoming).collect()),
+    }
+}
+
+fn print_depths(
+    path: &Path,
+    format: Option<InputFormat>,
+    entries: Option<&Path>,
+    output: Option<&Path>,
+    dot: Option<&Path>
+) -> io::Result<()> {
+    let mut graph = read_merged(path, format)?;
+    let entries = find_entries(&graph, entries)?;
+    let depths = call_depths(&graph, &entries);
+    let mut out: Box<dyn Write> = match output {
+        Some(output) => Box::new(BufWriter::new(File::create(output)?)),
+        None => Box::new(io::stdout().lock()),
+    };
+    writeln!(out, "function\tmin_depth\tmax_depth")?;
+    for (idx, depth) in graph.node_indices().zip(&depths) {
+        if let Some(depth) = depth {
+            writeln!(out, "{}\t{}\t{}", graph[idx].name, depth.min, depth.max)?;
+        }
+    }
+    out.flush()?;
+    if let Some(dot) = dot {
+        for (node, depth) in graph.node_weights_mut().zip(depths) {
+            if let Some(depth) = depth {
+                node.attrs.insert("min_depth".to_string(), depth.min.to_string());
+                node.attrs.insert("max_depth".to_string(), depth.max.to_string());
+            }
+        }
+        save_dot(dot, &graph)?;
+    }
+    Ok(())
+}
+
+fn print_similarity(first: &Path, second: &Path, format: Option<InputFormat>, k: usize) -> io::Result<()> {
+    let similarity = similarity(&read_merged(first, format)?, &read_merged(second, format)?);
+    for (kind, overlap) in [("nodes", similarity.nodes), ("edges", similarity.edges)] {
+        println!(
+            "{kind}: {:.4} (common {}, only in first {}, only in second {})",
+            overlap.jaccard(),
+            overlap.common,
+            overlap.only_first,
+            overlap.only_second
+        );
+    }
+    for region in similarity.regions.iter().take(k) {
+        println!("differing region of {} nodes around \"{}\"", region.size, region.representative);
+    }
+    Ok(())
+}
+
+fn print_syscalls(path: &Path, format: Option<InputFormat>, targets: Option<&Path>) -> io::Result<()> {
+    let graph = read_merged(path, format)?;
+    let targets = targets.map_or(Ok(vec![]), |targets| find_nodes(&graph, targets))?;
+    let surface = syscall_surface(&graph, &targets);
+    if surface.is_empty() {
+        warn!("No syscall entry points in {}", path.display());
+    }
+    println!("syscall\treachable\ttargets");
+    for syscall in surface {
+        let targets = syscall.targets.iter().map(|&idx| &*graph[idx].name).collect::<Vec<_>>();
+        println!("{}\t{}\t{}", syscall.syscall, syscall.reachable, targets.join(","));
+    }
+    Ok(())
+}
+
+fn print_weights(
+    path: &Path,
+    format: Option<InputFormat>,
+    strategy: &str,
+    targets: Option<&Path>,
+    k: usize
+) -> io::Result<()> {
+    let Some(weighting) = builtin_weighting(strategy) else {
+        let message = format!("Unknown weighting \"{strategy}\", expected one of {}", BUILTIN_WEIGHTINGS.join(", "));
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+    };
+    if weighting.needs_targets() && targets.is_none() {
+        let message = format!("{} weighting needs --targets", weighting.name());
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+    }
+    let graph = read_merged(path, format)?;
+    let targets = targets.map_or(Ok(vec![]), |targets| find_nodes(&graph, targets))?;
+    let mut weights = graph.node_indices().zip(weighting.weights(&graph, &targets)).collect::<Vec<_>>();
+    weights.sort_by(|(a, a_weight), (b, b_weight)| {
+        b_weight.total_cmp(a_weight).then_with(|| graph[*a].name.cmp(&graph[*b].name))
+    });
+    for (idx, weight) in weights.into_iter().take(k) {
+        println!("{weight}\t{}", graph[idx].name);
+    }
+    Ok(())
 }
 
-fn run_passes(args: &Args, objects: &mut Vec<(PathBuf, Graph<String, ()>)>) -> io::Result<()> {
-    let (before_link, should_link, after_link) = parse_config_file(&args.config)?;
-    for pass in before_link {
-        info!("Running pass before link: {}", pass.name());
-        objects.iter_mut()
-            .for_each(|(_, graph)| pass.run_pass(graph));
+fn print_must_pass(path: &Path, format: Option<InputFormat>, entry: &str, target: &str) -> io::Result<()> {
+    let graph = read_merged(path, format)?;
+    let find = |name: &str| graph.node_indices().find(|&idx| &*graph[idx].name == name).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, format!("No function \"{name}\" in {}", path.display()))
+    });
+    let (entry_idx, target_idx) = (find(entry)?, find(target)?);
+    let Some(chain) = must_pass_through(&graph, entry_idx, target_idx) else {
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("\"{entry}\" doesn't reach \"{target}\"")));
+    };
+    let between = chain.get(1..chain.len().saturating_sub(1)).unwrap_or_default();
+    if between.is_empty() {
+        println!("no function between \"{entry}\" and \"{target}\" lies on every path");
     }
-    if should_link {
-        let linked = link_all_graphs(
-            &objects.iter().map(|p| p.1.clone()).collect::<Vec<_>>()
+    for &idx in between {
+        println!("{}", graph[idx].name);
+    }
+    Ok(())
+}
+
+fn print_common_callers(path: &Path, format: Option<InputFormat>, targets: &Path, k: usize) -> io::Result<()> {
+    let graph = read_merged(path, format)?;
+    let targets = find_nodes(&graph, targets)?;
+    for ancestor in common_ancestors(&graph, &targets).into_iter().take(k) {
+        println!(
+            "{} (farthest target {}, total distance {})",
+            graph[ancestor.node].name,
+            ancestor.distance,
+            ancestor.total_distance
         );
-        *objects = vec![(args.save_extracted.clone(), linked)];
-        info!("Linked graphs");
     }
-    for pass in after_link {
-        info!("Running pass after link: {}", pass.name());
-        objects.iter_mut()
-            .for_each(|(_, graph)| pass.run_pass(graph));
+    Ok(())
+}
+
+fn print_reachability(
+    path: &Path,
+    format: Option<InputFormat>,
+    symbols: &Path,
+    output: Option<&Path>
+) -> io::Result<()> {
+    let graph = read_merged(path, format)?;
+    let symbols = find_nodes(&graph, symbols)?;
+    match output {
+        Some(output) => {
+            let mut out = BufWriter::new(File::create(output)?);
+            write_reachability_csv(&graph, &symbols, &mut out)?;
+            out.flush()
+        }
+        None => write_reachability_csv(&graph, &symbols, &mut io::stdout().lock()),
+    }
+}
+
+fn print_hot_paths(
+    path: &Path,
+    format: Option<InputFormat>,
+    targets: &Path,
+    entries: Option<&Path>,
+    k: usize,
+    dot: Option<&Path>
+) -> io::Result<()> {
+    let graph = read_merged(path, format)?;
+    let targets = find_nodes(&graph, targets)?;
+    let entries = find_entries(&graph, entries)?;
+    let paths = hot_paths(&graph, &entries, &targets, k);
+    for (rank, hot_path) in paths.iter().enumerate() {
+        let names = hot_path.nodes.iter().map(|&idx| &*graph[idx].name).collect::<Vec<_>>();
+        println!("{}. {} (length {}, weight {})", rank + 1, names.join(" -> "), hot_path.len(), hot_path.weight);
+    }
+    if let Some(dot) = dot {
+        save_dot(dot, &paths_subgraph(&graph, &paths))?;
+    }
+    Ok(())
+}
+
+/// Read all graphs of `path` as one
+fn read_merged(path: &Path, format: Option<InputFormat>) -> io::Result<CallGraph> {
+    let format = format.unwrap_or_else(|| InputFormat::detect(path));
+    let [graph] = <[_; 1]>::try_from(read_graphs(path, format, MultiGraph::Merge)?)
+        .unwrap_or_else(|_| unreachable!("merged inputs are one graph"));
+    Ok(graph)
+}
+
+fn print_components(path: &Path, format: Option<InputFormat>) -> io::Result<()> {
+    for component in components(&read_merged(path, format)?) {
+        println!("{} {}", component.size, component.representative);
+    }
+    Ok(())
+}
+
+fn print_cycles(path: &Path, format: Option<InputFormat>) -> io::Result<()> {
+    let cycles = cycles(&read_merged(path, format)?);
+    if cycles.is_empty() {
+        println!("no cycles");
+    }
+    for cycle in cycles {
+        match &cycle[..] {
+            [name] => println!("{name} calls itself"),
+            _ => println!("{} functions: {}", cycle.len(), cycle.join(", ")),
+        }
     }
+    Ok(())
+}
 
+#[cfg(feature = "dot")]
+fn verify(files: &[PathBuf], format: Option<InputFormat>) -> io::Result<()> {
+    let mut changed = 0;
+    for path in files {
+        let format = format.unwrap_or_else(|| InputFormat::detect(path));
+        for (i, graph) in read_graphs(path, format, MultiGraph::Split)?.iter().enumerate() {
+            let diff = round_trip(graph)?;
+            if diff.is_clean() {
+                println!("{} graph {i}: ok", path.display());
+            } else {
+                changed += 1;
+                print!("{} graph {i}: changed\n{diff}", path.display());
+            }
+        }
+    }
+    if changed > 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{changed} graphs changed on a round trip")));
+    }
     Ok(())
 }
 
-fn read_dot_graphs(args: &Args) -> io::Result<Vec<(PathBuf, Graph<String, ()>)>> {
-    let mut objects: Vec<(PathBuf, Graph<String, ()>)> = vec![];
-    let files = match &args.dots {
+#[cfg(not(feature = "dot"))]
+fn verify(_files: &[PathBuf], _format: Option<InputFormat>) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "verify needs the dot feature"))
+}
+
+fn listed_inputs(args: &Args) -> io::Result<Vec<PathBuf>> {
+    let listed = match &args.dots {
+        None if !args.recursive.is_empty() => vec![],
         None => {
             BufReader::new(io::stdin())
                 .lines()
@@ -72,37 +510,102 @@ fn read_dot_graphs(args: &Args) -> io::Result<Vec<(PathBuf, Graph<String, ()>)>>
                 .collect::<Vec<_>>()
         }
     };
-    for dot in &files {
-        debug!("reading {dot}");
-        let path = PathBuf::from(dot);
-        let Ok(graph) = parse(&read_to_string(path.clone())?) else {
-            panic!("Failed to parse .dot graph: {dot:?}");
-        };
-        let mut output_path = path;
-        output_path.set_extension("out.dot");
-        objects.push((
-            output_path,
-            graphviz_to_graph(&graph)
-        ));
-    }
-    Ok(objects)
-
+    Ok(listed.iter().map(PathBuf::from).chain(args.recursive.iter().cloned()).collect())
 }
 
 fn main() -> io::Result<()> {
     colog::init();
     let args = Args::parse();
-    // Keep objects with names to save them later if needed.
-    let mut graphs = read_dot_graphs(&args)?;
-
-    // Run deg pass on extracted subgraph
-    run_passes(&args, &mut graphs)?;
+    if let Some(trace) = &args.trace {
+        let subscriber = tracing_subscriber::fmt()
+            .json()
+            .with_span_events(FmtSpan::CLOSE)
+            .with_writer(Mutex::new(File::create(trace)?))
+            .finish();
+        tracing::subscriber::set_global_default(subscriber).map_err(io::Error::other)?;
+    }
+    #[allow(unused_mut)]
+    let mut registry = PassRegistry::builtin();
+    #[cfg(feature = "plugins")]
+    for plugin in &args.plugin {
+        load_plugin(plugin, &mut registry)?;
+    }
+    match &args.command {
+        Some(Command::Verify { files }) => return verify(files, args.format),
+        Some(Command::Cycles { file }) => return print_cycles(file, args.format),
+        Some(Command::Components { file }) => return print_components(file, args.format),
+        Some(Command::CommonCallers { file, targets, k }) => {
+            return print_common_callers(file, args.format, targets, *k);
+        }
+        Some(Command::Depth { file, entries, output, dot }) => {
+            return print_depths(file, args.format, entries.as_deref(), output.as_deref(), dot.as_deref());
+        }
+        Some(Command::Similarity { first, second, k }) => {
+            return print_similarity(first, second, args.format, *k);
+        }
+        Some(Command::Syscalls { file, targets }) => return print_syscalls(file, args.format, targets.as_deref()),
+        Some(Command::Weights { file, strategy, targets, k }) => {
+            return print_weights(file, args.format, strategy, targets.as_deref(), *k);
+        }
+        Some(Command::MustPass { file, entry, target }) => return print_must_pass(file, args.format, entry, target),
+        Some(Command::Reachability { file, symbols, output }) => {
+            return print_reachability(file, args.format, symbols, output.as_deref());
+        }
+        Some(Command::Hotpaths { file, targets, entries, k, dot }) => {
+            return print_hot_paths(file, args.format, targets, entries.as_deref(), *k, dot.as_deref());
+        }
+        None => {}
+    }
+    if args.list_passes {
+        registry.keywords().for_each(|keyword| println!("{keyword}"));
+        return Ok(());
+    }
+    let Some(config) = &args.config else {
+        unreachable!("clap requires config without --list-passes or a subcommand");
+    };
+    let mut options = RunOptions::new(listed_inputs(&args)?, config.clone());
+    options.exclude = args.exclude
+        .as_deref()
+        .map(Regex::new)
+        .transpose()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    options.registry = registry;
+    options.linked_output = args.save_extracted;
+    options.format = args.format;
+    options.multi_graph = args.multi_graph;
+    options.regex_cache = args.regex_cache;
+    options.cache_dir = args.cache_dir;
+    options.profile = args.profile.is_some();
+    options.incremental = args.incremental;
+    options.unique_edges = args.unique_edges;
+    options.duplicate_nodes = args.duplicate_nodes;
+    options.paranoid = args.paranoid;
+    options.skip_bad_inputs = args.skip_bad_inputs;
+    options.max_file_size = args.max_file_size;
+    options.max_nodes_per_file = args.max_nodes_per_file;
+    options.degree_hist = args.degree_hist;
 
-    for (save_to, gr) in graphs {
-        let dot_graph = Dot::with_config(&gr, &[Config::EdgeNoLabel]);
-        let _ = fs::write(save_to, format!("{dot_graph:?}")).inspect_err(|err| {
-            warn!("Failed to write .dot file: {err}");
-        });
+    let handler_cancel = options.cancel.clone();
+    ctrlc::set_handler(move || {
+        warn!("Interrupted, stopping after the current pass step");
+        handler_cancel.cancel();
+    }).map_err(io::Error::other)?;
+    let summary = run(&options)?;
+    if let Some(profile) = &args.profile {
+        write_json(&mut File::create(profile)?, &summary.phases)?;
+    }
+    if let Some(path) = &args.summary {
+        write_summary(&mut File::create(path)?, &summary)?;
+    }
+    if !summary.skipped_inputs.is_empty() {
+        let skipped = summary.skipped_inputs
+            .iter()
+            .map(|(path, _)| path.display().to_string())
+            .collect::<Vec<_>>();
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Skipped {} bad inputs: {}", skipped.len(), skipped.join(", "))
+        ));
     }
     Ok(())
-}
+}
\ No newline at end of file